@@ -0,0 +1,101 @@
+use std::env;
+use std::process::Command;
+
+// The versions of libobjc2 that change which APIs are available, from oldest
+// to newest, with the feature that selects each one.
+const GNUSTEP_VERSIONS: &[((u32, u32), &str)] = &[
+    ((1, 7), "CARGO_FEATURE_GNUSTEP_1_7"),
+    ((2, 0), "CARGO_FEATURE_GNUSTEP_2_0"),
+    ((2, 1), "CARGO_FEATURE_GNUSTEP_2_1"),
+];
+
+// The cfgs read by the crate, enabled from each version: 1.7 added the ARC
+// functions, and 2.0 replaced the slot API used by the slot cache.
+const GNUSTEP_CFGS: &[((u32, u32), &str)] = &[
+    ((1, 7), "gnustep_1_7"),
+    ((2, 0), "gnustep_2_0"),
+];
+
+// The version assumed when it can't be probed, the oldest one the crate
+// supports.
+const DEFAULT_GNUSTEP_VERSION: (u32, u32) = (1, 7);
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG");
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS");
+    for &(_, cfg) in GNUSTEP_CFGS {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+    }
+    println!("cargo:rustc-check-cfg=cfg(gnustep_msgsend)");
+
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let other_runtime = target_os == "macos" || target_os == "ios" ||
+        env::var_os("CARGO_FEATURE_OBJFW").is_some() ||
+        env::var_os("CARGO_FEATURE_GCC_LIBOBJC").is_some() ||
+        env::var_os("CARGO_FEATURE_MOCK_RUNTIME").is_some();
+    if other_runtime {
+        return;
+    }
+
+    // A version selected with a feature takes precedence over the probe
+    let selected = GNUSTEP_VERSIONS.iter().rev()
+        .find(|&&(_, feature)| env::var_os(feature).is_some())
+        .map(|&(version, _)| version);
+    let version = selected
+        .or_else(probe_gnustep_version)
+        .unwrap_or(DEFAULT_GNUSTEP_VERSION);
+    for &(min_version, cfg) in GNUSTEP_CFGS {
+        if version >= min_version {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+
+    // libobjc2 implements objc_msgSend since 2.0, and on aarch64 since 2.1.
+    // It's also implemented for 32-bit x86, but returns structs differently
+    // than Apple's runtime, so lookups are still used there.
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let has_msgsend = match &*target_arch {
+        "x86_64" | "arm" => version >= (2, 0),
+        "aarch64" => version >= (2, 1),
+        _ => false,
+    };
+    if has_msgsend {
+        println!("cargo:rustc-cfg=gnustep_msgsend");
+    }
+}
+
+/// Probes the version of the installed libobjc2 with pkg-config, adding its
+/// library directory to the link search path.
+///
+/// The installed library is usually for the host, so like the pkg-config
+/// crate, this is skipped when cross-compiling unless `PKG_CONFIG_ALLOW_CROSS`
+/// is set.
+fn probe_gnustep_version() -> Option<(u32, u32)> {
+    let cross = env::var("TARGET").ok() != env::var("HOST").ok();
+    if cross && env::var_os("PKG_CONFIG_ALLOW_CROSS").map_or(true, |allow| allow == "0") {
+        return None;
+    }
+
+    let pkg_config = env::var("PKG_CONFIG").unwrap_or_else(|_| "pkg-config".to_owned());
+    let output = Command::new(&pkg_config)
+        .args(["--modversion", "libobjc"])
+        .output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8(output.stdout).ok()?;
+    let mut parts = version.trim().split('.').map(|part| part.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+
+    if let Ok(output) = Command::new(&pkg_config).args(["--libs-only-L", "libobjc"]).output() {
+        let flags = String::from_utf8_lossy(&output.stdout);
+        for dir in flags.split_whitespace().filter_map(|flag| flag.strip_prefix("-L")) {
+            println!("cargo:rustc-link-search=native={}", dir);
+        }
+    }
+
+    Some((major, minor))
+}
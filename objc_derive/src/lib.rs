@@ -0,0 +1,223 @@
+/*!
+A derive macro for the `Encode` trait of the objc crate, re-exported by it as
+`objc::Encode` when its `"derive"` feature is enabled.
+
+Types must have a C layout for their encoding to be accurate:
+
+* `#[repr(C)]` structs are encoded as a struct of their fields, like
+  `{CGPoint=dd}`, and `#[repr(C)]` unions as a union of their fields.
+  The tag name is the name of the type unless overridden with
+  `#[encode(name = "...")]`, to match the name used by Objective-C.
+* `#[repr(transparent)]` types are encoded as the field they wrap.
+* Enums without fields that are `#[repr(C)]` or have an integer repr, like
+  `#[repr(isize)]`, are encoded as that integer.
+
+Each field must implement `Encode`, and for generic types each type
+parameter is required to implement `Encode`. `EncodeExtended` is implemented
+too, with the same encoding.
+
+# Example
+
+```
+# use objc::Encode;
+#[derive(Encode)]
+#[encode(name = "_NSRange")]
+#[repr(C)]
+struct Range {
+    location: usize,
+    length: usize,
+}
+
+assert_eq!(Range::ENCODING.to_string(), "{_NSRange=QQ}");
+```
+
+Types without a C layout fail to compile:
+
+``` compile_fail
+# use objc::Encode;
+#[derive(Encode)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+```
+
+``` compile_fail
+# use objc::Encode;
+#[derive(Encode)]
+#[repr(C, packed)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+```
+
+``` compile_fail
+# use objc::Encode;
+#[derive(Encode)]
+#[repr(u8)]
+enum Value {
+    Int(i32),
+    Float(f32),
+}
+```
+*/
+
+#![crate_name = "objc_derive"]
+#![crate_type = "proc-macro"]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parenthesized, parse_macro_input, parse_quote};
+use syn::{Attribute, Data, DeriveInput, Error, Fields, GenericParam, Ident, LitStr, Type};
+
+/// The integer reprs with an Objective-C encoding.
+const INTEGER_REPRS: &[&str] = &[
+    "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+];
+
+/// Derives `Encode` for a type with a C layout.
+#[proc_macro_derive(Encode, attributes(encode))]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct Repr {
+    c: bool,
+    transparent: bool,
+    packed: bool,
+    integer: Option<Ident>,
+}
+
+fn parse_repr(attrs: &[Attribute]) -> syn::Result<Repr> {
+    let mut repr = Repr::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            let ident = match meta.path.get_ident() {
+                Some(ident) => ident.clone(),
+                None => return Err(meta.error("unrecognized repr")),
+            };
+            // Skip the arguments of `align(N)` and `packed(N)`
+            if meta.input.peek(syn::token::Paren) {
+                let content;
+                parenthesized!(content in meta.input);
+                content.parse::<TokenStream2>()?;
+            }
+            match &*ident.to_string() {
+                "C" => repr.c = true,
+                "transparent" => repr.transparent = true,
+                "packed" => repr.packed = true,
+                "align" => (),
+                int if INTEGER_REPRS.contains(&int) => repr.integer = Some(ident),
+                _ => return Err(Error::new_spanned(&ident,
+                    "repr has no Objective-C encoding")),
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr)
+}
+
+fn parse_name(attrs: &[Attribute]) -> syn::Result<Option<LitStr>> {
+    let mut name = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("encode")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(name)
+}
+
+fn is_phantom_data(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) =>
+            matches!(path.path.segments.last(), Some(seg) if seg.ident == "PhantomData"),
+        _ => false,
+    }
+}
+
+fn aggregate_encoding(variant: Ident, name: String, fields: &Fields) -> TokenStream2 {
+    let types = fields.iter().map(|field| &field.ty);
+    quote! {
+        ::objc::Encoding::#variant(#name, &[
+            #(<#types as ::objc::Encode>::ENCODING),*
+        ])
+    }
+}
+
+fn transparent_encoding(input: &DeriveInput, fields: &Fields) -> syn::Result<TokenStream2> {
+    let mut wrapped = fields.iter().filter(|field| !is_phantom_data(&field.ty));
+    match (wrapped.next(), wrapped.next()) {
+        (Some(field), None) => {
+            let ty = &field.ty;
+            Ok(quote!(<#ty as ::objc::Encode>::ENCODING))
+        }
+        _ => Err(Error::new_spanned(&input.ident,
+            "transparent types must wrap one field besides `PhantomData`s")),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let repr = parse_repr(&input.attrs)?;
+    let name = parse_name(&input.attrs)?;
+    if repr.packed {
+        return Err(Error::new_spanned(&input.ident,
+            "packed types have no Objective-C encoding"));
+    }
+    let is_enum = matches!(input.data, Data::Enum(_));
+    if let (Some(name), true) = (&name, !repr.c || repr.transparent || is_enum) {
+        return Err(Error::new_spanned(name,
+            "only `#[repr(C)]` structs and unions have a name in their encoding"));
+    }
+    let tag = name.map_or_else(|| input.ident.to_string(), |name| name.value());
+
+    let encoding = match input.data {
+        Data::Struct(ref data) if repr.transparent =>
+            transparent_encoding(input, &data.fields)?,
+        Data::Struct(ref data) if repr.c =>
+            aggregate_encoding(parse_quote!(Struct), tag, &data.fields),
+        Data::Union(ref data) if repr.c =>
+            aggregate_encoding(parse_quote!(Union), tag, &Fields::Named(data.fields.clone())),
+        Data::Enum(ref data) if repr.c || repr.integer.is_some() => {
+            if let Some(variant) = data.variants.iter()
+                    .find(|v| !matches!(v.fields, Fields::Unit)) {
+                return Err(Error::new_spanned(&variant.ident,
+                    "enums with fields have no Objective-C encoding"));
+            }
+            let integer = match repr.integer {
+                Some(integer) => quote!(#integer),
+                None => quote!(::core::ffi::c_int),
+            };
+            quote!(<#integer as ::objc::Encode>::ENCODING)
+        }
+        _ => return Err(Error::new_spanned(&input.ident,
+            "`#[derive(Encode)]` requires a `#[repr(C)]`, `#[repr(transparent)]` \
+             or integer repr")),
+    };
+
+    let mut generics = input.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(ref mut param) = *param {
+            param.bounds.push(parse_quote!(::objc::Encode));
+        }
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        unsafe impl #impl_generics ::objc::Encode for #ident #ty_generics #where_clause {
+            const ENCODING: ::objc::Encoding<'static> = #encoding;
+        }
+
+        unsafe impl #impl_generics ::objc::EncodeExtended for #ident #ty_generics #where_clause { }
+    })
+}
@@ -0,0 +1,68 @@
+//! Prints Rust wrappers for the classes and protocols registered by shared
+//! libraries.
+
+extern crate objc;
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+
+use objc::codegen::Generator;
+use objc::dump::{self, Filter};
+
+const USAGE: &str = "\
+Usage: objc-codegen [options] [library...]
+
+Loads each library and prints Rust wrappers for the classes and protocols
+registered with the Objective-C runtime.
+
+Options:
+    --prefix PREFIX  only generate classes and protocols named with PREFIX
+    --image IMAGE    only generate classes and protocols from images whose
+                     path contains IMAGE
+    -h, --help       print this message";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("objc-codegen: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut filter = Filter::default();
+    let mut libraries = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--prefix" => match args.next() {
+                Some(prefix) => filter.prefix = Some(prefix),
+                None => usage_error("--prefix requires an argument"),
+            },
+            "--image" => match args.next() {
+                Some(image) => filter.image = Some(image),
+                None => usage_error("--image requires an argument"),
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ => libraries.push(arg),
+        }
+    }
+
+    for library in &libraries {
+        if let Err(err) = dump::load_image(library) {
+            eprintln!("objc-codegen: {}", err);
+            process::exit(1);
+        }
+    }
+
+    let mut generator = Generator::new();
+    generator.add_matching(&filter);
+    let source = generator.generate();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    // Writing fails if the output was piped to a command that exited early
+    let _ = stdout.write_all(source.as_bytes());
+}
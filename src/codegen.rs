@@ -0,0 +1,577 @@
+/*!
+Generating Rust wrappers for Objective-C classes and protocols from what is
+registered with the runtime.
+
+Each class becomes a zero-sized `#[repr(C)]` type implementing `Message`,
+`ClassType` and `TypedObject`, with a method for each of its instance and class methods that
+sends the message with `msg_send!`. Each type derefs to the wrapper of its
+nearest generated superclass, or to `Object` otherwise, so methods of
+superclasses can be called on it. Each protocol becomes a trait with default
+methods, implemented by the generated classes that adopt it.
+
+Argument and return types are decoded from the method encodings. Methods
+taking or returning types that can't be passed by value, like structs, are
+skipped with a comment in the output.
+
+Selectors are converted to snake case, like `set_object_for_key` for
+`setObject:forKey:`. Methods are named in order of their selectors, instance
+methods before class methods, and a name that's already taken gets the
+smallest numeric suffix that makes it unique, like `name_2`. A protocol with
+the same name as a generated class is suffixed with `Protocol`.
+
+This requires the `"codegen"` feature, and is what the `objc-codegen` binary
+uses.
+
+# Example
+``` no_run
+# use objc::codegen::Generator;
+# use objc::dump::Filter;
+let filter = Filter { prefix: Some("NS".to_owned()), image: None };
+let mut generator = Generator::new();
+generator.add_matching(&filter);
+println!("{}", generator.generate());
+```
+*/
+
+use alloc::borrow::ToOwned;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::dump::Filter;
+use crate::parse::{ParsedEncoding, split_method_types};
+use crate::runtime::{Class, Method, Protocol};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop",
+    "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct",
+    "trait", "true", "type", "unsafe", "use", "where", "while", "abstract",
+    "become", "box", "do", "final", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// Converts a camel case name, like `URLForResource`, into snake case, like
+/// `url_for_resource`. Runs of capitals are treated as a single word.
+pub fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 && !snake.ends_with('_') {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).map_or(false, |c| c.is_ascii_lowercase());
+            if prev.is_ascii_lowercase() || prev.is_ascii_digit() ||
+                    (prev.is_ascii_uppercase() && next_is_lower) {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Returns whether `name` can be used as a Rust identifier, possibly raw.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => (),
+        _ => return false,
+    }
+    name != "_" && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Escapes a snake case name that is a Rust keyword.
+fn escape_keyword(name: String) -> String {
+    match &*name {
+        "self" | "Self" | "super" | "crate" => name + "_",
+        _ if KEYWORDS.contains(&&*name) => format!("r#{}", name),
+        _ => name,
+    }
+}
+
+/// Returns the Rust method name for a selector, like `set_object_for_key`.
+pub fn method_name(sel: &str) -> String {
+    let words: Vec<String> = sel.split(':')
+        .filter(|label| !label.is_empty())
+        .map(snake_case)
+        .collect();
+    escape_keyword(words.join("_"))
+}
+
+/// Hands out names, adding a numeric suffix to names that are already taken.
+#[derive(Default)]
+struct Names {
+    taken: BTreeSet<String>,
+}
+
+impl Names {
+    fn unique(&mut self, name: String) -> String {
+        let mut unique = name.clone();
+        let mut suffix = 2;
+        while self.taken.contains(&unique) {
+            unique = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+        self.taken.insert(unique.clone());
+        unique
+    }
+}
+
+/// The decoded signature of a method.
+struct Signature {
+    args: Vec<(String, String)>,
+    ret: String,
+}
+
+/// A method to generate, with the encodings of its return type and
+/// arguments, or `None` if the runtime doesn't know them.
+struct MethodInfo {
+    sel: String,
+    types: Option<Vec<String>>,
+}
+
+impl MethodInfo {
+    fn from_method(method: &Method) -> MethodInfo {
+        let mut types = vec![method.return_type().to_string()];
+        for i in 0..method.arguments_count() {
+            types.extend(method.argument_type(i).map(|arg| arg.to_string()));
+        }
+        MethodInfo {
+            sel: String::from_utf8_lossy(method.name().name_cstr().to_bytes()).into_owned(),
+            types: Some(types),
+        }
+    }
+}
+
+/**
+Generates Rust source for wrappers of Objective-C classes and protocols.
+
+Classes and protocols are added with `add_class`, `add_protocol` or
+`add_matching`, and `generate` returns the source for all of them. The
+generated code refers to this crate as `::objc`.
+*/
+#[derive(Default)]
+pub struct Generator {
+    classes: BTreeMap<String, &'static Class>,
+    protocols: BTreeMap<String, &'static Protocol>,
+}
+
+impl Generator {
+    /// Constructs a `Generator` without any classes or protocols.
+    pub fn new() -> Generator {
+        Generator::default()
+    }
+
+    /// Adds a class to generate a wrapper for.
+    pub fn add_class(&mut self, cls: &'static Class) {
+        let name = String::from_utf8_lossy(cls.name_cstr().to_bytes()).into_owned();
+        self.classes.insert(name, cls);
+    }
+
+    /// Adds a protocol to generate a trait for.
+    pub fn add_protocol(&mut self, proto: &'static Protocol) {
+        let name = String::from_utf8_lossy(proto.name_cstr().to_bytes()).into_owned();
+        self.protocols.insert(name, proto);
+    }
+
+    /// Adds every registered class and protocol selected by `filter`.
+    pub fn add_matching(&mut self, filter: &Filter) {
+        for &cls in Class::classes().iter() {
+            if filter.matches_class(cls) {
+                self.add_class(cls);
+            }
+        }
+        for &proto in Protocol::protocols().iter() {
+            if filter.matches_protocol(proto) {
+                self.add_protocol(proto);
+            }
+        }
+    }
+
+    /// Returns the Rust name of the trait for a protocol.
+    fn trait_name(&self, proto: &str) -> String {
+        if self.classes.contains_key(proto) {
+            format!("{}Protocol", proto)
+        } else {
+            proto.to_owned()
+        }
+    }
+
+    fn is_generated_protocol(&self, proto: &Protocol) -> bool {
+        let name = String::from_utf8_lossy(proto.name_cstr().to_bytes());
+        is_identifier(&name) && self.protocols.contains_key(&*name)
+    }
+
+    /// Returns the Rust type for an encoding, or `None` if values of it
+    /// can't be passed to or returned from `msg_send!`.
+    fn rust_type(&self, enc: &ParsedEncoding) -> Option<String> {
+        use crate::parse::ParsedEncoding::*;
+        let ty = match *enc {
+            Char => "i8",
+            Short => "i16",
+            Int => "i32",
+            Long => "::core::ffi::c_long",
+            LongLong => "i64",
+            UChar => "u8",
+            UShort => "u16",
+            UInt => "u32",
+            ULong => "::core::ffi::c_ulong",
+            ULongLong => "u64",
+            Float => "f32",
+            Double => "f64",
+            Bool => "bool",
+            Void => "()",
+            String => "*mut ::core::ffi::c_char",
+            Object(Some(ref name)) if is_identifier(name) && self.classes.contains_key(name) =>
+                return Some(format!("*mut {}", name)),
+            Object(_) => "*mut ::objc::runtime::Object",
+            Class => "*const ::objc::runtime::Class",
+            Sel => "::objc::runtime::Sel",
+            Block | Unknown => "*mut ::core::ffi::c_void",
+            Pointer(ref pointee) => {
+                let pointee = match **pointee {
+                    Void | Unknown | Struct(..) | Union(..) => None,
+                    ref pointee => self.rust_type(pointee),
+                };
+                return Some(match pointee {
+                    Some(ref ty) if ty != "()" => format!("*mut {}", ty),
+                    _ => "*mut ::core::ffi::c_void".to_owned(),
+                });
+            }
+            LongDouble | BitField(_) | Array(..) | Struct(..) | Union(..) | Complex(_) =>
+                return None,
+        };
+        Some(ty.to_owned())
+    }
+
+    /// Decodes the signature of a method, or returns why it can't be.
+    fn signature(&self, method: &MethodInfo) -> Result<Signature, String> {
+        let types = match method.types {
+            Some(ref types) => types,
+            None => return Err("unknown type encoding".to_owned()),
+        };
+        let labels: Vec<&str> = method.sel.split(':').collect();
+        let labels = &labels[..labels.len() - 1];
+        if types.len() != labels.len() + 3 {
+            return Err("type encoding doesn't match the selector".to_owned());
+        }
+        if labels.iter().any(|label| !label.is_empty() && !is_identifier(label)) ||
+                (labels.is_empty() && !is_identifier(&method.sel)) {
+            return Err("selector isn't made of identifiers".to_owned());
+        }
+
+        let rust_type = |enc: &str| {
+            ParsedEncoding::parse(enc)
+                .and_then(|parsed| self.rust_type(&parsed))
+                .ok_or_else(|| format!("unsupported type {}", enc))
+        };
+        let ret = rust_type(&types[0])?;
+        let mut names = Names::default();
+        names.taken.insert("self".to_owned());
+        let mut args = Vec::with_capacity(labels.len());
+        for (i, (label, enc)) in labels.iter().zip(&types[3..]).enumerate() {
+            let name = if label.is_empty() {
+                format!("arg{}", i)
+            } else {
+                escape_keyword(snake_case(label))
+            };
+            args.push((names.unique(name), rust_type(enc)?));
+        }
+        Ok(Signature { args: args, ret: ret })
+    }
+
+    /// Writes a method sending `method` to self, or to the class for class
+    /// methods, or a comment saying why it was skipped.
+    fn write_method(&self, out: &mut String, names: &mut Names, method: &MethodInfo,
+            kind: char, owner: &str, in_trait: bool) {
+        let sig = match self.signature(method) {
+            Ok(sig) => sig,
+            Err(reason) => {
+                writeln!(out, "    // Skipped {}[{} {}]: {}", kind, owner, method.sel, reason)
+                    .unwrap();
+                return;
+            }
+        };
+
+        let name = names.unique(method_name(&method.sel));
+        let visibility = if in_trait { "" } else { "pub " };
+        writeln!(out, "    /// `{}[{} {}]`", kind, owner, method.sel).unwrap();
+        write!(out, "    {}unsafe fn {}(", visibility, name).unwrap();
+        let mut params: Vec<String> = sig.args.iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect();
+        if kind == '-' {
+            params.insert(0, "&self".to_owned());
+        }
+        out.push_str(&params.join(", "));
+        out.push(')');
+        if sig.ret != "()" {
+            write!(out, " -> {}", sig.ret).unwrap();
+        }
+        if kind == '+' && in_trait {
+            // Class methods of protocols need to know the adopting class
+            out.push_str(" where Self: ::objc::runtime::ClassType");
+        }
+        out.push_str(" {\n");
+
+        let receiver = if kind == '+' {
+            "<Self as ::objc::runtime::ClassType>::class()"
+        } else {
+            "self"
+        };
+        write!(out, "        ::objc::msg_send![{}, ", receiver).unwrap();
+        if sig.args.is_empty() {
+            out.push_str(&method.sel);
+        } else {
+            let labels = method.sel.split(':');
+            let parts: Vec<String> = labels.zip(&sig.args)
+                .map(|(label, (arg, _))| format!("{}: {}", label, arg))
+                .collect();
+            out.push_str(&parts.join(" "));
+        }
+        out.push_str("]\n    }\n");
+    }
+
+    fn write_class(&self, out: &mut String, name: &str, cls: &Class) {
+        writeln!(out, "/// The Objective-C class `{}`.", name).unwrap();
+        writeln!(out, "#[repr(C)]\npub struct {} {{\n    _priv: [u8; 0],\n}}\n", name).unwrap();
+        writeln!(out, "unsafe impl ::objc::Message for {} {{ }}\n", name).unwrap();
+        writeln!(out, "unsafe impl ::objc::runtime::ClassType for {} {{", name).unwrap();
+        writeln!(out, "    fn class() -> &'static ::objc::runtime::Class {{").unwrap();
+        writeln!(out, "        ::objc::class!({})\n    }}\n}}\n", name).unwrap();
+        writeln!(out, "unsafe impl ::objc::TypedObject for {} {{", name).unwrap();
+        writeln!(out, "    const CLASS_NAME: &'static str = \"{}\";\n}}\n", name).unwrap();
+        for reference in &["&'a", "&'a mut"] {
+            writeln!(out, "unsafe impl<'a> ::objc::Encode for {} {} {{", reference, name).unwrap();
+            writeln!(out, "    const ENCODING: ::objc::Encoding<'static> = \
+                ::objc::Encoding::Object;\n}}\n").unwrap();
+        }
+
+        // Deref to the nearest superclass that's generated too
+        let mut superclass = cls.superclass();
+        let target = loop {
+            match superclass {
+                Some(cls) => {
+                    let name = String::from_utf8_lossy(cls.name_cstr().to_bytes());
+                    if is_identifier(&name) && self.classes.contains_key(&*name) {
+                        break name.into_owned();
+                    }
+                    superclass = cls.superclass();
+                }
+                None => break "::objc::runtime::Object".to_owned(),
+            }
+        };
+        writeln!(out, "impl ::core::ops::Deref for {} {{", name).unwrap();
+        writeln!(out, "    type Target = {};\n", target).unwrap();
+        writeln!(out, "    fn deref(&self) -> &{} {{", target).unwrap();
+        writeln!(out, "        unsafe {{ &*(self as *const Self as *const {}) }}", target).unwrap();
+        writeln!(out, "    }}\n}}\n").unwrap();
+
+        let methods = |cls: &Class| {
+            let mut methods: Vec<MethodInfo> = cls.instance_methods().iter()
+                .map(|&method| MethodInfo::from_method(method))
+                .collect();
+            methods.sort_by(|a, b| a.sel.cmp(&b.sel));
+            methods
+        };
+        let mut names = Names::default();
+        let mut body = String::new();
+        for method in methods(cls) {
+            self.write_method(&mut body, &mut names, &method, '-', name, false);
+        }
+        for method in methods(cls.metaclass()) {
+            self.write_method(&mut body, &mut names, &method, '+', name, false);
+        }
+        writeln!(out, "impl {} {{\n{}}}\n", name, body).unwrap();
+
+        // Implement the traits of adopted protocols, and the protocols they
+        // conform to since those are supertraits
+        let mut adopted = BTreeSet::new();
+        let mut pending: Vec<&Protocol> = cls.adopted_protocols().iter().cloned().collect();
+        while let Some(proto) = pending.pop() {
+            if self.is_generated_protocol(proto) &&
+                    adopted.insert(self.trait_name(&String::from_utf8_lossy(
+                        proto.name_cstr().to_bytes()))) {
+                pending.extend(proto.adopted_protocols().iter().cloned());
+            }
+        }
+        for trait_name in adopted {
+            writeln!(out, "impl {} for {} {{ }}\n", trait_name, name).unwrap();
+        }
+    }
+
+    fn write_protocol(&self, out: &mut String, name: &str, proto: &Protocol) {
+        let trait_name = self.trait_name(name);
+        let mut supertraits = vec!["::objc::Message".to_owned(), "Sized".to_owned()];
+        let mut adopted: Vec<String> = proto.adopted_protocols().iter()
+            .filter(|proto| self.is_generated_protocol(proto))
+            .map(|proto| self.trait_name(&String::from_utf8_lossy(proto.name_cstr().to_bytes())))
+            .collect();
+        adopted.sort();
+        supertraits.extend(adopted);
+
+        writeln!(out, "/// The Objective-C protocol `{}`.", name).unwrap();
+        writeln!(out, "pub trait {}: {} {{", trait_name, supertraits.join(" + ")).unwrap();
+        let mut names = Names::default();
+        for &(kind, instance) in &[('-', true), ('+', false)] {
+            let mut methods: Vec<MethodInfo> = Vec::new();
+            for &required in &[true, false] {
+                for method in proto.method_descriptions(required, instance).iter() {
+                    let types = method.types();
+                    let types = types.as_deref()
+                        .and_then(split_method_types)
+                        .map(|types| types.into_iter().map(str::to_owned).collect());
+                    methods.push(MethodInfo {
+                        sel: String::from_utf8_lossy(method.name().name_cstr().to_bytes())
+                            .into_owned(),
+                        types: types,
+                    });
+                }
+            }
+            methods.sort_by(|a, b| a.sel.cmp(&b.sel));
+            for method in methods {
+                self.write_method(out, &mut names, &method, kind, name, true);
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+
+    /// Returns the Rust source of the wrappers for every added class and
+    /// protocol, sorted by name.
+    pub fn generate(&self) -> String {
+        let mut out = String::new();
+        out.push_str("// Generated from the Objective-C runtime by objc::codegen.\n\n");
+        for (name, &proto) in &self.protocols {
+            if is_identifier(name) {
+                self.write_protocol(&mut out, name, proto);
+            } else {
+                writeln!(out, "// Skipped protocol {}: name isn't an identifier\n", name).unwrap();
+            }
+        }
+        for (name, &cls) in &self.classes {
+            if is_identifier(name) {
+                self.write_class(&mut out, name, cls);
+            } else {
+                writeln!(out, "// Skipped class {}: name isn't an identifier\n", name).unwrap();
+            }
+        }
+        // Trim the blank line after the last item
+        let len = out.trim_end().len();
+        out.truncate(len);
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{method_name, snake_case};
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("length"), "length");
+        assert_eq!(snake_case("isEqualToString"), "is_equal_to_string");
+        assert_eq!(snake_case("URLForResource"), "url_for_resource");
+        assert_eq!(snake_case("setHTTPBody"), "set_http_body");
+        assert_eq!(snake_case("initWithUTF8String"), "init_with_utf8_string");
+        assert_eq!(snake_case("_privateName"), "_private_name");
+    }
+
+    #[test]
+    fn test_method_name() {
+        assert_eq!(method_name("setObject:forKey:"), "set_object_for_key");
+        assert_eq!(method_name("performSelector::"), "perform_selector");
+        assert_eq!(method_name("type"), "r#type");
+        assert_eq!(method_name("self"), "self_");
+    }
+
+    #[cfg(feature = "mock-runtime")]
+    #[test]
+    fn test_generate() {
+        use crate::declare::{ClassDecl, ProtocolDecl};
+        use crate::runtime::{Class, Imp, Object, Sel, self};
+        use super::Generator;
+
+        extern fn set_count(_this: &mut Object, _cmd: Sel, _count: u32, _animated: bool) { }
+        extern fn count(_this: &Object, _cmd: Sel) -> u32 { 0 }
+        extern fn class_count(_cls: &Class, _cmd: Sel) -> u32 { 0 }
+
+        let mut decl = ProtocolDecl::new("CodegenTestCounting").unwrap();
+        decl.add_method_description::<(), u32>(sel!(count), true);
+        decl.add_class_method_description::<(), *mut Object>(sel!(counter), false);
+        let proto = decl.register();
+
+        let mut decl = ClassDecl::new("CodegenTestCounter", class!(NSObject)).unwrap();
+        decl.add_protocol(proto);
+        unsafe {
+            decl.add_method(sel!(setCount:animated:),
+                set_count as extern fn(&mut Object, Sel, u32, bool));
+            decl.add_method(sel!(count), count as extern fn(&Object, Sel) -> u32);
+            decl.add_class_method(sel!(count), class_count as extern fn(&Class, Sel) -> u32);
+        }
+        let superclass = decl.register();
+
+        let cls = ClassDecl::new("CodegenTestView", superclass).unwrap().register();
+        unsafe {
+            // Structs can't be declared as return types with ClassDecl
+            let f: extern fn(&Object, Sel) -> u32 = count;
+            let imp: Imp = core::mem::transmute(f);
+            runtime::class_addMethod(cls as *const Class as *mut Class, sel!(frame), imp,
+                b"{CGRect={CGPoint=dd}{CGSize=dd}}16@0:8\0".as_ptr() as *const _);
+        }
+
+        let mut generator = Generator::new();
+        generator.add_class(superclass);
+        generator.add_class(cls);
+        generator.add_protocol(proto);
+        let source = generator.generate();
+
+        assert!(source.contains("\
+pub trait CodegenTestCounting: ::objc::Message + Sized {
+    /// `-[CodegenTestCounting count]`
+    unsafe fn count(&self) -> u32 {
+        ::objc::msg_send![self, count]
+    }
+    /// `+[CodegenTestCounting counter]`
+    unsafe fn counter() -> *mut ::objc::runtime::Object \
+where Self: ::objc::runtime::ClassType {
+        ::objc::msg_send![<Self as ::objc::runtime::ClassType>::class(), counter]
+    }
+}
+"));
+        assert!(source.contains("\
+impl CodegenTestCounter {
+    /// `-[CodegenTestCounter count]`
+    pub unsafe fn count(&self) -> u32 {
+        ::objc::msg_send![self, count]
+    }
+    /// `-[CodegenTestCounter setCount:animated:]`
+    pub unsafe fn set_count_animated(&self, set_count: u32, animated: bool) {
+        ::objc::msg_send![self, setCount: set_count animated: animated]
+    }
+    /// `+[CodegenTestCounter count]`
+    pub unsafe fn count_2() -> u32 {
+        ::objc::msg_send![<Self as ::objc::runtime::ClassType>::class(), count]
+    }
+}
+"));
+        assert!(source.contains("impl CodegenTestCounting for CodegenTestCounter { }"));
+        assert!(source.contains("\
+unsafe impl ::objc::TypedObject for CodegenTestCounter {
+    const CLASS_NAME: &'static str = \"CodegenTestCounter\";
+}
+"));
+        // NSObject isn't generated, so the superclass chain ends at Object
+        assert!(source.contains("\
+impl ::core::ops::Deref for CodegenTestCounter {
+    type Target = ::objc::runtime::Object;
+"));
+        assert!(source.contains("\
+impl ::core::ops::Deref for CodegenTestView {
+    type Target = CodegenTestCounter;
+"));
+        assert!(source.contains("    // Skipped -[CodegenTestView frame]: unsupported type {CGRect={CGPoint=dd}{CGSize=dd}}\n"));
+    }
+}
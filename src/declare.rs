@@ -34,12 +34,23 @@ decl.register();
 ```
 */
 
-use std::ffi::CString;
-use std::mem;
-use std::ptr;
-
-use crate::runtime::{BOOL, Class, Imp, NO, Object, Protocol, Sel, self};
-use crate::{Encode, EncodeArguments, Encoding, Message};
+use alloc::ffi::CString;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ffi::c_int;
+use core::fmt::Write;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use crate::encode::extended_encoding;
+use crate::header::InterfaceDecl;
+use crate::parse::ParsedEncoding;
+use crate::runtime::{Class, Imp, NO, Object, Protocol, Sel, self};
+#[cfg(not(feature = "gcc_libobjc"))]
+use crate::runtime::BOOL;
+use crate::{Encode, EncodeArguments, EncodeArgumentsExtended, EncodeExtended, Encoding, Message};
 
 /// Types that can be used as the implementation of an Objective-C method.
 pub trait MethodImplementation {
@@ -88,18 +99,73 @@ method_decl_impl!(A, B, C, D, E, F, G, H, I, J, K);
 method_decl_impl!(A, B, C, D, E, F, G, H, I, J, K, L);
 
 fn count_args(sel: Sel) -> usize {
-    sel.name().chars().filter(|&c| c == ':').count()
+    crate::macros::sel_args_count(sel.name())
+}
+
+/// Fails to compile when instantiated if the arguments `A` don't match the
+/// `N` arguments of a selector.
+#[doc(hidden)]
+pub struct __ArgsCountCheck<A, const N: usize>(PhantomData<A>);
+
+impl<A: EncodeArguments, const N: usize> __ArgsCountCheck<A, N> {
+    #[doc(hidden)]
+    pub const CHECK: () = assert!(A::ENCODINGS.len() == N,
+        "Selector and function take different numbers of arguments");
 }
 
-fn method_type_encoding(ret: &Encoding, args: &[Encoding]) -> CString {
+#[doc(hidden)]
+#[inline(always)]
+pub fn __check_method_args<F, const N: usize>(_func: &F)
+        where F: MethodImplementation {
+    let () = __ArgsCountCheck::<F::Args, N>::CHECK;
+}
+
+fn encoding_strings(encs: &[Encoding]) -> Vec<String> {
+    encs.iter().map(ToString::to_string).collect()
+}
+
+/// Returns the type encoding of a method with the given return and argument
+/// encodings, annotated like clang does with the size of the arguments and
+/// the offset of each one, like `v20@0:8I16`.
+fn method_type_encoding(ret: &str, args: &[String]) -> CString {
     // First two arguments are always self and the selector
-    let mut types = format!("{}{}{}",
-        ret, <*mut Object>::ENCODING, Sel::ENCODING);
-    for enc in args {
-        use std::fmt::Write;
-        write!(&mut types, "{}", enc).unwrap();
+    let receiver = [<*mut Object>::ENCODING.to_string(), Sel::ENCODING.to_string()];
+    let mut arg_types = String::new();
+    let mut offset = 0;
+    for arg in receiver.iter().chain(args) {
+        write!(&mut arg_types, "{}{}", arg, offset).unwrap();
+        offset += argument_size(arg);
+    }
+    CString::new(format!("{}{}{}", ret, offset, arg_types)).unwrap()
+}
+
+/// Returns the number of bytes an argument with the given encoding takes up
+/// in the arguments of a method. Like in clang, this is the size of its type
+/// except that integers smaller than an `int` are promoted to one.
+fn argument_size(enc: &str) -> usize {
+    use crate::parse::ParsedEncoding::*;
+
+    match ParsedEncoding::parse(enc) {
+        Some(Char) | Some(UChar) | Some(Bool) | Some(Short) | Some(UShort) =>
+            mem::size_of::<c_int>(),
+        parsed => parsed.and_then(|enc| enc.layout())
+            .map_or(mem::size_of::<*const u8>(), |layout| layout.size),
+    }
+}
+
+/// Returns the selector with the given types for registering a method.
+/// On GNUstep this is a typed selector, so the runtime can catch messages
+/// sent with mismatched types.
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+fn method_sel(sel: Sel, types: &CString) -> Sel {
+    unsafe {
+        runtime::sel_registerTypedName_np(runtime::sel_getName(sel), types.as_ptr())
     }
-    CString::new(types).unwrap()
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn method_sel(sel: Sel, _types: &CString) -> Sel {
+    sel
 }
 
 fn log2_align_of<T>() -> u8 {
@@ -114,20 +180,24 @@ fn log2_align_of<T>() -> u8 {
 /// before registering it.
 pub struct ClassDecl {
     cls: *mut Class,
+    interface: InterfaceDecl,
+    methods: Vec<(bool, Sel, CString)>,
 }
 
 impl ClassDecl {
     fn with_superclass(name: &str, superclass: Option<&Class>)
             -> Option<ClassDecl> {
-        let name = CString::new(name).unwrap();
+        let c_name = CString::new(name).unwrap();
         let super_ptr = superclass.map_or(ptr::null(), |c| c);
         let cls = unsafe {
-            runtime::objc_allocateClassPair(super_ptr, name.as_ptr(), 0)
+            runtime::objc_allocateClassPair(super_ptr, c_name.as_ptr(), 0)
         };
         if cls.is_null() {
             None
         } else {
-            Some(ClassDecl { cls: cls })
+            let interface = InterfaceDecl::new(name,
+                superclass.map(Class::name));
+            Some(ClassDecl { cls: cls, interface: interface, methods: Vec::new() })
         }
     }
 
@@ -161,45 +231,86 @@ impl ClassDecl {
         decl
     }
 
+    unsafe fn add_method_common(&mut self, sel: Sel, imp: Imp, is_instance_method: bool,
+            encs: &[Encoding], types: CString) {
+        let sel_args = count_args(sel);
+        assert!(sel_args == encs.len(),
+            "Selector accepts {} arguments, but function accepts {}",
+            sel_args, encs.len(),
+        );
+
+        let sel = method_sel(sel, &types);
+        if is_instance_method {
+            let success = runtime::class_addMethod(self.cls, sel, imp, types.as_ptr());
+            assert!(success != NO, "Failed to add method {:?}", sel);
+        } else {
+            let metaclass = (*self.cls).metaclass() as *const _ as *mut _;
+            let success = runtime::class_addMethod(metaclass, sel, imp, types.as_ptr());
+            assert!(success != NO, "Failed to add class method {:?}", sel);
+        }
+        self.methods.push((is_instance_method, sel, types));
+    }
+
     /// Adds a method with the given name and implementation to self.
     /// Panics if the method wasn't sucessfully added
     /// or if the selector and function take different numbers of arguments.
+    /// The number of arguments is only checked here, at runtime, by counting
+    /// the colons of the selector; the `add_method!` macro checks it at compile time
+    /// instead when the selector is given with `sel!`.
     /// Unsafe because the caller must ensure that the types match those that
     /// are expected when the method is invoked from Objective-C.
     pub unsafe fn add_method<F>(&mut self, sel: Sel, func: F)
             where F: MethodImplementation<Callee=Object> {
         let encs = F::Args::ENCODINGS;
-        let sel_args = count_args(sel);
-        assert!(sel_args == encs.len(),
-            "Selector accepts {} arguments, but function accepts {}",
-            sel_args, encs.len(),
-        );
+        let types = method_type_encoding(&F::Ret::ENCODING.to_string(),
+            &encoding_strings(encs));
+        self.add_method_common(sel, func.imp(), true, encs, types);
+    }
 
-        let types = method_type_encoding(&F::Ret::ENCODING, encs);
-        let success = runtime::class_addMethod(self.cls, sel, func.imp(),
-            types.as_ptr());
-        assert!(success != NO, "Failed to add method {:?}", sel);
+    /// Adds a method like `add_method`, but with the extended type encodings
+    /// of its arguments and return type, which name the class of typed
+    /// objects like `@"NSString"`.
+    /// Since an `Encoding` can't name a class, messages to the method only
+    /// pass verification at `VerificationLevel::AbiCompatible` or looser.
+    /// Unsafe because the caller must ensure that the types match those that
+    /// are expected when the method is invoked from Objective-C.
+    pub unsafe fn add_method_extended<F>(&mut self, sel: Sel, func: F)
+            where F: MethodImplementation<Callee=Object>,
+                  F::Args: EncodeArgumentsExtended,
+                  F::Ret: EncodeExtended {
+        let types = method_type_encoding(&extended_encoding::<F::Ret>(),
+            &F::Args::extended_encodings());
+        self.add_method_common(sel, func.imp(), true, F::Args::ENCODINGS, types);
     }
 
     /// Adds a class method with the given name and implementation to self.
     /// Panics if the method wasn't sucessfully added
     /// or if the selector and function take different numbers of arguments.
+    /// The number of arguments is only checked here, at runtime, by counting
+    /// the colons of the selector; the `add_class_method!` macro checks it at compile time
+    /// instead when the selector is given with `sel!`.
     /// Unsafe because the caller must ensure that the types match those that
     /// are expected when the method is invoked from Objective-C.
     pub unsafe fn add_class_method<F>(&mut self, sel: Sel, func: F)
             where F: MethodImplementation<Callee=Class> {
         let encs = F::Args::ENCODINGS;
-        let sel_args = count_args(sel);
-        assert!(sel_args == encs.len(),
-            "Selector accepts {} arguments, but function accepts {}",
-            sel_args, encs.len(),
-        );
+        let types = method_type_encoding(&F::Ret::ENCODING.to_string(),
+            &encoding_strings(encs));
+        self.add_method_common(sel, func.imp(), false, encs, types);
+    }
 
-        let types = method_type_encoding(&F::Ret::ENCODING, encs);
-        let metaclass = (*self.cls).metaclass() as *const _ as *mut _;
-        let success = runtime::class_addMethod(metaclass, sel, func.imp(),
-            types.as_ptr());
-        assert!(success != NO, "Failed to add class method {:?}", sel);
+    /// Adds a class method like `add_class_method`, but with the extended
+    /// type encodings of its arguments and return type, like
+    /// `add_method_extended`.
+    /// Unsafe because the caller must ensure that the types match those that
+    /// are expected when the method is invoked from Objective-C.
+    pub unsafe fn add_class_method_extended<F>(&mut self, sel: Sel, func: F)
+            where F: MethodImplementation<Callee=Class>,
+                  F::Args: EncodeArgumentsExtended,
+                  F::Ret: EncodeExtended {
+        let types = method_type_encoding(&extended_encoding::<F::Ret>(),
+            &F::Args::extended_encodings());
+        self.add_method_common(sel, func.imp(), false, F::Args::ENCODINGS, types);
     }
 
     /// Adds an ivar with type `T` and the provided name to self.
@@ -221,6 +332,20 @@ impl ClassDecl {
     pub fn add_protocol(&mut self, proto: &Protocol) {
         let success = unsafe { runtime::class_addProtocol(self.cls, proto) };
         assert!(success != NO, "Failed to add protocol {:?}", proto);
+        self.interface.add_protocol(proto.name());
+    }
+
+    /// Returns the interface of the methods and protocols added to self so
+    /// far, for exporting in a header with `header::Header`.
+    /// The methods are described by the type encodings they were added with,
+    /// so those added with `add_method_extended` name the class of their
+    /// typed objects.
+    pub fn interface(&self) -> InterfaceDecl {
+        let mut interface = self.interface.clone();
+        for &(instance, sel, ref types) in &self.methods {
+            interface.push_method_types(instance, sel.name(), types);
+        }
+        interface
     }
 
     /// Registers self, consuming it and returning a reference to the
@@ -244,12 +369,24 @@ impl Drop for ClassDecl {
     }
 }
 
+/// Stands in for `ProtocolDecl` with GCC's libobjc, which can't declare
+/// protocols at runtime, so that using it explains why it's unavailable.
+/// It can't be constructed.
+#[cfg(feature = "gcc_libobjc")]
+#[deprecated(note = "GCC's libobjc can't declare protocols at runtime; \
+    ProtocolDecl requires Apple's runtime or GNUstep's libobjc2")]
+pub enum ProtocolDecl { }
+
 /// A type for declaring a new protocol and adding new methods to it
 /// before registering it.
+///
+/// Not available with GCC's libobjc, which can't declare protocols at runtime.
+#[cfg(not(feature = "gcc_libobjc"))]
 pub struct ProtocolDecl {
     proto: *mut Protocol
 }
 
+#[cfg(not(feature = "gcc_libobjc"))]
 impl ProtocolDecl {
     /// Constructs a `ProtocolDecl` with the given name. Returns `None` if the
     /// protocol couldn't be allocated.
@@ -265,17 +402,13 @@ impl ProtocolDecl {
         }
     }
 
-    fn add_method_description_common<Args, Ret>(&mut self, sel: Sel, is_required: bool,
-            is_instance_method: bool)
-            where Args: EncodeArguments,
-                  Ret: Encode {
-        let encs = Args::ENCODINGS;
+    fn add_method_description_common(&mut self, sel: Sel, is_required: bool,
+            is_instance_method: bool, arg_count: usize, types: CString) {
         let sel_args = count_args(sel);
-        assert!(sel_args == encs.len(),
+        assert!(sel_args == arg_count,
             "Selector accepts {} arguments, but function accepts {}",
-            sel_args, encs.len(),
+            sel_args, arg_count,
         );
-        let types = method_type_encoding(&Ret::ENCODING, encs);
         unsafe {
             runtime::protocol_addMethodDescription(
                 self.proto, sel, types.as_ptr(), is_required as BOOL, is_instance_method as BOOL);
@@ -283,17 +416,53 @@ impl ProtocolDecl {
     }
 
     /// Adds an instance method declaration with a given description to self.
+    /// Panics if the selector and arguments have different numbers of
+    /// arguments; to check this at compile time instead, use the
+    /// `add_method_description!` macro.
     pub fn add_method_description<Args, Ret>(&mut self, sel: Sel, is_required: bool)
             where Args: EncodeArguments,
                   Ret: Encode {
-        self.add_method_description_common::<Args, Ret>(sel, is_required, true)
+        let types = method_type_encoding(&Ret::ENCODING.to_string(),
+            &encoding_strings(Args::ENCODINGS));
+        self.add_method_description_common(sel, is_required, true,
+            Args::ENCODINGS.len(), types)
+    }
+
+    /// Adds an instance method declaration like `add_method_description`, but
+    /// with the extended type encodings of its arguments and return type,
+    /// which name the class of typed objects like `@"NSString"`.
+    pub fn add_method_description_extended<Args, Ret>(&mut self, sel: Sel, is_required: bool)
+            where Args: EncodeArgumentsExtended,
+                  Ret: EncodeExtended {
+        let types = method_type_encoding(&extended_encoding::<Ret>(),
+            &Args::extended_encodings());
+        self.add_method_description_common(sel, is_required, true,
+            Args::ENCODINGS.len(), types)
     }
 
     /// Adds a class method declaration with a given description to self.
+    /// Panics if the selector and arguments have different numbers of
+    /// arguments; to check this at compile time instead, use the
+    /// `add_method_description!` macro.
     pub fn add_class_method_description<Args, Ret>(&mut self, sel: Sel, is_required: bool)
             where Args: EncodeArguments,
                   Ret: Encode {
-        self.add_method_description_common::<Args, Ret>(sel, is_required, false)
+        let types = method_type_encoding(&Ret::ENCODING.to_string(),
+            &encoding_strings(Args::ENCODINGS));
+        self.add_method_description_common(sel, is_required, false,
+            Args::ENCODINGS.len(), types)
+    }
+
+    /// Adds a class method declaration like `add_class_method_description`,
+    /// but with the extended type encodings of its arguments and return type.
+    pub fn add_class_method_description_extended<Args, Ret>(&mut self, sel: Sel,
+            is_required: bool)
+            where Args: EncodeArgumentsExtended,
+                  Ret: EncodeExtended {
+        let types = method_type_encoding(&extended_encoding::<Ret>(),
+            &Args::extended_encodings());
+        self.add_method_description_common(sel, is_required, false,
+            Args::ENCODINGS.len(), types)
     }
 
     /// Adds a requirement on another protocol.
@@ -315,7 +484,31 @@ impl ProtocolDecl {
 
 #[cfg(test)]
 mod tests {
+    use alloc::borrow::ToOwned;
+    use alloc::format;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use core::mem;
+    use crate::runtime::{Object, Sel};
+    use crate::{Encode, Encoding, Message, TypedObject, VerificationLevel};
     use crate::test_utils;
+    use super::{ClassDecl, count_args, method_type_encoding};
+    #[cfg(not(feature = "gcc_libobjc"))]
+    use super::ProtocolDecl;
+
+    struct CustomObject {
+        _priv: [u8; 0],
+    }
+
+    unsafe impl Message for CustomObject { }
+
+    unsafe impl TypedObject for CustomObject {
+        const CLASS_NAME: &'static str = "CustomObject";
+    }
+
+    unsafe impl<'a> Encode for &'a CustomObject {
+        const ENCODING: Encoding<'static> = Encoding::Object;
+    }
 
     #[test]
     fn test_custom_class() {
@@ -328,6 +521,135 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_count_args() {
+        assert_eq!(count_args(sel!(foo)), 0);
+        assert_eq!(count_args(sel!(setFoo:)), 1);
+        assert_eq!(count_args(sel!(performSelector::)), 2);
+        assert_eq!(count_args(sel!("weird-selector:with:")), 2);
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    #[test]
+    fn test_typed_method_sel() {
+        let _ = test_utils::custom_class();
+        let types = crate::runtime::Sel::registered_types("setFoo:");
+        let word = mem::size_of::<usize>();
+        let expected = format!("v{}@0:{}I{}", 2 * word + 4, word, 2 * word);
+        assert!(types.iter().any(|t| *t == expected));
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_method_type_encoding() {
+        fn types(ret: &str, args: &[&str]) -> String {
+            let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+            method_type_encoding(ret, &args).into_string().unwrap()
+        }
+
+        assert_eq!(types("v", &[]), "v16@0:8");
+        assert_eq!(types("I", &["I"]), "I20@0:8I16");
+        // Small integers are promoted to an int, but nothing is aligned
+        assert_eq!(types("v", &["c", "d", "{CGPoint=dd}"]),
+            "v44@0:8c16d20{CGPoint=dd}28");
+        assert_eq!(types("@\"NSString\"", &["@?<v@?>", "^{CGRect}"]),
+            "@\"NSString\"32@0:8@?<v@?>16^{CGRect}24");
+    }
+
+    #[test]
+    fn test_extended_method() {
+        use crate::runtime::Class;
+
+        extern fn take_object(_cls: &Class, _cmd: Sel, _flag: u8,
+                _obj: Option<&'static CustomObject>) -> f64 {
+            1.0
+        }
+
+        let superclass = test_utils::custom_class();
+        let mut decl = ClassDecl::new("ExtendedMethodObject", superclass).unwrap();
+        unsafe {
+            decl.add_class_method_extended(sel!(takeFlag:object:), take_object as
+                extern fn(&Class, Sel, u8, Option<&'static CustomObject>) -> f64);
+        }
+        let cls = decl.register();
+
+        let method = cls.metaclass().instance_method(sel!(takeFlag:object:)).unwrap();
+        assert_eq!(method.arguments_count(), 4);
+        assert_eq!(&*method.return_type(), "d");
+        assert_eq!(method.argument_type(0).as_deref(), Some("@"));
+        assert_eq!(method.argument_type(1).as_deref(), Some(":"));
+        assert_eq!(method.argument_type(2).as_deref(), Some("C"));
+        assert_eq!(method.argument_type(3).as_deref(), Some("@\"CustomObject\""));
+
+        // An `Encoding` can't name the class, so it doesn't match exactly
+        let sel = sel!(takeFlag:object:);
+        assert!(cls.verify_message_with_level::<(u8, Option<&CustomObject>), f64>(
+            sel, VerificationLevel::AbiCompatible).is_ok());
+        assert!(cls.verify_message_with_level::<(u8, Option<&CustomObject>), f64>(
+            sel, VerificationLevel::Exact).is_err());
+    }
+
+    #[cfg(not(feature = "gcc_libobjc"))]
+    #[test]
+    fn test_extended_method_description() {
+        let mut decl = ProtocolDecl::new("ExtendedMethodProtocol").unwrap();
+        decl.add_method_description_extended::<(&CustomObject,), ()>(
+            sel!(setObject:), true);
+        let proto = decl.register();
+
+        let methods = proto.method_descriptions(true, true);
+        let word = mem::size_of::<usize>();
+        let expected = format!("v{}@0:{}@\"CustomObject\"{}", 3 * word, word, 2 * word);
+        assert_eq!(methods.len(), 1);
+        assert_eq!(methods[0].types().as_deref(), Some(&*expected));
+    }
+
+    #[test]
+    fn test_interface() {
+        extern fn get_bar(_this: &Object, _cmd: Sel) -> u32 { 0 }
+        extern fn set_bar(_this: &mut Object, _cmd: Sel, _bar: u32) { }
+
+        let superclass = test_utils::custom_class();
+        let mut decl = ClassDecl::new("InterfaceObject", superclass).unwrap();
+        unsafe {
+            decl.add_method(sel!(bar), get_bar as extern fn(&Object, Sel) -> u32);
+            add_method!(decl, sel!(setBar:), set_bar as extern fn(&mut Object, Sel, u32));
+        }
+
+        let interface = decl.interface().to_string();
+        assert!(interface.starts_with(
+            "@interface InterfaceObject : CustomObject\n"));
+        assert!(interface.contains("@property (nonatomic, assign) unsigned int bar;\n"));
+    }
+
+    #[cfg(not(feature = "gcc_libobjc"))]
+    #[test]
+    fn test_pointer_types() {
+        use crate::runtime::{Class, Protocol};
+
+        extern fn conforms(_cls: &Class, _cmd: Sel, obj: Option<&'static Object>,
+                proto: *const Protocol) -> Option<&'static Object> {
+            assert!(obj.is_none());
+            assert!(!proto.is_null());
+            None
+        }
+
+        let superclass = test_utils::custom_class();
+        let mut decl = ClassDecl::new("PointerTypesObject", superclass).unwrap();
+        unsafe {
+            add_class_method!(decl, sel!(object:conformsTo:), conforms as
+                extern fn(&Class, Sel, Option<&'static Object>, *const Protocol)
+                    -> Option<&'static Object>);
+        }
+        let cls = decl.register();
+
+        let proto: *const Protocol = test_utils::custom_protocol();
+        let result: Option<&Object> = unsafe {
+            msg_send![cls, object:None::<&Object> conformsTo:proto]
+        };
+        assert!(result.is_none());
+    }
+
     #[test]
     fn test_class_method() {
         let cls = test_utils::custom_class();
@@ -0,0 +1,387 @@
+//! Printing the classes and protocols registered with the runtime as
+//! Objective-C declarations, like the headers they were compiled from.
+//!
+//! This is what the `class-dump` binary uses, and requires the `"class-dump"`
+//! feature.
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ffi::{CStr, c_char, c_int, c_void};
+use core::fmt;
+use core::ptr;
+
+use crate::parse::{ParsedEncoding, split_method_types};
+use crate::runtime::{Class, Ivar, Method, MethodDescription, Protocol, Sel};
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const RTLD_GLOBAL: c_int = 0x8;
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+const RTLD_GLOBAL: c_int = 0x100;
+const RTLD_NOW: c_int = 0x2;
+
+#[repr(C)]
+struct DlInfo {
+    dli_fname: *const c_char,
+    dli_fbase: *mut c_void,
+    dli_sname: *const c_char,
+    dli_saddr: *mut c_void,
+}
+
+#[cfg_attr(target_os = "linux", link(name = "dl"))]
+extern {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlerror() -> *const c_char;
+    fn dladdr(addr: *const c_void, info: *mut DlInfo) -> c_int;
+}
+
+/// An error loading a shared library with `load_image`.
+#[derive(Debug)]
+pub struct LoadError(String);
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Loads the shared library at `path` so that the classes and protocols it
+/// defines are registered with the runtime. The library is never unloaded.
+pub fn load_image(path: &str) -> Result<(), LoadError> {
+    let c_path = alloc::ffi::CString::new(path)
+        .map_err(|_| LoadError(format!("{:?}: nul byte found in path", path)))?;
+    unsafe {
+        if !dlopen(c_path.as_ptr(), RTLD_NOW | RTLD_GLOBAL).is_null() {
+            return Ok(());
+        }
+        let err = dlerror();
+        let message = if err.is_null() {
+            format!("{}: failed to load", path)
+        } else {
+            CStr::from_ptr(err).to_string_lossy().into_owned()
+        };
+        Err(LoadError(message))
+    }
+}
+
+/// Returns the path of the image, either the executable or a shared library,
+/// whose data contains `ptr`, or `None` if it isn't in any loaded image.
+fn image_path(ptr: *const c_void) -> Option<String> {
+    let mut info = DlInfo {
+        dli_fname: ptr::null(),
+        dli_fbase: ptr::null_mut(),
+        dli_sname: ptr::null(),
+        dli_saddr: ptr::null_mut(),
+    };
+    unsafe {
+        if dladdr(ptr, &mut info) == 0 || info.dli_fname.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(info.dli_fname).to_string_lossy().into_owned())
+        }
+    }
+}
+
+/// Returns the path of the image that defines `cls`, or `None` if it was
+/// declared at runtime rather than loaded from an image.
+pub fn class_image(cls: &Class) -> Option<String> {
+    image_path(cls as *const Class as *const c_void)
+}
+
+/// Returns the path of the image that defines `proto`, or `None` if it was
+/// declared at runtime rather than loaded from an image.
+pub fn protocol_image(proto: &Protocol) -> Option<String> {
+    image_path(proto as *const Protocol as *const c_void)
+}
+
+/// Selects which classes and protocols are printed by `write_header`.
+///
+/// The default filter selects everything.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Only select classes and protocols whose names start with this prefix.
+    pub prefix: Option<String>,
+    /// Only select classes and protocols defined by an image whose path
+    /// contains this string.
+    pub image: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, name: &CStr, image: impl FnOnce() -> Option<String>) -> bool {
+        if let Some(ref prefix) = self.prefix {
+            if !name.to_bytes().starts_with(prefix.as_bytes()) {
+                return false;
+            }
+        }
+        match self.image {
+            Some(ref filter) => image().map_or(false, |path| path.contains(&**filter)),
+            None => true,
+        }
+    }
+
+    /// Returns whether `cls` is selected by self.
+    pub fn matches_class(&self, cls: &Class) -> bool {
+        self.matches(cls.name_cstr(), || class_image(cls))
+    }
+
+    /// Returns whether `proto` is selected by self.
+    pub fn matches_protocol(&self, proto: &Protocol) -> bool {
+        self.matches(proto.name_cstr(), || protocol_image(proto))
+    }
+}
+
+/// Returns the C type for an encoding, or a commented out encoding if it
+/// can't be parsed.
+fn type_name(encoding: &str) -> String {
+    declaration(encoding, "")
+}
+
+fn declaration(encoding: &str, name: &str) -> String {
+    match ParsedEncoding::parse(encoding) {
+        Some(enc) => enc.c_declaration(name),
+        None if name.is_empty() => format!("void * /* {} */", encoding),
+        None => format!("void *{} /* {} */", name, encoding),
+    }
+}
+
+/// Writes a method declaration from the encodings of its return type and
+/// arguments, including the receiver and selector.
+fn write_method<W: fmt::Write>(w: &mut W, kind: char, sel: Sel, types: &[String])
+        -> fmt::Result {
+    let ret = types.first().map_or("id".to_owned(), |ret| type_name(ret));
+    write!(w, "{} ({})", kind, ret)?;
+
+    let name = String::from_utf8_lossy(sel.name_cstr().to_bytes());
+    if !name.contains(':') {
+        return writeln!(w, "{};", name);
+    }
+    let labels = name.split(':').take(name.matches(':').count());
+    for (i, label) in labels.enumerate() {
+        let arg = types.get(i + 3).map_or("id".to_owned(), |arg| type_name(arg));
+        if i > 0 {
+            w.write_char(' ')?;
+        }
+        write!(w, "{}:({})arg{}", label, arg, i)?;
+    }
+    writeln!(w, ";")
+}
+
+fn method_types(method: &Method) -> Vec<String> {
+    let mut types = Vec::with_capacity(method.arguments_count() + 1);
+    types.push(method.return_type().to_string());
+    for i in 0..method.arguments_count() {
+        types.extend(method.argument_type(i).map(|arg| arg.to_string()));
+    }
+    types
+}
+
+fn write_methods<W: fmt::Write>(w: &mut W, kind: char, cls: &Class) -> fmt::Result {
+    let mut methods: Vec<&Method> = cls.instance_methods().iter().cloned().collect();
+    methods.sort_by(|a, b| a.name().name_cstr().cmp(b.name().name_cstr()));
+    for method in methods {
+        write_method(w, kind, method.name(), &method_types(method))?;
+    }
+    Ok(())
+}
+
+fn write_ivar<W: fmt::Write>(w: &mut W, ivar: &Ivar) -> fmt::Result {
+    let name = String::from_utf8_lossy(ivar.name_cstr().to_bytes());
+    let types = String::from_utf8_lossy(ivar.type_encoding_cstr().to_bytes());
+    writeln!(w, "    {};", declaration(&types, &name))
+}
+
+fn write_protocol_list<W: fmt::Write>(w: &mut W, protocols: &[&Protocol]) -> fmt::Result {
+    if protocols.is_empty() {
+        return Ok(());
+    }
+    w.write_str(" <")?;
+    for (i, proto) in protocols.iter().enumerate() {
+        if i > 0 {
+            w.write_str(", ")?;
+        }
+        write!(w, "{:?}", proto)?;
+    }
+    w.write_char('>')
+}
+
+/// Writes an `@interface` declaring the superclass, adopted protocols,
+/// instance variables and methods of `cls`.
+pub fn write_interface<W: fmt::Write>(w: &mut W, cls: &Class) -> fmt::Result {
+    write!(w, "@interface {:?}", cls)?;
+    if let Some(superclass) = cls.superclass() {
+        write!(w, " : {:?}", superclass)?;
+    }
+    write_protocol_list(w, &cls.adopted_protocols())?;
+    w.write_char('\n')?;
+
+    let ivars = cls.instance_variables();
+    if !ivars.is_empty() {
+        w.write_str("{\n")?;
+        for ivar in ivars.iter() {
+            write_ivar(w, ivar)?;
+        }
+        w.write_str("}\n")?;
+    }
+
+    w.write_char('\n')?;
+    write_methods(w, '+', cls.metaclass())?;
+    write_methods(w, '-', cls)?;
+    w.write_str("@end\n")
+}
+
+fn write_method_descriptions<W: fmt::Write>(w: &mut W, proto: &Protocol, required: bool)
+        -> fmt::Result {
+    for &(kind, instance) in &[('+', false), ('-', true)] {
+        let mut methods: Vec<MethodDescription> =
+            proto.method_descriptions(required, instance).iter().cloned().collect();
+        methods.sort_by(|a, b| a.name().name_cstr().cmp(b.name().name_cstr()));
+        for method in methods {
+            let types = method.types();
+            let types: Vec<String> = types.as_deref()
+                .and_then(split_method_types)
+                .map(|types| types.into_iter().map(str::to_owned).collect())
+                .unwrap_or_default();
+            write_method(w, kind, method.name(), &types)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a `@protocol` declaring the adopted protocols and the required and
+/// optional methods of `proto`.
+pub fn write_protocol<W: fmt::Write>(w: &mut W, proto: &Protocol) -> fmt::Result {
+    write!(w, "@protocol {:?}", proto)?;
+    write_protocol_list(w, &proto.adopted_protocols())?;
+    w.write_str("\n\n")?;
+    write_method_descriptions(w, proto, true)?;
+
+    let mut optional = String::new();
+    write_method_descriptions(&mut optional, proto, false)?;
+    if !optional.is_empty() {
+        w.write_str("\n@optional\n")?;
+        w.write_str(&optional)?;
+    }
+    w.write_str("@end\n")
+}
+
+/// Writes the declarations of every registered protocol and class selected
+/// by `filter`, protocols first, each sorted by name.
+pub fn write_header<W: fmt::Write>(w: &mut W, filter: &Filter) -> fmt::Result {
+    let mut protocols: Vec<&Protocol> = Protocol::protocols().iter().cloned()
+        .filter(|&proto| filter.matches_protocol(proto))
+        .collect();
+    protocols.sort_by(|a, b| a.name_cstr().cmp(b.name_cstr()));
+
+    let mut classes: Vec<&Class> = Class::classes().iter().cloned()
+        .filter(|&cls| filter.matches_class(cls))
+        .collect();
+    classes.sort_by(|a, b| a.name_cstr().cmp(b.name_cstr()));
+
+    let mut first = true;
+    for proto in protocols {
+        if !first {
+            w.write_char('\n')?;
+        }
+        first = false;
+        write_protocol(w, proto)?;
+    }
+    for cls in classes {
+        if !first {
+            w.write_char('\n')?;
+        }
+        first = false;
+        write_interface(w, cls)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "mock-runtime"))]
+mod tests {
+    use alloc::string::String;
+
+    use crate::declare::{ClassDecl, ProtocolDecl};
+    use crate::runtime::{Class, Object, Protocol, Sel};
+    use super::{Filter, write_header, write_interface, write_protocol};
+
+    #[test]
+    fn test_write_interface() {
+        extern fn set_origin(_this: &mut Object, _cmd: Sel, _x: f64, _y: f64) { }
+        extern fn shared(_cls: &Class, _cmd: Sel) -> *mut Object { core::ptr::null_mut() }
+
+        let superclass = class!(NSObject);
+        let mut decl = ClassDecl::new("DumpTestView", superclass).unwrap();
+        decl.add_ivar::<u32>("_tag");
+        decl.add_ivar::<*mut core::ffi::c_void>("_context");
+        unsafe {
+            decl.add_method(sel!(setOriginX:y:),
+                set_origin as extern fn(&mut Object, Sel, f64, f64));
+            decl.add_class_method(sel!(shared),
+                shared as extern fn(&Class, Sel) -> *mut Object);
+        }
+        let cls = decl.register();
+
+        let mut header = String::new();
+        write_interface(&mut header, cls).unwrap();
+        assert_eq!(header, "\
+@interface DumpTestView : NSObject
+{
+    unsigned int _tag;
+    void *_context;
+}
+
++ (id)shared;
+- (void)setOriginX:(double)arg0 y:(double)arg1;
+@end
+");
+    }
+
+    #[test]
+    fn test_write_protocol() {
+        let mut decl = ProtocolDecl::new("DumpTestDelegate").unwrap();
+        decl.add_method_description::<(i32,), ()>(sel!(didChange:), true);
+        decl.add_method_description::<(), bool>(sel!(isReady), false);
+        decl.add_class_method_description::<(), *mut Object>(sel!(defaultDelegate), true);
+        let proto = decl.register();
+
+        let mut header = String::new();
+        write_protocol(&mut header, proto).unwrap();
+        assert_eq!(header, "\
+@protocol DumpTestDelegate
+
++ (id)defaultDelegate;
+- (void)didChange:(int)arg0;
+
+@optional
+- (bool)isReady;
+@end
+");
+    }
+
+    #[test]
+    fn test_filter() {
+        let protocol_filter = Filter {
+            prefix: Some("DumpTestFilter".to_owned()),
+            image: None,
+        };
+        ProtocolDecl::new("DumpTestFilterProtocol").unwrap().register();
+        let proto = Protocol::get("DumpTestFilterProtocol").unwrap();
+        assert!(protocol_filter.matches_protocol(proto));
+        assert!(!protocol_filter.matches_class(class!(NSObject)));
+
+        let mut header = String::new();
+        write_header(&mut header, &protocol_filter).unwrap();
+        assert_eq!(header, "@protocol DumpTestFilterProtocol\n\n@end\n");
+
+        // Classes declared at runtime don't belong to any image
+        let image_filter = Filter { prefix: None, image: Some(String::new()) };
+        assert!(!image_filter.matches_class(class!(NSObject)));
+    }
+}
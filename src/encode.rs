@@ -1,5 +1,10 @@
-use crate::runtime::{Class, Object, Sel};
-use crate::{Encode, Encoding};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::fmt;
+
+use crate::runtime::{Class, FnPointer, Object, Protocol, Sel};
+use crate::{Encode, Encoding, Message};
 
 unsafe impl Encode for Sel {
     const ENCODING: Encoding<'static> = Encoding::Sel;
@@ -21,6 +26,132 @@ unsafe impl<'a> Encode for &'a mut Class {
     const ENCODING: Encoding<'static> = Encoding::Class;
 }
 
+unsafe impl<'a> Encode for &'a Protocol {
+    const ENCODING: Encoding<'static> = Encoding::Object;
+}
+
+unsafe impl Encode for FnPointer {
+    const ENCODING: Encoding<'static> = Encoding::Pointer(&Encoding::Unknown);
+}
+
+/**
+Object types of a known class, so that references to them have a typed object
+encoding naming the class, like `@"NSString"`.
+
+References to objects must be encoded as `@` to be sent in messages, so a
+typed wrapper implements `Encode` for `&` and `&mut` references to itself
+with `Encoding::Object`, and this trait with its class name. References and
+pointers to it then implement `EncodeExtended` with the typed encoding.
+
+Unsafe because the objects must be instances of the named class or one of its
+subclasses.
+
+# Example
+```
+# use objc::{Encode, EncodeExtended, Encoding, Message, TypedObject};
+#[repr(C)]
+struct NSString {
+    _priv: [u8; 0],
+}
+
+unsafe impl Message for NSString { }
+
+unsafe impl TypedObject for NSString {
+    const CLASS_NAME: &'static str = "NSString";
+}
+
+unsafe impl<'a> Encode for &'a NSString {
+    const ENCODING: Encoding<'static> = Encoding::Object;
+}
+
+let mut encoding = String::new();
+<&NSString>::write_extended(&mut encoding).unwrap();
+assert_eq!(encoding, "@\"NSString\"");
+```
+*/
+pub unsafe trait TypedObject: Message {
+    /// The name of the class of these objects.
+    const CLASS_NAME: &'static str;
+}
+
+/// Types with an extended type encoding, which is their `Encode` encoding
+/// except that typed objects also name their class, like `@"NSString"`.
+///
+/// Types without a class only need an empty impl, like
+/// `unsafe impl EncodeExtended for MyStruct { }`, and block types can
+/// override `write_extended` to include their signature, like `@?<v@?i>`.
+/// Unsafe because a class name must only be given for references to objects
+/// of that class.
+pub unsafe trait EncodeExtended: Encode {
+    /// The name of the class of the object Self refers to, if it's known.
+    const CLASS_NAME: Option<&'static str> = None;
+
+    /// Writes the extended encoding of Self.
+    fn write_extended<W: fmt::Write>(w: &mut W) -> fmt::Result where Self: Sized {
+        match Self::CLASS_NAME {
+            Some(name) => write!(w, "@\"{}\"", name),
+            None => write!(w, "{}", Self::ENCODING),
+        }
+    }
+}
+
+macro_rules! encode_extended_impls {
+    ($($t:ty),* $(,)?) => ($(
+        unsafe impl EncodeExtended for $t { }
+    )*);
+}
+
+encode_extended_impls!(
+    i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, bool, (), Sel, FnPointer,
+    *mut i8, *const i8, *mut u8, *const u8, *mut c_void, *const c_void,
+);
+
+macro_rules! encode_extended_ref_impls {
+    ($($t:ty),*) => ($(
+        unsafe impl<'a> EncodeExtended for &'a $t { }
+        unsafe impl<'a> EncodeExtended for Option<&'a $t> { }
+        unsafe impl EncodeExtended for *const $t { }
+    )*);
+}
+
+encode_extended_ref_impls!(Object, Class, Protocol);
+
+macro_rules! encode_extended_mut_impls {
+    ($($t:ty),*) => ($(
+        unsafe impl<'a> EncodeExtended for &'a mut $t { }
+        unsafe impl<'a> EncodeExtended for Option<&'a mut $t> { }
+        unsafe impl EncodeExtended for *mut $t { }
+    )*);
+}
+
+encode_extended_mut_impls!(Object, Class);
+
+unsafe impl<T: EncodeExtended, const N: usize> EncodeExtended for [T; N] where [T; N]: Encode { }
+
+unsafe impl<'a, T: TypedObject> EncodeExtended for &'a T where &'a T: Encode {
+    const CLASS_NAME: Option<&'static str> = Some(T::CLASS_NAME);
+}
+
+unsafe impl<'a, T: TypedObject> EncodeExtended for &'a mut T where &'a mut T: Encode {
+    const CLASS_NAME: Option<&'static str> = Some(T::CLASS_NAME);
+}
+
+unsafe impl<'a, T: TypedObject> EncodeExtended for Option<&'a T> where &'a T: Encode {
+    const CLASS_NAME: Option<&'static str> = Some(T::CLASS_NAME);
+}
+
+unsafe impl<'a, T: TypedObject> EncodeExtended for Option<&'a mut T> where &'a mut T: Encode {
+    const CLASS_NAME: Option<&'static str> = Some(T::CLASS_NAME);
+}
+
+unsafe impl<T: TypedObject + 'static> EncodeExtended for *const T where for<'a> &'a T: Encode {
+    const CLASS_NAME: Option<&'static str> = Some(T::CLASS_NAME);
+}
+
+unsafe impl<T: TypedObject + 'static> EncodeExtended for *mut T where for<'a> &'a mut T: Encode {
+    const CLASS_NAME: Option<&'static str> = Some(T::CLASS_NAME);
+}
+
 /// Types that represent a group of arguments, where each has an Objective-C
 /// type encoding.
 pub trait EncodeArguments {
@@ -52,10 +183,52 @@ encode_args_impl!(A, B, C, D, E, F, G, H, I, J);
 encode_args_impl!(A, B, C, D, E, F, G, H, I, J, K);
 encode_args_impl!(A, B, C, D, E, F, G, H, I, J, K, L);
 
+/// Returns the extended encoding of `T` as a string.
+pub(crate) fn extended_encoding<T: EncodeExtended>() -> String {
+    let mut encoding = String::new();
+    T::write_extended(&mut encoding).unwrap();
+    encoding
+}
+
+/// Types that represent a group of arguments, where each has an extended
+/// type encoding.
+pub trait EncodeArgumentsExtended: EncodeArguments {
+    /// Returns the extended encodings of the arguments of Self.
+    fn extended_encodings() -> Vec<String>;
+}
+
+macro_rules! encode_args_extended_impl {
+    ($($t:ident),*) => (
+        impl<$($t: EncodeExtended),*> EncodeArgumentsExtended for ($($t,)*) {
+            fn extended_encodings() -> Vec<String> {
+                alloc::vec![$(extended_encoding::<$t>()),*]
+            }
+        }
+    );
+}
+
+encode_args_extended_impl!();
+encode_args_extended_impl!(A);
+encode_args_extended_impl!(A, B);
+encode_args_extended_impl!(A, B, C);
+encode_args_extended_impl!(A, B, C, D);
+encode_args_extended_impl!(A, B, C, D, E);
+encode_args_extended_impl!(A, B, C, D, E, F);
+encode_args_extended_impl!(A, B, C, D, E, F, G);
+encode_args_extended_impl!(A, B, C, D, E, F, G, H);
+encode_args_extended_impl!(A, B, C, D, E, F, G, H, I);
+encode_args_extended_impl!(A, B, C, D, E, F, G, H, I, J);
+encode_args_extended_impl!(A, B, C, D, E, F, G, H, I, J, K);
+encode_args_extended_impl!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 #[cfg(test)]
 mod tests {
-    use objc_encode::Encode;
-    use crate::runtime::{Class, Object, Sel};
+    use core::mem;
+    use objc_encode::{Encode, Encoding};
+    use crate::Message;
+    use crate::rc::StrongPtr;
+    use crate::runtime::{Class, FnPointer, Imp, Object, Protocol, Sel};
+    use super::{EncodeArgumentsExtended, TypedObject, extended_encoding};
 
     #[test]
     fn test_encode() {
@@ -63,5 +236,45 @@ mod tests {
         assert!(<*mut Object>::ENCODING.to_string() == "@");
         assert!(<&Class>::ENCODING.to_string() == "#");
         assert!(Sel::ENCODING.to_string() == ":");
+        assert!(<&Protocol>::ENCODING.to_string() == "@");
+        assert!(<*const Protocol>::ENCODING.to_string() == "@");
+        assert!(<Option<&Protocol>>::ENCODING.to_string() == "@");
+        assert!(<Option<&Object>>::ENCODING.to_string() == "@");
+        assert!(FnPointer::ENCODING.to_string() == "^?");
+        assert!(<[u8; 4]>::ENCODING.to_string() == "[4C]");
+    }
+
+    #[test]
+    fn test_same_abi() {
+        assert_eq!(mem::size_of::<StrongPtr>(), mem::size_of::<*mut Object>());
+        assert_eq!(mem::size_of::<Option<&Object>>(), mem::size_of::<*mut Object>());
+        assert_eq!(mem::size_of::<FnPointer>(), mem::size_of::<Imp>());
+    }
+
+    #[repr(C)]
+    struct NSString {
+        _priv: [u8; 0],
+    }
+
+    unsafe impl Message for NSString { }
+
+    unsafe impl TypedObject for NSString {
+        const CLASS_NAME: &'static str = "NSString";
+    }
+
+    unsafe impl<'a> Encode for &'a NSString {
+        const ENCODING: Encoding<'static> = Encoding::Object;
+    }
+
+    #[test]
+    fn test_encode_extended() {
+        assert_eq!(extended_encoding::<&NSString>(), "@\"NSString\"");
+        assert_eq!(extended_encoding::<*const NSString>(), "@\"NSString\"");
+        assert_eq!(extended_encoding::<Option<&NSString>>(), "@\"NSString\"");
+        assert_eq!(extended_encoding::<&Object>(), "@");
+        assert_eq!(extended_encoding::<*mut Object>(), "@");
+        assert_eq!(extended_encoding::<u32>(), "I");
+        assert_eq!(extended_encoding::<[i8; 2]>(), "[2c]");
+        assert_eq!(<(u32, &NSString)>::extended_encodings(), ["I", "@\"NSString\""]);
     }
 }
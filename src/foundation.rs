@@ -0,0 +1,289 @@
+/*!
+Scalar and geometry types used throughout Foundation and Core Graphics, with
+their Objective-C encodings.
+
+Their sizes depend on the target's pointer width, like in Objective-C:
+`NSInteger` and `NSUInteger` are pointer sized and `CGFloat` is a `double` on
+64-bit targets and a `float` on 32-bit ones.
+
+# Example
+
+``` no_run
+# #[macro_use] extern crate objc;
+# use objc::foundation::{NSRange, NSUInteger};
+# use objc::runtime::Object;
+# fn main() {
+# let string: *mut Object = std::ptr::null_mut();
+let range = NSRange::new(0, 5);
+let substring: *mut Object = unsafe {
+    msg_send![string, substringWithRange:range]
+};
+let length: NSUInteger = unsafe { msg_send![substring, length] };
+# }
+```
+*/
+
+use crate::{Encode, EncodeExtended, Encoding};
+
+/// A signed integer as wide as a pointer.
+pub type NSInteger = isize;
+
+/// An unsigned integer as wide as a pointer.
+pub type NSUInteger = usize;
+
+/// The floating point type of graphics values, a `double` on 64-bit targets.
+#[cfg(target_pointer_width = "64")]
+pub type CGFloat = f64;
+
+/// The floating point type of graphics values, a `float` on 32-bit targets.
+#[cfg(not(target_pointer_width = "64"))]
+pub type CGFloat = f32;
+
+/// A UTF-16 code unit, as in the characters of an `NSString`.
+#[allow(non_camel_case_types)]
+pub type unichar = u16;
+
+/// The value returned by searches that don't find anything, like the
+/// location of an `NSRange`.
+#[allow(non_upper_case_globals)]
+pub const NSNotFound: NSInteger = NSInteger::MAX;
+
+/// A range of items, like characters of a string or indices of an array.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NSRange {
+    /// The start of the range.
+    pub location: NSUInteger,
+    /// The number of items in the range.
+    pub length: NSUInteger,
+}
+
+impl NSRange {
+    /// Constructs an `NSRange` with the given location and length.
+    pub fn new(location: NSUInteger, length: NSUInteger) -> NSRange {
+        NSRange { location: location, length: length }
+    }
+
+    /// Returns the location just past the end of self.
+    pub fn end(&self) -> NSUInteger {
+        self.location + self.length
+    }
+}
+
+unsafe impl Encode for NSRange {
+    const ENCODING: Encoding<'static> =
+        Encoding::Struct("_NSRange", &[NSUInteger::ENCODING, NSUInteger::ENCODING]);
+}
+
+unsafe impl EncodeExtended for NSRange { }
+
+/// A point in a two-dimensional coordinate system.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CGPoint {
+    /// The x coordinate.
+    pub x: CGFloat,
+    /// The y coordinate.
+    pub y: CGFloat,
+}
+
+impl CGPoint {
+    /// Constructs a `CGPoint` with the given coordinates.
+    pub fn new(x: CGFloat, y: CGFloat) -> CGPoint {
+        CGPoint { x: x, y: y }
+    }
+}
+
+unsafe impl Encode for CGPoint {
+    const ENCODING: Encoding<'static> =
+        Encoding::Struct("CGPoint", &[CGFloat::ENCODING, CGFloat::ENCODING]);
+}
+
+unsafe impl EncodeExtended for CGPoint { }
+
+/// A width and a height.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CGSize {
+    /// The width.
+    pub width: CGFloat,
+    /// The height.
+    pub height: CGFloat,
+}
+
+impl CGSize {
+    /// Constructs a `CGSize` with the given width and height.
+    pub fn new(width: CGFloat, height: CGFloat) -> CGSize {
+        CGSize { width: width, height: height }
+    }
+}
+
+unsafe impl Encode for CGSize {
+    const ENCODING: Encoding<'static> =
+        Encoding::Struct("CGSize", &[CGFloat::ENCODING, CGFloat::ENCODING]);
+}
+
+unsafe impl EncodeExtended for CGSize { }
+
+/// A rectangle with an origin and a size.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CGRect {
+    /// The origin of the rectangle.
+    pub origin: CGPoint,
+    /// The width and height of the rectangle.
+    pub size: CGSize,
+}
+
+impl CGRect {
+    /// Constructs a `CGRect` with the given origin and size.
+    pub fn new(origin: CGPoint, size: CGSize) -> CGRect {
+        CGRect { origin: origin, size: size }
+    }
+}
+
+unsafe impl Encode for CGRect {
+    const ENCODING: Encoding<'static> =
+        Encoding::Struct("CGRect", &[CGPoint::ENCODING, CGSize::ENCODING]);
+}
+
+unsafe impl EncodeExtended for CGRect { }
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::ToString;
+    use core::mem;
+    use crate::Encode;
+    use super::*;
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_encodings_64() {
+        assert_eq!(NSInteger::ENCODING.to_string(), "q");
+        assert_eq!(NSUInteger::ENCODING.to_string(), "Q");
+        assert_eq!(CGFloat::ENCODING.to_string(), "d");
+        assert_eq!(NSRange::ENCODING.to_string(), "{_NSRange=QQ}");
+        assert_eq!(CGPoint::ENCODING.to_string(), "{CGPoint=dd}");
+        assert_eq!(CGSize::ENCODING.to_string(), "{CGSize=dd}");
+        assert_eq!(CGRect::ENCODING.to_string(), "{CGRect={CGPoint=dd}{CGSize=dd}}");
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn test_encodings_32() {
+        assert_eq!(NSInteger::ENCODING.to_string(), "i");
+        assert_eq!(NSUInteger::ENCODING.to_string(), "I");
+        assert_eq!(CGFloat::ENCODING.to_string(), "f");
+        assert_eq!(NSRange::ENCODING.to_string(), "{_NSRange=II}");
+        assert_eq!(CGRect::ENCODING.to_string(), "{CGRect={CGPoint=ff}{CGSize=ff}}");
+    }
+
+    #[test]
+    fn test_layouts() {
+        let word = mem::size_of::<usize>();
+        assert_eq!(mem::size_of::<NSRange>(), 2 * word);
+        assert_eq!(mem::size_of::<CGPoint>(), 2 * mem::size_of::<CGFloat>());
+        assert_eq!(mem::size_of::<CGRect>(), 4 * mem::size_of::<CGFloat>());
+        assert_eq!(mem::size_of::<unichar>(), 2);
+    }
+
+    #[test]
+    fn test_parsed_layouts() {
+        use crate::parse::{Layout, ParsedEncoding};
+
+        fn layout<T: Encode>() -> Option<Layout> {
+            ParsedEncoding::from_encoding(&T::ENCODING).layout()
+        }
+        fn expected<T>() -> Option<Layout> {
+            Some(Layout { size: mem::size_of::<T>(), align: mem::align_of::<T>() })
+        }
+        assert_eq!(layout::<NSRange>(), expected::<NSRange>());
+        assert_eq!(layout::<CGPoint>(), expected::<CGPoint>());
+        assert_eq!(layout::<CGSize>(), expected::<CGSize>());
+        assert_eq!(layout::<CGRect>(), expected::<CGRect>());
+    }
+
+    ns_enum! {
+        enum TestResult: NSInteger {
+            Less = -1,
+            Equal = 0,
+            Greater = 1,
+        }
+    }
+
+    ns_options! {
+        struct TestOptions: NSUInteger {
+            const First = 1 << 0;
+            const Second = 1 << 1;
+            const Both = 0b11;
+        }
+    }
+
+    #[test]
+    fn test_ns_enum() {
+        assert_eq!(TestResult::ENCODING, NSInteger::ENCODING);
+        assert_eq!(mem::size_of::<TestResult>(), mem::size_of::<NSInteger>());
+        assert_eq!(TestResult::Less.name(), Some("Less"));
+        assert_eq!(TestResult(5).name(), None);
+        assert_eq!(format!("{:?}", TestResult::Greater), "TestResult::Greater");
+        assert_eq!(format!("{:?}", TestResult(5)), "TestResult(5)");
+    }
+
+    #[test]
+    fn test_ns_options() {
+        assert_eq!(TestOptions::ENCODING, NSUInteger::ENCODING);
+        let mut options = TestOptions::First;
+        assert!(!options.contains(TestOptions::Both));
+        assert!(options.intersects(TestOptions::Both));
+        options |= TestOptions::Second;
+        assert_eq!(options, TestOptions::Both);
+        options.remove(TestOptions::First);
+        assert_eq!(options, TestOptions::Second);
+        assert_eq!(TestOptions::Both - TestOptions::Second, TestOptions::First);
+        assert!(TestOptions::empty().is_empty());
+
+        assert_eq!(format!("{:?}", TestOptions::empty()), "TestOptions(0x0)");
+        assert_eq!(format!("{:?}", TestOptions::Both), "TestOptions(First | Second)");
+        assert_eq!(format!("{:?}", TestOptions(0b110)), "TestOptions(Second | 0x4)");
+    }
+
+    #[cfg(not(feature = "objfw"))]
+    #[test]
+    fn test_declared_method() {
+        use crate::declare::ClassDecl;
+        use crate::runtime::{Class, Sel};
+        use crate::Message;
+
+        extern fn compare(_cls: &Class, _cmd: Sel, value: NSInteger, options: TestOptions)
+                -> TestResult {
+            if options.contains(TestOptions::Second) {
+                TestResult(-value)
+            } else {
+                TestResult(value)
+            }
+        }
+
+        let superclass = crate::test_utils::custom_class();
+        let mut decl = ClassDecl::new("NSEnumTestObject", superclass).unwrap();
+        unsafe {
+            add_class_method!(decl, sel!(compare:options:),
+                compare as extern fn(&Class, Sel, NSInteger, TestOptions) -> TestResult);
+        }
+        let cls = decl.register();
+
+        assert!(cls.verify_message::<(NSInteger, TestOptions), TestResult>(
+            sel!(compare:options:)).is_ok());
+        let result: TestResult = unsafe {
+            msg_send![cls, compare:1isize options:TestOptions::Both]
+        };
+        assert_eq!(result, TestResult::Less);
+    }
+
+    #[test]
+    fn test_range() {
+        let range = NSRange::new(3, 4);
+        assert_eq!(range.end(), 7);
+        assert_eq!(NSNotFound as NSUInteger, usize::MAX >> 1);
+    }
+}
@@ -0,0 +1,438 @@
+/*!
+Functionality for exporting Objective-C headers for classes declared in Rust.
+
+An `InterfaceDecl` describes the `@interface` of a class with the same Rust
+types used to declare its methods, so Objective-C code can message the class
+without casting. It doesn't need the runtime, so it can be built in a build
+script; a `ClassDecl` also builds one from the methods added to it, and one can
+be read back from a registered `Class`.
+
+Pairs of getters and setters, like `-number` and `-setNumber:`, are declared as
+properties. Since classes declared at runtime have no linker symbol, the
+header also defines a function returning the class, like `MyNumberClass()`,
+to use as the receiver of class messages.
+
+# Example
+
+``` no_run
+# use objc::header::{Header, InterfaceDecl};
+let mut decl = InterfaceDecl::new("MyNumber", Some("NSObject"));
+decl.add_method::<(), u32>("number");
+decl.add_method::<(u32,), ()>("setNumber:");
+decl.add_class_method::<(u32,), *mut objc::runtime::Object>("numberWithValue:");
+
+let mut header = Header::new("MY_NUMBER_H");
+header.add_import("<Foundation/Foundation.h>");
+header.add_interface(decl);
+# #[cfg(feature = "std")]
+header.write_to_file("MyNumber.h").unwrap();
+```
+*/
+
+use alloc::borrow::ToOwned;
+#[cfg(not(feature = "objfw"))]
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(not(feature = "objfw"))]
+use core::ffi::CStr;
+use core::fmt::{self, Write};
+
+#[cfg(not(feature = "objfw"))]
+use crate::runtime::{Class, Method};
+use crate::{Encode, EncodeArguments, Encoding};
+use crate::parse::ParsedEncoding;
+#[cfg(not(feature = "objfw"))]
+use crate::parse;
+
+#[derive(Clone, Debug)]
+struct MethodDecl {
+    instance: bool,
+    name: String,
+    ret: String,
+    args: Vec<String>,
+}
+
+impl MethodDecl {
+    fn write<W: Write>(&self, w: &mut W) -> fmt::Result {
+        let kind = if self.instance { '-' } else { '+' };
+        write!(w, "{} ({})", kind, self.ret)?;
+        if !self.name.contains(':') {
+            return writeln!(w, "{};", self.name);
+        }
+        let labels = self.name.split(':').take(self.name.matches(':').count());
+        for (i, label) in labels.enumerate() {
+            let arg = self.args.get(i).map_or("id", |arg| &**arg);
+            if i > 0 {
+                w.write_char(' ')?;
+            }
+            write!(w, "{}:({})arg{}", label, arg, i)?;
+        }
+        writeln!(w, ";")
+    }
+}
+
+fn type_name(encoding: &Encoding) -> String {
+    ParsedEncoding::from_encoding(encoding).c_declaration("")
+}
+
+#[cfg(not(feature = "objfw"))]
+fn parsed_type_name(encoding: &str) -> String {
+    match ParsedEncoding::parse(encoding) {
+        Some(enc) => enc.c_declaration(""),
+        None => format!("void * /* {} */", encoding),
+    }
+}
+
+/// Returns the name of the property set by a setter like `setNumber:`.
+fn setter_property(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("set")?.strip_suffix(':')?;
+    let mut chars = rest.chars();
+    let first = chars.next()?;
+    if rest.contains(':') || !first.is_ascii_uppercase() {
+        return None;
+    }
+    let mut property = String::with_capacity(rest.len());
+    // Acronyms like the `URL` of `setURL:` keep their case
+    if chars.clone().next().map_or(false, |c| c.is_ascii_uppercase()) {
+        property.push(first);
+    } else {
+        property.push(first.to_ascii_lowercase());
+    }
+    property.push_str(chars.as_str());
+    Some(property)
+}
+
+/// A description of the `@interface` of a class, for exporting it in a
+/// header.
+#[derive(Clone, Debug)]
+pub struct InterfaceDecl {
+    name: String,
+    superclass: Option<String>,
+    protocols: Vec<String>,
+    methods: Vec<MethodDecl>,
+}
+
+impl InterfaceDecl {
+    /// Constructs an `InterfaceDecl` for a class with the given name and
+    /// superclass, or a root class if `superclass` is `None`.
+    pub fn new(name: &str, superclass: Option<&str>) -> InterfaceDecl {
+        InterfaceDecl {
+            name: name.to_owned(),
+            superclass: superclass.map(str::to_owned),
+            protocols: Vec::new(),
+            methods: Vec::new(),
+        }
+    }
+
+    /// Reads the `InterfaceDecl` of a registered class from its superclass,
+    /// adopted protocols and methods, each sorted by name.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
+    pub fn from_class(cls: &Class) -> InterfaceDecl {
+        let superclass = cls.superclass().map(Class::name);
+        let mut decl = InterfaceDecl::new(cls.name(), superclass);
+        let mut protocols: Vec<&str> = cls.adopted_protocols().iter()
+            .map(|proto| proto.name())
+            .collect();
+        protocols.sort();
+        for proto in protocols {
+            decl.add_protocol(proto);
+        }
+
+        for &(instance, cls) in &[(false, cls.metaclass()), (true, cls)] {
+            let mut methods: Vec<&Method> = cls.instance_methods().iter().cloned().collect();
+            methods.sort_by(|a, b| a.name().name_cstr().cmp(b.name().name_cstr()));
+            for method in methods {
+                // Skip the receiver and selector
+                let args = (2..method.arguments_count())
+                    .filter_map(|i| method.argument_type(i))
+                    .map(|arg| parsed_type_name(&arg))
+                    .collect();
+                decl.methods.push(MethodDecl {
+                    instance: instance,
+                    name: method.name().name().to_owned(),
+                    ret: parsed_type_name(&method.return_type()),
+                    args: args,
+                });
+            }
+        }
+        decl
+    }
+
+    /// Returns the name of the declared class.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds a protocol the class adopts.
+    pub fn add_protocol(&mut self, name: &str) {
+        if !self.protocols.iter().any(|proto| proto == name) {
+            self.protocols.push(name.to_owned());
+        }
+    }
+
+    fn push_method(&mut self, instance: bool, name: &str, ret: &Encoding,
+            args: &[Encoding]) {
+        self.methods.push(MethodDecl {
+            instance: instance,
+            name: name.to_owned(),
+            ret: type_name(ret),
+            args: args.iter().map(type_name).collect(),
+        });
+    }
+
+    /// Adds a method from the type encoding it was added to a class with.
+    #[cfg(not(feature = "objfw"))]
+    pub(crate) fn push_method_types(&mut self, instance: bool, name: &str,
+            types: &CStr) {
+        // Skip the return type, receiver and selector
+        let count = parse::method_arguments_count(types);
+        let args = (3..count + 1)
+            .filter_map(|i| parse::method_type(types, i))
+            .map(parsed_type_name)
+            .collect();
+        let ret = parse::method_type(types, 0).map_or_else(
+            || "void".to_owned(), parsed_type_name);
+        self.methods.push(MethodDecl {
+            instance: instance,
+            name: name.to_owned(),
+            ret: ret,
+            args: args,
+        });
+    }
+
+    /// Adds an instance method with the given selector name, like
+    /// `setNumber:`, taking `Args` and returning `Ret`.
+    pub fn add_method<Args, Ret>(&mut self, name: &str)
+            where Args: EncodeArguments, Ret: Encode {
+        self.push_method(true, name, &Ret::ENCODING, Args::ENCODINGS);
+    }
+
+    /// Adds a class method with the given selector name taking `Args` and
+    /// returning `Ret`.
+    pub fn add_class_method<Args, Ret>(&mut self, name: &str)
+            where Args: EncodeArguments, Ret: Encode {
+        self.push_method(false, name, &Ret::ENCODING, Args::ENCODINGS);
+    }
+
+    /// Returns the indices of the instance methods that are getters and
+    /// setters of a property, with the property name and type.
+    fn properties(&self) -> Vec<(usize, usize, &str, &str)> {
+        let mut properties = Vec::new();
+        for (setter, method) in self.methods.iter().enumerate() {
+            let name = match setter_property(&method.name) {
+                Some(name) if method.instance && method.ret == "void" => name,
+                _ => continue,
+            };
+            let getter = self.methods.iter().position(|getter| {
+                getter.instance && getter.name == name && getter.args.is_empty() &&
+                    method.args.first() == Some(&getter.ret)
+            });
+            if let Some(getter) = getter {
+                let getter_decl = &self.methods[getter];
+                properties.push((getter, setter, &*getter_decl.name, &*getter_decl.ret));
+            }
+        }
+        properties
+    }
+
+    /// Writes the `@interface` of self, followed by a function returning the
+    /// class.
+    pub fn write<W: Write>(&self, w: &mut W) -> fmt::Result {
+        if self.superclass.is_none() {
+            w.write_str("__attribute__((objc_root_class))\n")?;
+        }
+        write!(w, "@interface {}", self.name)?;
+        if let Some(ref superclass) = self.superclass {
+            write!(w, " : {}", superclass)?;
+        }
+        if !self.protocols.is_empty() {
+            write!(w, " <{}>", self.protocols.join(", "))?;
+        }
+        w.write_str("\n\n")?;
+
+        let properties = self.properties();
+        for &(_, _, name, ty) in &properties {
+            // Without an ownership attribute, object properties warn
+            let separator = if ty.ends_with('*') { "" } else { " " };
+            writeln!(w, "@property (nonatomic, assign) {}{}{};", ty, separator, name)?;
+        }
+        if !properties.is_empty() {
+            w.write_char('\n')?;
+        }
+
+        for (i, method) in self.methods.iter().enumerate() {
+            let is_accessor = properties.iter()
+                .any(|&(getter, setter, _, _)| i == getter || i == setter);
+            if !is_accessor {
+                method.write(w)?;
+            }
+        }
+        w.write_str("@end\n\n")?;
+
+        writeln!(w, "static inline Class {}Class(void) {{", self.name)?;
+        writeln!(w, "    return (Class)objc_getClass(\"{}\");", self.name)?;
+        w.write_str("}\n")
+    }
+}
+
+impl fmt::Display for InterfaceDecl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+/// An Objective-C header file declaring the interfaces of classes.
+#[derive(Clone, Debug)]
+pub struct Header {
+    guard: String,
+    imports: Vec<String>,
+    interfaces: Vec<InterfaceDecl>,
+}
+
+impl Header {
+    /// Constructs an empty `Header` whose contents are guarded by the given
+    /// macro name, importing `<objc/runtime.h>` and `<stdbool.h>`.
+    pub fn new(guard: &str) -> Header {
+        Header {
+            guard: guard.to_owned(),
+            imports: vec!["<objc/runtime.h>".to_owned(), "<stdbool.h>".to_owned()],
+            interfaces: Vec::new(),
+        }
+    }
+
+    /// Adds an import, like `<Foundation/Foundation.h>` or `"Other.h"`.
+    /// Superclasses and protocols must be declared by an imported header.
+    pub fn add_import(&mut self, header: &str) {
+        if !self.imports.iter().any(|import| import == header) {
+            self.imports.push(header.to_owned());
+        }
+    }
+
+    /// Adds the interface of a class.
+    pub fn add_interface(&mut self, interface: InterfaceDecl) {
+        self.interfaces.push(interface);
+    }
+
+    /// Writes the contents of the header.
+    pub fn write<W: Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "#ifndef {}", self.guard)?;
+        writeln!(w, "#define {}\n", self.guard)?;
+        for import in &self.imports {
+            writeln!(w, "#import {}", import)?;
+        }
+        for interface in &self.interfaces {
+            w.write_char('\n')?;
+            interface.write(w)?;
+        }
+        writeln!(w, "\n#endif")
+    }
+
+    /// Writes the contents of the header to a file at the given path,
+    /// replacing it if it exists.
+    #[cfg(feature = "std")]
+    pub fn write_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use crate::runtime::{Object, Sel};
+    use super::{Header, InterfaceDecl, setter_property};
+
+    #[test]
+    fn test_setter_property() {
+        assert_eq!(setter_property("setNumber:").as_deref(), Some("number"));
+        assert_eq!(setter_property("setURL:").as_deref(), Some("URL"));
+        assert_eq!(setter_property("setURLString:").as_deref(), Some("URLString"));
+        assert_eq!(setter_property("setup"), None);
+        assert_eq!(setter_property("settle:"), None);
+        assert_eq!(setter_property("setX:y:"), None);
+    }
+
+    #[test]
+    fn test_interface() {
+        let mut decl = InterfaceDecl::new("MyNumber", Some("NSObject"));
+        decl.add_protocol("NSCopying");
+        decl.add_method::<(), u32>("number");
+        decl.add_method::<(u32,), ()>("setNumber:");
+        decl.add_method::<(), *mut Object>("delegate");
+        decl.add_method::<(*mut Object,), ()>("setDelegate:");
+        decl.add_method::<(i32, Sel), bool>("addValue:withSelector:");
+        decl.add_method::<(i32,), ()>("setOther:");
+        decl.add_class_method::<(u32,), *mut Object>("numberWithValue:");
+
+        let expected = "\
+@interface MyNumber : NSObject <NSCopying>
+
+@property (nonatomic, assign) unsigned int number;
+@property (nonatomic, assign) id delegate;
+
+- (bool)addValue:(int)arg0 withSelector:(SEL)arg1;
+- (void)setOther:(int)arg0;
++ (id)numberWithValue:(unsigned int)arg0;
+@end
+
+static inline Class MyNumberClass(void) {
+    return (Class)objc_getClass(\"MyNumber\");
+}
+";
+        assert_eq!(decl.to_string(), expected);
+    }
+
+    #[cfg(not(feature = "objfw"))]
+    #[test]
+    fn test_from_class() {
+        let cls = crate::test_utils::custom_class();
+        let interface = InterfaceDecl::from_class(cls).to_string();
+        assert!(interface.starts_with(
+            "__attribute__((objc_root_class))\n@interface CustomObject"));
+        assert!(interface.contains("@property (nonatomic, assign) unsigned int foo;\n"));
+        assert!(interface.contains("+ (int)addNumber:(int)arg0 toNumber:(int)arg1;\n"));
+        assert!(interface.contains("- (void)setBar:(unsigned int)arg0;\n"));
+        assert!(interface.contains("objc_getClass(\"CustomObject\")"));
+    }
+
+    #[test]
+    fn test_header() {
+        let mut header = Header::new("MY_ROOT_H");
+        header.add_import("\"Other.h\"");
+        header.add_import("<objc/runtime.h>");
+        let mut decl = InterfaceDecl::new("MyRoot", None);
+        decl.add_method::<(), *const u8>("name");
+        header.add_interface(decl);
+
+        let expected = "\
+#ifndef MY_ROOT_H
+#define MY_ROOT_H
+
+#import <objc/runtime.h>
+#import <stdbool.h>
+#import \"Other.h\"
+
+__attribute__((objc_root_class))
+@interface MyRoot
+
+- (char *)name;
+@end
+
+static inline Class MyRootClass(void) {
+    return (Class)objc_getClass(\"MyRoot\");
+}
+
+#endif
+";
+        assert_eq!(header.to_string(), expected);
+    }
+}
@@ -20,6 +20,19 @@ let _: () = msg_send![obj, release];
 # }
 ```
 
+Messages sent repeatedly in performance-sensitive code can be prepared ahead
+of time with [`PreparedMessage`](struct.PreparedMessage.html), which looks up
+the method's implementation once and calls it directly.
+
+Messages to nil return zero when the runtime defines it, like Apple's runtime,
+and zero is a valid value of the return type; otherwise the message fails
+with a `MessageError`. This can be made stricter with
+[`set_nil_policy`](fn.set_nil_policy.html).
+
+An object's `Debug` output only names its class and address; for logging, its
+`description` can be retrieved with `Object::description` or displayed with
+`Object::display`.
+
 # Reference counting
 
 Utilities for reference counting Objective-C objects are provided in the
@@ -48,49 +61,228 @@ To use this functionality, enable the `"verify_message"` feature.
 With this feature enabled, type checking is performed for every message send,
 which also requires that all arguments and return values for all messages
 implement `Encode`.
+The encoding of the return type then also chooses the variant of
+`objc_msgSend` to call, like `objc_msgSend_stret`; without the feature it is
+chosen from the size of the return type, unless the message is sent with
+[`Message::send_message_encoded`](trait.Message.html#method.send_message_encoded).
 
 If this requirement is burdensome or you'd rather
 just verify specific messages, you can call the
 [`Message::verify_message`](trait.Message.html#method.verify_message) method
 for specific selectors.
 
+By default the encodings must match exactly, but the compiler may encode
+compatible types differently, like a `BOOL` as either `c` or `B`.
+A looser comparison can be chosen with
+[`set_verification_level`](fn.set_verification_level.html).
+
+Messages can also be verified at runtime without the feature, by setting the
+`OBJC_VERIFY_MESSAGE` environment variable or calling
+[`set_runtime_verification`](fn.set_runtime_verification.html).
+This only checks the sizes of argument and return types and reports any
+mismatches without failing the message, so it doesn't require `Encode`
+implementations and can be turned on without rebuilding.
+
+# Foundation types
+
+The `foundation` module defines the scalar and geometry types most messages
+take, like `NSInteger`, `CGFloat`, `NSRange` and `CGRect`, with sizes and
+encodings that match Objective-C on 32- and 64-bit targets.
+The `ns_enum!` and `ns_options!` macros declare typed wrappers of enums and
+option sets, which can be sent in messages in place of their integers.
+
+# Typed objects
+
+Besides references to `Object`, `Class` and `Protocol`, optional references
+and raw pointers to them can be sent in messages and used in declared methods,
+all encoded as the object pointer they wrap, as can function pointers wrapped
+in a `runtime::FnPointer`. `NonNull<Object>`, `Imp` and other function
+pointers don't implement `Encode`, since the trait is defined in `objc-encode`;
+send a reference or a `FnPointer` instead. Neither does `StrongPtr`: wrap the
+`*mut Object` returned by a `new` or `copy` method with `StrongPtr::new`. Typed wrappers
+of a class implement `TypedObject` to name it, giving references to them an
+extended type encoding like `@"NSString"` through `EncodeExtended`.
+Methods declared with `ClassDecl::add_method_extended` and the other
+`_extended` methods of the `declare` module register these encodings.
+
+# Deriving `Encode`
+
+Enabling the `"derive"` feature provides a `#[derive(Encode)]` macro for
+`#[repr(C)]` structs and unions, `#[repr(transparent)]` types and enums
+without fields, so their encodings stay in sync with their fields:
+
+``` ignore
+#[derive(Encode)]
+#[encode(name = "_NSRange")]
+#[repr(C)]
+struct NSRange {
+    location: usize,
+    length: usize,
+}
+```
+
+Types without a C layout are rejected at compile time.
+
+# `no_std` support
+
+The crate's `"std"` feature is enabled by default. With it disabled, the crate
+only depends on `core` and `alloc`, so it can be used wherever an Objective-C
+runtime is available without the standard library. `MessageError` then doesn't
+implement `std::error::Error`, and runtime verification is only enabled by
+[`set_runtime_verification`](fn.set_runtime_verification.html) and has no
+default reporter.
+
 # Support for other Operating Systems
 
 The bindings can be used on Linux or *BSD utilizing the
 [GNUstep Objective-C runtime](https://www.github.com/gnustep/libobjc2).
+
+The available functions differ between versions of libobjc2, so the crate's
+build script probes the installed version with `pkg-config`, except when
+cross-compiling unless `PKG_CONFIG_ALLOW_CROSS` is set. The version can
+instead be selected with one of the `"gnustep-1-7"`, `"gnustep-2-0"` or
+`"gnustep-2-1"` features; otherwise version 1.7, the oldest one supported, is
+assumed. On version 2.0 and later, messages are sent with `objc_msgSend`
+instead of looking up the method first on the architectures where it's
+supported.
+
+The [ObjFW runtime](https://objfw.nil.im) can be used instead by enabling the
+`"objfw"` feature. ObjFW can't add instance variables or protocols to classes
+at runtime, so the `declare` module isn't available with it, nor are the
+methods that look up protocols.
+
+GCC's own libobjc can be used by enabling the `"gcc_libobjc"` feature.
+It can't declare protocols at runtime and has no weak references, so
+`ProtocolDecl` and `WeakPtr` are replaced with deprecated types that can't be
+constructed, whose deprecation notes explain why.
+
+With the `"gnustep_slot_cache"` feature enabled, each `msg_send!` on GNUstep
+caches the method it looks up and reuses it for later sends to instances of
+the same class, until the runtime invalidates it. With libobjc2 1.x the slot
+is kept and checked against its version; from 2.0 (the `"gnustep-2-0"`
+feature, or detected by the build script) only the implementation is kept,
+along with the runtime's global method cache version.
+
+# Mock runtime
+
+Code built on this crate can be unit tested without an Objective-C runtime
+installed by enabling the `"mock-runtime"` feature. The crate then doesn't
+link libobjc, and instead uses a fake runtime implemented in Rust that
+supports declaring classes and protocols, sending messages, and reference
+counting with weak references and autorelease pools. Only a root `NSObject`
+class with its memory management methods is present; messages that aren't
+implemented panic.
+
+# Exporting headers
+
+Classes declared in Rust can be used from Objective-C through a header
+exported with the `header` module, from a `ClassDecl`, a registered class, or
+in a build script from the method types alone.
+
+# Dumping classes
+
+Enabling the `"class-dump"` feature builds a `class-dump` binary, which loads
+the shared libraries it's given and prints the classes and protocols they
+register as Objective-C `@interface` and `@protocol` declarations:
+
+``` text
+cargo run --features class-dump --bin class-dump -- --prefix NS \
+    --image gnustep-base /usr/lib/libgnustep-base.so
+```
+
+The same output can be produced programmatically with the `dump` module.
+
+# Generating wrappers
+
+Enabling the `"codegen"` feature builds an `objc-codegen` binary, which takes
+the same arguments as `class-dump` but prints Rust source with a wrapper type
+for each class, with typed methods sending its messages, and a trait for each
+protocol. The `codegen` module provides the same generator as a library.
 */
 
 #![crate_name = "objc"]
 #![crate_type = "lib"]
 
 #![warn(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
 extern crate malloc_buf;
 extern crate objc_encode;
+#[cfg(feature = "derive")]
+extern crate objc_derive;
 #[cfg(feature = "exception")]
 extern crate objc_exception;
 
+#[cfg(all(feature = "objfw", feature = "gcc_libobjc"))]
+compile_error!("The \"objfw\" and \"gcc_libobjc\" features select different runtimes \
+                and can't be enabled together");
+#[cfg(all(feature = "gcc_libobjc", feature = "gnustep_slot_cache"))]
+compile_error!("GCC's libobjc doesn't look up methods by slots, \
+                so the \"gnustep_slot_cache\" feature can't be used with it");
+#[cfg(all(feature = "mock-runtime",
+          any(feature = "objfw", feature = "gcc_libobjc", feature = "gnustep_slot_cache",
+              feature = "exception")))]
+compile_error!("The \"mock-runtime\" feature replaces the Objective-C runtime \
+                and can't be used with features for a real runtime");
+#[cfg(all(not(any(target_os = "macos", target_os = "ios", feature = "objfw",
+                  feature = "gcc_libobjc", feature = "mock-runtime")),
+          not(gnustep_1_7)))]
+compile_error!("libobjc2 versions older than 1.7 lack the ARC functions used for \
+                reference counting, like objc_retain and objc_autoreleasePoolPush");
+#[cfg(all(feature = "class-dump", feature = "objfw"))]
+compile_error!("ObjFW doesn't register protocols by name, \
+                so the \"class-dump\" feature can't be used with it");
+
 pub use objc_encode::{Encode, Encoding};
+#[cfg(feature = "derive")]
+pub use objc_derive::Encode;
 
-pub use crate::encode::EncodeArguments;
-pub use crate::message::{Message, MessageArguments, MessageError};
+pub use crate::encode::{EncodeArguments, EncodeArgumentsExtended, EncodeExtended, TypedObject};
+pub use crate::message::{Message, MessageArguments, MessageError, PreparedMessage};
+pub use crate::message::{NilPolicy, nil_policy, set_nil_policy};
+pub use crate::message::{VerificationLevel, set_verification_level, verification_level};
+pub use crate::message::{RuntimeVerification, VerificationReporter, runtime_verification,
+    set_runtime_verification, set_verification_reporter};
 
 pub use crate::cache::CachedClass as __CachedClass;
 pub use crate::cache::CachedSel as __CachedSel;
 pub use crate::message::send_message as __send_message;
+pub use crate::message::send_message_cached as __send_message_cached;
+pub use crate::message::CachedSlot as __CachedSlot;
 pub use crate::message::send_super_message as __send_super_message;
+pub use crate::macros::sel_args_count as __sel_args_count;
+pub use crate::macros::sel_is_valid as __sel_is_valid;
 
 #[macro_use]
 mod macros;
 
 pub mod runtime;
+#[cfg(not(feature = "objfw"))]
 pub mod declare;
 pub mod rc;
 mod cache;
 mod encode;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+#[cfg(feature = "class-dump")]
+pub mod dump;
 #[cfg(feature = "exception")]
 mod exception;
+pub mod foundation;
+pub mod header;
 mod message;
+#[cfg(feature = "mock-runtime")]
+mod mock;
+mod parse;
+#[cfg(not(feature = "objfw"))]
+mod snapshot;
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "objfw")))]
 mod test_utils;
+
+// ObjFW's runtime doesn't include any classes; the tests use OFObject from
+// the ObjFW library
+#[cfg(all(test, feature = "objfw"))]
+#[link(name = "objfw", kind = "dylib")]
+extern { }
@@ -29,37 +29,123 @@ macro_rules! class {
 /**
 Registers a selector, returning a `Sel`.
 
+The selector can be written as it would be in Objective-C, including keyword
+parts with no name like `performSelector::`. It can also be given as a string
+literal for names that aren't identifiers, which is checked at compile time
+to be either a single name or keyword parts that each end with a colon.
+Names may use any characters but NUL and whitespace.
+
 # Example
 ```
 # #[macro_use] extern crate objc;
 # fn main() {
 let sel = sel!(description);
 let sel = sel!(setObject:forKey:);
+let sel = sel!(performSelector::);
+let sel = sel!("_setObject:forKey:");
+let sel = sel!("weird-selector:with:");
+# }
+```
+
+Malformed selectors fail to compile:
+
+``` compile_fail
+# #[macro_use] extern crate objc;
+# fn main() {
+let sel = sel!("setObject:forKey");
 # }
 ```
 */
 #[macro_export]
 macro_rules! sel {
-    ($name:ident) => ({
+    ($name:literal) => ({
+        const _: () = assert!($crate::__sel_is_valid($name),
+            concat!("Invalid selector ", stringify!($name)));
         static SEL: $crate::__CachedSel = $crate::__CachedSel::new();
-        let name = concat!(stringify!($name), '\0');
+        let name = concat!($name, '\0');
         #[allow(unused_unsafe)]
         unsafe { SEL.get(name) }
     });
-    ($($name:ident :)+) => ({
+    ($($name:tt)+) => ({
         static SEL: $crate::__CachedSel = $crate::__CachedSel::new();
-        let name = concat!($(stringify!($name), ':'),+, '\0');
+        let name = concat!($crate::__sel_name!($($name)+), '\0');
         #[allow(unused_unsafe)]
         unsafe { SEL.get(name) }
     });
 }
 
+/// Expands to the name of a selector as a string literal, rejecting tokens
+/// that don't form a selector.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sel_name {
+    ($name:literal) => ($name);
+    ($name:ident) => (stringify!($name));
+    ($($name:tt)+) => ($crate::__sel_keywords!([] $($name)+));
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sel_keywords {
+    ([$($acc:tt)*]) => (concat!($($acc)*));
+    ([$($acc:tt)*] $name:ident : $($rest:tt)*) =>
+        ($crate::__sel_keywords!([$($acc)* stringify!($name), ':',] $($rest)*));
+    ([$($acc:tt)*] $name:ident :: $($rest:tt)*) =>
+        ($crate::__sel_keywords!([$($acc)* stringify!($name), "::",] $($rest)*));
+    ([$($acc:tt)*] : $($rest:tt)*) =>
+        ($crate::__sel_keywords!([$($acc)* ':',] $($rest)*));
+    ([$($acc:tt)*] :: $($rest:tt)*) =>
+        ($crate::__sel_keywords!([$($acc)* "::",] $($rest)*));
+}
+
+/// Returns whether the given name is a valid selector: either a single name
+/// or keyword parts that each end with a colon, where a keyword's name may be
+/// empty. The runtime registers any C string, so names may use any
+/// characters but NUL and whitespace.
+#[doc(hidden)]
+pub const fn sel_is_valid(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() {
+        return false;
+    }
+    let mut has_colon = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'\0' || b.is_ascii_whitespace() {
+            return false;
+        }
+        if b == b':' {
+            has_colon = true;
+        }
+        i += 1;
+    }
+    // With keywords, the last part must end with a colon too
+    !has_colon || bytes[bytes.len() - 1] == b':'
+}
+
+/// Returns the number of arguments taken by the selector with the given name.
+#[doc(hidden)]
+pub const fn sel_args_count(name: &str) -> usize {
+    let bytes = name.as_bytes();
+    let mut count = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b':' {
+            count += 1;
+        }
+        i += 1;
+    }
+    count
+}
+
 /**
 Sends a message to an object.
 
 The first argument can be any type that dereferences to a type that implements
 `Message`, like a reference, pointer, or an `Id`.
-The syntax is similar to the message syntax in Objective-C.
+The syntax is similar to the message syntax in Objective-C, including
+keyword parts with no name, like `msg_send![obj, performSelector:sel :arg]`.
 Variadic arguments are not currently supported.
 
 # Example
@@ -81,37 +167,413 @@ macro_rules! msg_send {
     (super($obj:expr, $superclass:expr), $name:ident) => ({
         let sel = $crate::sel!($name);
         let result;
-        match $crate::__send_super_message(&*$obj, $superclass, sel, ()) {
+        match $crate::__send_super_message(::core::ptr::addr_of!(*$obj), $superclass, sel, ()) {
             Err(s) => panic!("{}", s),
             Ok(r) => result = r,
         }
         result
     });
-    (super($obj:expr, $superclass:expr), $($name:ident : $arg:expr)+) => ({
-        let sel = $crate::sel!($($name:)+);
+    (super($obj:expr, $superclass:expr), $($($name:ident)? : $arg:expr)+) => ({
+        let sel = $crate::sel!($($($name)? :)+);
         let result;
-        match $crate::__send_super_message(&*$obj, $superclass, sel, ($($arg,)*)) {
+        match $crate::__send_super_message(::core::ptr::addr_of!(*$obj), $superclass, sel, ($($arg,)*)) {
             Err(s) => panic!("{}", s),
             Ok(r) => result = r,
         }
         result
     });
     ($obj:expr, $name:ident) => ({
+        static SLOT: $crate::__CachedSlot = $crate::__CachedSlot::new();
         let sel = $crate::sel!($name);
         let result;
-        match $crate::__send_message(&*$obj, sel, ()) {
+        match $crate::__send_message_cached(&SLOT, ::core::ptr::addr_of!(*$obj), sel, ()) {
             Err(s) => panic!("{}", s),
             Ok(r) => result = r,
         }
         result
     });
-    ($obj:expr, $($name:ident : $arg:expr)+) => ({
-        let sel = $crate::sel!($($name:)+);
+    ($obj:expr, $($($name:ident)? : $arg:expr)+) => ({
+        static SLOT: $crate::__CachedSlot = $crate::__CachedSlot::new();
+        let sel = $crate::sel!($($($name)? :)+);
         let result;
-        match $crate::__send_message(&*$obj, sel, ($($arg,)*)) {
+        match $crate::__send_message_cached(&SLOT, ::core::ptr::addr_of!(*$obj), sel, ($($arg,)*)) {
             Err(s) => panic!("{}", s),
             Ok(r) => result = r,
         }
         result
     });
 }
+
+/**
+Adds a method to a `ClassDecl`, like `ClassDecl::add_method`.
+
+When the selector is given with `sel!`, the number of arguments it takes is
+checked against the function at compile time rather than at runtime.
+
+# Example
+``` no_run
+# #[macro_use] extern crate objc;
+# use objc::declare::ClassDecl;
+# use objc::runtime::{Object, Sel};
+# fn main() {
+# let mut decl = ClassDecl::new("MyObject", class!(NSObject)).unwrap();
+extern fn set_number(_this: &mut Object, _cmd: Sel, _number: u32) { }
+unsafe {
+    add_method!(decl, sel!(setNumber:),
+        set_number as extern fn(&mut Object, Sel, u32));
+}
+# }
+```
+*/
+#[macro_export]
+macro_rules! add_method {
+    ($decl:expr, sel!($($sel:tt)+), $func:expr) => ({
+        let func = $func;
+        $crate::declare::__check_method_args::<_,
+            { $crate::__sel_args_count($crate::__sel_name!($($sel)+)) }>(&func);
+        $decl.add_method($crate::sel!($($sel)+), func)
+    });
+    ($decl:expr, $sel:expr, $func:expr) => ($decl.add_method($sel, $func));
+}
+
+/**
+Adds a class method to a `ClassDecl`, like `ClassDecl::add_class_method`.
+
+When the selector is given with `sel!`, the number of arguments it takes is
+checked against the function at compile time rather than at runtime.
+*/
+#[macro_export]
+macro_rules! add_class_method {
+    ($decl:expr, sel!($($sel:tt)+), $func:expr) => ({
+        let func = $func;
+        $crate::declare::__check_method_args::<_,
+            { $crate::__sel_args_count($crate::__sel_name!($($sel)+)) }>(&func);
+        $decl.add_class_method($crate::sel!($($sel)+), func)
+    });
+    ($decl:expr, $sel:expr, $func:expr) => ($decl.add_class_method($sel, $func));
+}
+
+/**
+Adds a method description to a `ProtocolDecl`, like
+`ProtocolDecl::add_method_description` or, when prefixed with `class`,
+`ProtocolDecl::add_class_method_description`.
+
+When the selector is given with `sel!`, the number of arguments it takes is
+checked against the argument types at compile time rather than at runtime.
+
+# Example
+``` no_run
+# #[macro_use] extern crate objc;
+# use objc::declare::ProtocolDecl;
+# fn main() {
+let mut decl = ProtocolDecl::new("MyProtocol").unwrap();
+add_method_description!(decl, sel!(setNumber:), (u32) -> (), true);
+add_method_description!(decl, class sel!(defaultNumber), () -> u32, false);
+# }
+```
+*/
+#[macro_export]
+macro_rules! add_method_description {
+    ($decl:expr, class sel!($($sel:tt)+), ($($arg:ty),*) -> $ret:ty, $required:expr) => ({
+        let () = $crate::declare::__ArgsCountCheck::<($($arg,)*),
+            { $crate::__sel_args_count($crate::__sel_name!($($sel)+)) }>::CHECK;
+        $decl.add_class_method_description::<($($arg,)*), $ret>(
+            $crate::sel!($($sel)+), $required)
+    });
+    ($decl:expr, class $sel:expr, ($($arg:ty),*) -> $ret:ty, $required:expr) => (
+        $decl.add_class_method_description::<($($arg,)*), $ret>($sel, $required)
+    );
+    ($decl:expr, sel!($($sel:tt)+), ($($arg:ty),*) -> $ret:ty, $required:expr) => ({
+        let () = $crate::declare::__ArgsCountCheck::<($($arg,)*),
+            { $crate::__sel_args_count($crate::__sel_name!($($sel)+)) }>::CHECK;
+        $decl.add_method_description::<($($arg,)*), $ret>(
+            $crate::sel!($($sel)+), $required)
+    });
+    ($decl:expr, $sel:expr, ($($arg:ty),*) -> $ret:ty, $required:expr) => (
+        $decl.add_method_description::<($($arg,)*), $ret>($sel, $required)
+    );
+}
+
+/**
+Declares a type for an Objective-C `NS_ENUM`, with a constant for each case.
+
+The type is a transparent wrapper of the underlying integer, usually an
+`NSInteger`, so it can be sent in messages and used in methods declared with
+`ClassDecl`, and its `Encode` impl has the integer's encoding.
+Unlike a Rust enum, any value is valid, including cases added by later
+versions of a framework, so `match`es need a fallback arm.
+
+# Example
+```
+# #[macro_use] extern crate objc;
+use objc::foundation::NSInteger;
+
+ns_enum! {
+    /// The result of comparing two values.
+    pub enum NSComparisonResult: NSInteger {
+        NSOrderedAscending = -1,
+        NSOrderedSame = 0,
+        NSOrderedDescending = 1,
+    }
+}
+
+# fn main() {
+let result = NSComparisonResult(1);
+match result {
+    NSComparisonResult::NSOrderedAscending => println!("less"),
+    NSComparisonResult::NSOrderedSame => println!("equal"),
+    NSComparisonResult::NSOrderedDescending => println!("greater"),
+    NSComparisonResult(value) => println!("unknown result {}", value),
+}
+assert_eq!(format!("{:?}", result), "NSComparisonResult::NSOrderedDescending");
+# }
+```
+*/
+#[macro_export]
+macro_rules! ns_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident: $int:ty {
+            $(
+                $(#[$case_meta:meta])*
+                $case:ident = $value:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        $vis struct $name(pub $int);
+
+        #[allow(dead_code, non_upper_case_globals)]
+        impl $name {
+            $(
+                $(#[$case_meta])*
+                pub const $case: $name = $name($value);
+            )*
+
+            /// Returns the name of the case of self, or `None` if its value
+            /// isn't one of the declared cases.
+            pub fn name(&self) -> ::core::option::Option<&'static str> {
+                $(
+                    if *self == $name::$case {
+                        return ::core::option::Option::Some(stringify!($case));
+                    }
+                )*
+                ::core::option::Option::None
+            }
+        }
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                match self.name() {
+                    ::core::option::Option::Some(name) =>
+                        write!(f, "{}::{}", stringify!($name), name),
+                    ::core::option::Option::None =>
+                        write!(f, "{}({:?})", stringify!($name), self.0),
+                }
+            }
+        }
+
+        unsafe impl $crate::Encode for $name {
+            const ENCODING: $crate::Encoding<'static> = <$int as $crate::Encode>::ENCODING;
+        }
+
+        unsafe impl $crate::EncodeExtended for $name { }
+    };
+}
+
+/**
+Declares a type for an Objective-C `NS_OPTIONS` set, with a constant for each
+option.
+
+The type is a transparent wrapper of the underlying integer, usually an
+`NSUInteger`, with the bitwise operators and methods like `contains` and
+`insert` of a set of flags. Like `ns_enum!`, its `Encode` impl has the
+integer's encoding, and bits that aren't declared are kept.
+
+# Example
+```
+# #[macro_use] extern crate objc;
+use objc::foundation::NSUInteger;
+
+ns_options! {
+    /// Options for comparing and searching strings.
+    pub struct NSStringCompareOptions: NSUInteger {
+        const NSCaseInsensitiveSearch = 1;
+        const NSLiteralSearch = 2;
+        const NSBackwardsSearch = 4;
+    }
+}
+
+# fn main() {
+let options = NSStringCompareOptions::NSCaseInsensitiveSearch |
+    NSStringCompareOptions::NSBackwardsSearch;
+assert!(options.contains(NSStringCompareOptions::NSBackwardsSearch));
+assert!(!options.contains(NSStringCompareOptions::NSLiteralSearch));
+assert_eq!(format!("{:?}", options),
+    "NSStringCompareOptions(NSCaseInsensitiveSearch | NSBackwardsSearch)");
+# }
+```
+*/
+#[macro_export]
+macro_rules! ns_options {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident: $int:ty {
+            $(
+                $(#[$flag_meta:meta])*
+                const $flag:ident = $value:expr;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+        $vis struct $name(pub $int);
+
+        #[allow(dead_code, non_upper_case_globals)]
+        impl $name {
+            $(
+                $(#[$flag_meta])*
+                pub const $flag: $name = $name($value);
+            )*
+
+            /// Returns a set with no options.
+            pub const fn empty() -> $name {
+                $name(0)
+            }
+
+            /// Returns the raw bits of self.
+            pub const fn bits(&self) -> $int {
+                self.0
+            }
+
+            /// Returns true if self has no options.
+            pub fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns true if self has all the options of `other`.
+            pub fn contains(&self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns true if self has any of the options of `other`.
+            pub fn intersects(&self, other: $name) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            /// Adds the options of `other` to self.
+            pub fn insert(&mut self, other: $name) {
+                self.0 |= other.0;
+            }
+
+            /// Removes the options of `other` from self.
+            pub fn remove(&mut self, other: $name) {
+                self.0 &= !other.0;
+            }
+        }
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                write!(f, "{}(", stringify!($name))?;
+                let mut remaining = self.0;
+                let mut first = true;
+                $(
+                    let flag = $name::$flag.0;
+                    if flag != 0 && self.0 & flag == flag && remaining & flag != 0 {
+                        if !first {
+                            f.write_str(" | ")?;
+                        }
+                        f.write_str(stringify!($flag))?;
+                        remaining &= !flag;
+                        first = false;
+                    }
+                )*
+                if remaining != 0 || first {
+                    if !first {
+                        f.write_str(" | ")?;
+                    }
+                    write!(f, "{:#x}", remaining)?;
+                }
+                f.write_str(")")
+            }
+        }
+
+        impl ::core::ops::BitOr for $name {
+            type Output = $name;
+            fn bitor(self, other: $name) -> $name { $name(self.0 | other.0) }
+        }
+
+        impl ::core::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: $name) { self.0 |= other.0; }
+        }
+
+        impl ::core::ops::BitAnd for $name {
+            type Output = $name;
+            fn bitand(self, other: $name) -> $name { $name(self.0 & other.0) }
+        }
+
+        impl ::core::ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, other: $name) { self.0 &= other.0; }
+        }
+
+        impl ::core::ops::BitXor for $name {
+            type Output = $name;
+            fn bitxor(self, other: $name) -> $name { $name(self.0 ^ other.0) }
+        }
+
+        impl ::core::ops::BitXorAssign for $name {
+            fn bitxor_assign(&mut self, other: $name) { self.0 ^= other.0; }
+        }
+
+        impl ::core::ops::Sub for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name { $name(self.0 & !other.0) }
+        }
+
+        impl ::core::ops::SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) { self.0 &= !other.0; }
+        }
+
+        impl ::core::ops::Not for $name {
+            type Output = $name;
+            fn not(self) -> $name { $name(!self.0) }
+        }
+
+        unsafe impl $crate::Encode for $name {
+            const ENCODING: $crate::Encoding<'static> = <$int as $crate::Encode>::ENCODING;
+        }
+
+        unsafe impl $crate::EncodeExtended for $name { }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sel_args_count, sel_is_valid};
+
+    #[test]
+    fn test_sel_is_valid() {
+        assert!(sel_is_valid("description"));
+        assert!(sel_is_valid("setObject:forKey:"));
+        assert!(sel_is_valid("performSelector::"));
+        assert!(sel_is_valid(":"));
+        assert!(sel_is_valid("_private$Value2:"));
+        assert!(sel_is_valid("weird-selector:"));
+        assert!(sel_is_valid("2fast:"));
+
+        assert!(!sel_is_valid(""));
+        assert!(!sel_is_valid("foo:bar"));
+        assert!(!sel_is_valid("set Foo:"));
+        assert!(!sel_is_valid("foo:\tbar:"));
+        assert!(!sel_is_valid("foo\0"));
+    }
+
+    #[test]
+    fn test_sel_args_count() {
+        assert_eq!(sel_args_count("description"), 0);
+        assert_eq!(sel_args_count("performSelector::"), 2);
+    }
+}
@@ -0,0 +1,326 @@
+//! Classification of message return types by the calling convention of each
+//! architecture, which determines the variant of `objc_msgSend` to call.
+//!
+//! For more information, see Apple's documentation:
+//! <https://developer.apple.com/documentation/xcode/writing-64-bit-intel-code-for-apple-platforms>
+
+use core::any::{Any, TypeId};
+use core::mem;
+
+use crate::Encoding;
+use crate::parse::round_up;
+
+/// An architecture supported by the Apple runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Arm64,
+}
+
+impl Arch {
+    /// The architecture being compiled for, if the Apple runtime supports it.
+    pub const CURRENT: Option<Arch> =
+        if cfg!(target_arch = "x86") { Some(Arch::X86) }
+        else if cfg!(target_arch = "x86_64") { Some(Arch::X86_64) }
+        else if cfg!(target_arch = "arm") { Some(Arch::Arm) }
+        else if cfg!(target_arch = "aarch64") { Some(Arch::Arm64) }
+        else { None };
+
+    fn pointer_size(self) -> usize {
+        match self {
+            Arch::X86 | Arch::Arm => 4,
+            Arch::X86_64 | Arch::Arm64 => 8,
+        }
+    }
+
+    /// The alignment of `long long` and `double` inside structs, which the
+    /// 32-bit Apple ABIs only align to 4 bytes.
+    fn align_of_8_byte_scalar(self) -> usize {
+        match self {
+            Arch::X86 | Arch::Arm => 4,
+            Arch::X86_64 | Arch::Arm64 => 8,
+        }
+    }
+}
+
+/// How a value is returned, determining the variant of `objc_msgSend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReturnKind {
+    /// Returned in registers, using `objc_msgSend`.
+    Regular,
+    /// Returned in memory provided by the caller, using `objc_msgSend_stret`.
+    Stret,
+    /// Returned on the x87 floating-point stack, using `objc_msgSend_fpret`.
+    Fpret,
+}
+
+/// Returns the size and alignment of the encoded type on `arch`, or `None`
+/// if it cannot be determined from the encoding.
+fn layout(enc: &Encoding, arch: Arch) -> Option<(usize, usize)> {
+    use crate::Encoding::*;
+    let layout = match *enc {
+        Char | UChar | Bool => (1, 1),
+        Short | UShort => (2, 2),
+        Int | UInt | Float => (4, 4),
+        LongLong | ULongLong | Double => (8, arch.align_of_8_byte_scalar()),
+        Long | ULong | String | Object | Block | Class | Sel | Pointer(_) =>
+            (arch.pointer_size(), arch.pointer_size()),
+        Void => (0, 1),
+        Unknown | BitField(_) => return None,
+        Array(len, item) => {
+            let (size, align) = layout(item, arch)?;
+            (size * len as usize, align)
+        }
+        Struct(_, fields) => {
+            // An empty struct encoding is used for opaque structs
+            if fields.is_empty() {
+                return None;
+            }
+            let mut size: usize = 0;
+            let mut align = 1;
+            for field in fields {
+                let (field_size, field_align) = layout(field, arch)?;
+                size = round_up(size, field_align) + field_size;
+                align = align.max(field_align);
+            }
+            (round_up(size, align), align)
+        }
+        Union(_, members) => {
+            if members.is_empty() {
+                return None;
+            }
+            let mut size: usize = 0;
+            let mut align = 1;
+            for member in members {
+                let (member_size, member_align) = layout(member, arch)?;
+                size = size.max(member_size);
+                align = align.max(member_align);
+            }
+            (round_up(size, align), align)
+        }
+    };
+    Some(layout)
+}
+
+fn is_aggregate(enc: &Encoding) -> bool {
+    matches!(*enc, Encoding::Struct(..) | Encoding::Union(..) | Encoding::Array(..))
+}
+
+/// Classifies how a value with the given encoding, size and alignment is
+/// returned on `arch`.
+///
+/// The size and alignment are those of the Rust type, which may be packed;
+/// a packed aggregate with misaligned fields is always returned in memory.
+/// Neither `long double` nor complex types can be encoded with an
+/// `Encoding`, so they are never classified.
+pub fn classify(enc: &Encoding, size: usize, align: usize, arch: Arch) -> ReturnKind {
+    if size == 0 {
+        return ReturnKind::Regular;
+    }
+
+    match arch {
+        Arch::X86_64 => {
+            // If the size of an object is larger than two eightbytes, or it
+            // has unaligned fields, it has class MEMORY.
+            // <https://gitlab.com/x86-psABIs/x86-64-ABI>
+            if !is_aggregate(enc) {
+                return ReturnKind::Regular;
+            }
+            let packed = match layout(enc, arch) {
+                Some((_, natural_align)) => align < natural_align,
+                None => false,
+            };
+            if size > 16 || packed { ReturnKind::Stret } else { ReturnKind::Regular }
+        }
+        Arch::X86 => {
+            // Floating-point values are returned in ST0. Structures 1 or 2
+            // bytes in size are placed in EAX, structures 4 or 8 bytes in
+            // size are placed in EAX and EDX, and structures of other sizes
+            // are placed at the address supplied by the caller.
+            // <https://developer.apple.com/library/mac/documentation/DeveloperTools/Conceptual/LowLevelABI/130-IA-32_Function_Calling_Conventions/IA32.html>
+            match *enc {
+                Encoding::Float | Encoding::Double => ReturnKind::Fpret,
+                _ if !is_aggregate(enc) => ReturnKind::Regular,
+                _ => match size {
+                    1 | 2 | 4 | 8 => ReturnKind::Regular,
+                    _ => ReturnKind::Stret,
+                },
+            }
+        }
+        Arch::Arm => {
+            // Double-word sized fundamental data types don't use stret,
+            // but any composite type larger than 4 bytes does.
+            // <http://infocenter.arm.com/help/topic/com.arm.doc.ihi0042e/IHI0042E_aapcs.pdf>
+            if is_aggregate(enc) && size > 4 {
+                ReturnKind::Stret
+            } else {
+                ReturnKind::Regular
+            }
+        }
+        // stret is not even available in arm64.
+        // <https://twitter.com/gparker/status/378079715824660480>
+        Arch::Arm64 => ReturnKind::Regular,
+    }
+}
+
+/// Returns the encoding of `R` if known, or otherwise the encoding assumed
+/// for it from its type.
+///
+/// Only `f32`, `f64` and 64-bit integers are recognized as scalars; any other
+/// type is assumed to be an aggregate of its size.
+fn type_encoding<'a, R: Any>(enc: Option<&Encoding<'a>>) -> Encoding<'a> {
+    if let Some(enc) = enc {
+        return *enc;
+    }
+    let type_id = TypeId::of::<R>();
+    if type_id == TypeId::of::<f32>() {
+        Encoding::Float
+    } else if type_id == TypeId::of::<f64>() {
+        Encoding::Double
+    } else if type_id == TypeId::of::<i64>() || type_id == TypeId::of::<u64>() {
+        Encoding::LongLong
+    } else {
+        Encoding::Struct("?", &[])
+    }
+}
+
+/// Classifies how a value of type `R` is returned on `arch`, using its
+/// encoding if known.
+#[cfg_attr(not(any(target_os = "macos", target_os = "ios", feature = "objfw")), allow(dead_code))]
+pub fn classify_type<R: Any>(enc: Option<&Encoding>, arch: Arch) -> ReturnKind {
+    let enc = type_encoding::<R>(enc);
+    classify(&enc, mem::size_of::<R>(), mem::align_of::<R>(), arch)
+}
+
+/// Counts the members of a homogeneous floating-point aggregate, whose
+/// members must all be of the same floating-point type, `is_double`.
+fn count_hfa_members(enc: &Encoding, is_double: &mut Option<bool>) -> Option<usize> {
+    match *enc {
+        Encoding::Float | Encoding::Double => {
+            let double = *enc == Encoding::Double;
+            if *is_double.get_or_insert(double) == double { Some(1) } else { None }
+        }
+        Encoding::Array(len, item) =>
+            count_hfa_members(item, is_double).map(|count| count * len as usize),
+        Encoding::Struct(_, fields) if !fields.is_empty() => {
+            let mut count = 0;
+            for field in fields {
+                count += count_hfa_members(field, is_double)?;
+            }
+            Some(count)
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether a value of type `R` is returned in registers on `arch`,
+/// rather than in memory provided by the caller.
+pub fn returned_in_registers<R: Any>(enc: Option<&Encoding>, arch: Arch) -> bool {
+    let enc = type_encoding::<R>(enc);
+    let size = mem::size_of::<R>();
+    match arch {
+        // Composite types larger than 16 bytes are returned in memory,
+        // unless they are homogeneous aggregates of up to four floating-point
+        // members, which are returned in v0-v3.
+        // <https://github.com/ARM-software/abi-aa/blob/main/aapcs64/aapcs64.rst>
+        Arch::Arm64 => !is_aggregate(&enc) || size <= 16 ||
+            count_hfa_members(&enc, &mut None).map_or(false, |count| count <= 4),
+        _ => classify(&enc, size, mem::align_of::<R>(), arch) != ReturnKind::Stret,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Encoding;
+    use super::{Arch, ReturnKind, classify, classify_type, layout, returned_in_registers};
+    use super::ReturnKind::{Fpret, Regular, Stret};
+
+    const POINT_F32: Encoding<'static> =
+        Encoding::Struct("CGPoint", &[Encoding::Float, Encoding::Float]);
+    const POINT_F64: Encoding<'static> =
+        Encoding::Struct("CGPoint", &[Encoding::Double, Encoding::Double]);
+    const RECT_F64: Encoding<'static> =
+        Encoding::Struct("CGRect", &[POINT_F64, POINT_F64]);
+    const RANGE_32: Encoding<'static> =
+        Encoding::Struct("_NSRange", &[Encoding::UInt, Encoding::UInt]);
+    const ONE_CHAR: Encoding<'static> = Encoding::Struct("OneChar", &[Encoding::Char]);
+    const THREE_CHARS: Encoding<'static> =
+        Encoding::Struct("ThreeChars", &[Encoding::Char, Encoding::Char, Encoding::Char]);
+    const CHAR_LONG_LONG: Encoding<'static> =
+        Encoding::Struct("CharLongLong", &[Encoding::Char, Encoding::LongLong]);
+    const INT_OR_DOUBLE: Encoding<'static> =
+        Encoding::Union("IntOrDouble", &[Encoding::Int, Encoding::Double]);
+    const OPAQUE: Encoding<'static> = Encoding::Struct("Opaque", &[]);
+
+    // The encoding, Rust size and alignment, and expected classification on
+    // x86, x86_64, arm and arm64.
+    const CASES: &[(Encoding<'static>, usize, usize, [ReturnKind; 4])] = &[
+        (Encoding::Void, 0, 1, [Regular, Regular, Regular, Regular]),
+        (Encoding::Int, 4, 4, [Regular, Regular, Regular, Regular]),
+        (Encoding::LongLong, 8, 8, [Regular, Regular, Regular, Regular]),
+        (Encoding::Float, 4, 4, [Fpret, Regular, Regular, Regular]),
+        (Encoding::Double, 8, 8, [Fpret, Regular, Regular, Regular]),
+        (ONE_CHAR, 1, 1, [Regular, Regular, Regular, Regular]),
+        (THREE_CHARS, 3, 1, [Stret, Regular, Regular, Regular]),
+        (POINT_F32, 8, 4, [Regular, Regular, Stret, Regular]),
+        (RANGE_32, 8, 4, [Regular, Regular, Stret, Regular]),
+        (POINT_F64, 16, 8, [Stret, Regular, Stret, Regular]),
+        (RECT_F64, 32, 8, [Stret, Stret, Stret, Regular]),
+        (INT_OR_DOUBLE, 8, 8, [Regular, Regular, Stret, Regular]),
+        // A packed struct has a misaligned field
+        (CHAR_LONG_LONG, 9, 1, [Stret, Stret, Stret, Regular]),
+        (CHAR_LONG_LONG, 16, 8, [Stret, Regular, Stret, Regular]),
+        // Without a layout, only the size is considered
+        (OPAQUE, 8, 8, [Regular, Regular, Stret, Regular]),
+        (OPAQUE, 24, 8, [Stret, Stret, Stret, Regular]),
+    ];
+
+    #[test]
+    fn test_classify() {
+        let archs = [Arch::X86, Arch::X86_64, Arch::Arm, Arch::Arm64];
+        for &(ref enc, size, align, expected) in CASES {
+            for (&arch, &kind) in archs.iter().zip(&expected) {
+                assert!(classify(enc, size, align, arch) == kind,
+                    "{} of size {} on {:?} should be {:?}", enc, size, arch, kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_classify_type() {
+        assert_eq!(classify_type::<f64>(None, Arch::X86), Fpret);
+        assert_eq!(classify_type::<u64>(None, Arch::Arm), Regular);
+        assert_eq!(classify_type::<[u8; 3]>(None, Arch::X86), Stret);
+        assert_eq!(classify_type::<[u32; 2]>(None, Arch::Arm), Stret);
+        assert_eq!(classify_type::<[u32; 2]>(Some(&Encoding::Double), Arch::X86), Fpret);
+        assert_eq!(classify_type::<[u8; 9]>(Some(&CHAR_LONG_LONG), Arch::X86_64), Stret);
+    }
+
+    #[test]
+    fn test_returned_in_registers() {
+        let rect_f32 = Encoding::Struct("CGRect", &[POINT_F32, POINT_F32]);
+        assert!(returned_in_registers::<[f64; 4]>(Some(&RECT_F64), Arch::Arm64));
+        assert!(returned_in_registers::<[f32; 4]>(Some(&rect_f32), Arch::Arm64));
+        assert!(!returned_in_registers::<[f64; 4]>(Some(&RECT_F64), Arch::X86_64));
+        assert!(!returned_in_registers::<[u64; 3]>(None, Arch::Arm64));
+        assert!(returned_in_registers::<[u64; 2]>(None, Arch::X86_64));
+        assert!(returned_in_registers::<f64>(None, Arch::X86));
+        assert!(!returned_in_registers::<[u32; 2]>(Some(&RANGE_32), Arch::Arm));
+
+        let mixed = Encoding::Struct("Mixed", &[Encoding::Float, Encoding::Double]);
+        assert!(!returned_in_registers::<[f64; 4]>(Some(&mixed), Arch::Arm64));
+        let array = Encoding::Array(5, &Encoding::Float);
+        assert!(!returned_in_registers::<[f32; 5]>(Some(&array), Arch::Arm64));
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(layout(&CHAR_LONG_LONG, Arch::X86_64), Some((16, 8)));
+        assert_eq!(layout(&CHAR_LONG_LONG, Arch::X86), Some((12, 4)));
+        assert_eq!(layout(&RECT_F64, Arch::Arm), Some((32, 4)));
+        assert_eq!(layout(&Encoding::Pointer(&OPAQUE), Arch::Arm64), Some((8, 8)));
+        assert_eq!(layout(&OPAQUE, Arch::X86_64), None);
+    }
+}
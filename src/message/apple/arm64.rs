@@ -1,3 +1,4 @@
+use crate::Encoding;
 use crate::runtime::Imp;
 
 extern {
@@ -6,13 +7,13 @@ extern {
     fn objc_msgSendSuper();
 }
 
-pub fn msg_send_fn<R>() -> Imp {
+pub fn msg_send_fn<R>(_ret: Option<&Encoding>) -> Imp {
     // stret is not even available in arm64.
     // <https://twitter.com/gparker/status/378079715824660480>
 
     objc_msgSend
 }
 
-pub fn msg_send_super_fn<R>() -> Imp {
+pub fn msg_send_super_fn<R>(_ret: Option<&Encoding>) -> Imp {
     objc_msgSendSuper
 }
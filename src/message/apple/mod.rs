@@ -1,7 +1,9 @@
-use std::any::Any;
+use core::any::Any;
 
+use crate::Encoding;
 use crate::runtime::{Class, Object, Sel};
 use super::{Message, MessageArguments, MessageError, Super};
+use super::nil::send_to_nil;
 
 #[cfg(target_arch = "x86")]
 #[path = "x86.rs"]
@@ -18,22 +20,28 @@ mod arch;
 
 use self::arch::{msg_send_fn, msg_send_super_fn};
 
-pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A)
-        -> Result<R, MessageError>
+pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A,
+        ret: Option<&Encoding>) -> Result<R, MessageError>
         where T: Message, A: MessageArguments, R: Any {
+    // The runtime returns zero itself, but leaves structs returned in memory
+    // undefined, so messages to nil follow the policy without reaching it
+    if obj.is_null() {
+        return send_to_nil(sel, ret);
+    }
+
     let receiver = obj as *mut T as *mut Object;
-    let msg_send_fn = msg_send_fn::<R>();
+    let msg_send_fn = msg_send_fn::<R>(ret);
     objc_try!({
         A::invoke(msg_send_fn, receiver, sel, args)
     })
 }
 
 pub unsafe fn send_super_unverified<T, A, R>(obj: *const T, superclass: &Class,
-        sel: Sel, args: A) -> Result<R, MessageError>
+        sel: Sel, args: A, ret: Option<&Encoding>) -> Result<R, MessageError>
         where T: Message, A: MessageArguments, R: Any {
     let sup = Super { receiver: obj as *mut T as *mut Object, superclass: superclass };
     let receiver = &sup as *const Super as *mut Object;
-    let msg_send_fn = msg_send_super_fn::<R>();
+    let msg_send_fn = msg_send_super_fn::<R>(ret);
     objc_try!({
         A::invoke(msg_send_fn, receiver, sel, args)
     })
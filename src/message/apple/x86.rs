@@ -1,7 +1,8 @@
-use std::any::{Any, TypeId};
-use std::mem;
+use core::any::Any;
 
+use crate::Encoding;
 use crate::runtime::Imp;
+use super::super::abi::{Arch, ReturnKind, classify_type};
 
 extern {
     fn objc_msgSend();
@@ -12,29 +13,18 @@ extern {
     fn objc_msgSendSuper_stret();
 }
 
-pub fn msg_send_fn<R: Any>() -> Imp {
-    // Structures 1 or 2 bytes in size are placed in EAX.
-    // Structures 4 or 8 bytes in size are placed in: EAX and EDX.
-    // Structures of other sizes are placed at the address supplied by the caller.
-    // <https://developer.apple.com/library/mac/documentation/DeveloperTools/Conceptual/LowLevelABI/130-IA-32_Function_Calling_Conventions/IA32.html>
-
-    let type_id = TypeId::of::<R>();
-    let size = mem::size_of::<R>();
-    if type_id == TypeId::of::<f32>() ||
-            type_id == TypeId::of::<f64>() {
-        objc_msgSend_fpret
-    } else if size == 0 || size == 1 || size == 2 || size == 4 || size == 8 {
-        objc_msgSend
-    } else {
-        objc_msgSend_stret
+pub fn msg_send_fn<R: Any>(ret: Option<&Encoding>) -> Imp {
+    match classify_type::<R>(ret, Arch::X86) {
+        ReturnKind::Regular => objc_msgSend,
+        ReturnKind::Fpret => objc_msgSend_fpret,
+        ReturnKind::Stret => objc_msgSend_stret,
     }
 }
 
-pub fn msg_send_super_fn<R: Any>() -> Imp {
-    let size = mem::size_of::<R>();
-    if size == 0 || size == 1 || size == 2 || size == 4 || size == 8 {
-        objc_msgSendSuper
-    } else {
-        objc_msgSendSuper_stret
+pub fn msg_send_super_fn<R: Any>(ret: Option<&Encoding>) -> Imp {
+    // There is no fpret variant of objc_msgSendSuper
+    match classify_type::<R>(ret, Arch::X86) {
+        ReturnKind::Regular | ReturnKind::Fpret => objc_msgSendSuper,
+        ReturnKind::Stret => objc_msgSendSuper_stret,
     }
 }
@@ -1,7 +1,12 @@
-use std::mem;
+use core::any::Any;
 
+use crate::Encoding;
 use crate::runtime::Imp;
+use super::super::abi::{Arch, ReturnKind, classify_type};
 
+// objc_msgSend_fpret and objc_msgSend_fp2ret are only used to return
+// `long double` and `_Complex long double`, which can't be encoded or
+// represented in Rust, so they aren't declared.
 extern {
     fn objc_msgSend();
     fn objc_msgSend_stret();
@@ -10,23 +15,16 @@ extern {
     fn objc_msgSendSuper_stret();
 }
 
-pub fn msg_send_fn<R>() -> Imp {
-    // If the size of an object is larger than two eightbytes, it has class MEMORY.
-    // If the type has class MEMORY, then the caller provides space for the return
-    // value and passes the address of this storage.
-    // <http://people.freebsd.org/~obrien/amd64-elf-abi.pdf>
-
-    if mem::size_of::<R>() <= 16 {
-        objc_msgSend
-    } else {
-        objc_msgSend_stret
+pub fn msg_send_fn<R: Any>(ret: Option<&Encoding>) -> Imp {
+    match classify_type::<R>(ret, Arch::X86_64) {
+        ReturnKind::Regular | ReturnKind::Fpret => objc_msgSend,
+        ReturnKind::Stret => objc_msgSend_stret,
     }
 }
 
-pub fn msg_send_super_fn<R>() -> Imp {
-    if mem::size_of::<R>() <= 16 {
-        objc_msgSendSuper
-    } else {
-        objc_msgSendSuper_stret
+pub fn msg_send_super_fn<R: Any>(ret: Option<&Encoding>) -> Imp {
+    match classify_type::<R>(ret, Arch::X86_64) {
+        ReturnKind::Regular | ReturnKind::Fpret => objc_msgSendSuper,
+        ReturnKind::Stret => objc_msgSendSuper_stret,
     }
 }
@@ -1,19 +1,264 @@
-use std::any::Any;
-use std::mem;
+use core::any::Any;
+#[cfg(all(feature = "gnustep_slot_cache", not(gnustep_2_0)))]
+use core::ffi::{c_char, c_int};
+#[cfg(all(feature = "gnustep_slot_cache", gnustep_2_0))]
+use core::mem;
+#[cfg(feature = "gnustep_slot_cache")]
+use core::ptr;
+#[cfg(feature = "gnustep_slot_cache")]
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering, fence};
+#[cfg(all(feature = "gnustep_slot_cache", not(gnustep_2_0)))]
+use core::sync::atomic::AtomicI32;
+#[cfg(all(feature = "gnustep_slot_cache", gnustep_2_0))]
+use core::sync::atomic::AtomicU64;
 
+use crate::Encoding;
 use crate::runtime::{Class, Object, Imp, Sel};
 use super::{Message, MessageArguments, MessageError, Super};
+use super::nil::send_to_nil;
 
 extern {
+    #[cfg(any(not(gnustep_msgsend), feature = "gnustep_slot_cache"))]
     fn objc_msg_lookup(receiver: *mut Object, op: Sel) -> Imp;
     fn objc_msg_lookup_super(sup: *const Super, sel: Sel) -> Imp;
 }
 
-pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A)
-        -> Result<R, MessageError>
+// Structs are returned like on Apple's platforms on the architectures where
+// libobjc2 implements objc_msgSend, so the variant is chosen the same way.
+#[cfg(gnustep_msgsend)]
+extern {
+    fn objc_msgSend();
+    #[cfg(not(target_arch = "aarch64"))]
+    fn objc_msgSend_stret();
+}
+
+#[cfg(gnustep_msgsend)]
+fn msg_send_fn<R: Any>(ret: Option<&Encoding>) -> Imp {
+    use super::abi::{Arch, ReturnKind, classify_type};
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        let arch = if cfg!(target_arch = "x86_64") { Arch::X86_64 } else { Arch::Arm };
+        if classify_type::<R>(ret, arch) == ReturnKind::Stret {
+            return objc_msgSend_stret;
+        }
+    }
+    let _ = ret;
+    objc_msgSend
+}
+
+/// A method slot, as returned by the slot lookup functions of libobjc2 1.x.
+#[cfg(all(feature = "gnustep_slot_cache", not(gnustep_2_0)))]
+#[repr(C)]
+struct Slot {
+    owner: *const Class,
+    cached_for: *const Class,
+    types: *const c_char,
+    /// Incremented by the runtime whenever the slot is no longer valid,
+    /// like when its method is replaced.
+    version: c_int,
+    method: Imp,
+    selector: Sel,
+}
+
+#[cfg(all(feature = "gnustep_slot_cache", not(gnustep_2_0)))]
+extern {
+    fn objc_msg_lookup_sender(receiver: *mut *mut Object, selector: Sel,
+                              sender: *mut Object) -> *mut Slot;
+}
+
+/// A method slot, as returned by the slot lookup functions of libobjc2 2.x.
+/// Unlike 1.x slots, these can't be kept across calls; the implementation
+/// is only valid while `objc_method_cache_version` is unchanged.
+#[cfg(all(feature = "gnustep_slot_cache", gnustep_2_0))]
+#[repr(C)]
+struct Slot2 {
+    method: Imp,
+}
+
+#[cfg(all(feature = "gnustep_slot_cache", gnustep_2_0))]
+extern {
+    /// Incremented by the runtime whenever any cached method lookup may no
+    /// longer be valid.
+    static objc_method_cache_version: AtomicU64;
+
+    fn objc_slot_lookup_version(receiver: *mut *mut Object, selector: Sel,
+                                version: *mut u64) -> *mut Slot2;
+}
+
+/// The state that decides whether a cached implementation is still valid.
+#[cfg(all(feature = "gnustep_slot_cache", not(gnustep_2_0)))]
+type SlotVersion = c_int;
+#[cfg(all(feature = "gnustep_slot_cache", gnustep_2_0))]
+type SlotVersion = u64;
+
+/// Caches the implementation looked up for a message in a static at each
+/// `msg_send!` call site, so that repeated sends to instances of the same
+/// class can skip the lookup while it is still valid.
+///
+/// With libobjc2 1.x, the slot is kept and validated by its own version.
+/// From 2.0, slots aren't safe to keep, so their implementation is cached
+/// with the global `objc_method_cache_version` at the time of the lookup.
+#[cfg(feature = "gnustep_slot_cache")]
+#[doc(hidden)]
+pub struct CachedSlot {
+    // A sequence lock: odd while the fields below are being written
+    seq: AtomicUsize,
+    cls: AtomicPtr<Class>,
+    #[cfg(not(gnustep_2_0))]
+    slot: AtomicPtr<Slot>,
+    #[cfg(gnustep_2_0)]
+    imp: AtomicUsize,
+    #[cfg(not(gnustep_2_0))]
+    version: AtomicI32,
+    #[cfg(gnustep_2_0)]
+    version: AtomicU64,
+}
+
+#[cfg(feature = "gnustep_slot_cache")]
+impl CachedSlot {
+    /// Constructs a new, empty `CachedSlot`.
+    pub const fn new() -> CachedSlot {
+        CachedSlot {
+            seq: AtomicUsize::new(0),
+            cls: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(not(gnustep_2_0))]
+            slot: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(gnustep_2_0)]
+            imp: AtomicUsize::new(0),
+            #[cfg(not(gnustep_2_0))]
+            version: AtomicI32::new(0),
+            #[cfg(gnustep_2_0)]
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached implementation if it was cached for `cls` and is
+    /// still valid.
+    unsafe fn get(&self, cls: *const Class) -> Option<Imp> {
+        let seq = self.seq.load(Ordering::Acquire);
+        if seq % 2 == 1 {
+            return None;
+        }
+        let cached_cls = self.cls.load(Ordering::Relaxed);
+        #[cfg(not(gnustep_2_0))]
+        let slot = self.slot.load(Ordering::Relaxed);
+        #[cfg(gnustep_2_0)]
+        let imp = self.imp.load(Ordering::Relaxed);
+        let version = self.version.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) != seq || cached_cls as *const Class != cls {
+            return None;
+        }
+
+        #[cfg(not(gnustep_2_0))]
+        {
+            if !slot.is_null() && ptr::read_volatile(&(*slot).version) == version {
+                Some((*slot).method)
+            } else {
+                None
+            }
+        }
+        #[cfg(gnustep_2_0)]
+        {
+            if imp != 0 && objc_method_cache_version.load(Ordering::Acquire) == version {
+                Some(mem::transmute::<usize, Imp>(imp))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Caches the implementation looked up for `cls`, unless another thread
+    /// is already updating the cache.
+    #[cfg(not(gnustep_2_0))]
+    unsafe fn set(&self, cls: *const Class, slot: *mut Slot, version: SlotVersion) {
+        self.write(cls, |this| {
+            this.slot.store(slot, Ordering::Relaxed);
+            this.version.store(version, Ordering::Relaxed);
+        });
+    }
+
+    #[cfg(gnustep_2_0)]
+    unsafe fn set(&self, cls: *const Class, imp: Imp, version: SlotVersion) {
+        self.write(cls, |this| {
+            this.imp.store(imp as usize, Ordering::Relaxed);
+            this.version.store(version, Ordering::Relaxed);
+        });
+    }
+
+    fn write<F: FnOnce(&CachedSlot)>(&self, cls: *const Class, f: F) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        if seq % 2 == 1 || self.seq.compare_exchange(seq, seq + 1,
+                Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return;
+        }
+        self.cls.store(cls as *mut Class, Ordering::Relaxed);
+        f(self);
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+}
+
+/// Looks up the implementation for a message, caching it for the class of
+/// the receiver. The receiver may be replaced by the lookup, like for a proxy.
+#[cfg(all(feature = "gnustep_slot_cache", not(gnustep_2_0)))]
+unsafe fn lookup_cached(cache: &CachedSlot, receiver: &mut *mut Object, cls: *const Class,
+        sel: Sel) -> Imp {
+    let original = *receiver;
+    let slot = objc_msg_lookup_sender(receiver, sel, ptr::null_mut());
+    if slot.is_null() {
+        return objc_msg_lookup(*receiver, sel);
+    }
+    // Only a slot for the original receiver's class is cached
+    if *receiver == original {
+        cache.set(cls, slot, ptr::read_volatile(&(*slot).version));
+    }
+    (*slot).method
+}
+
+#[cfg(all(feature = "gnustep_slot_cache", gnustep_2_0))]
+unsafe fn lookup_cached(cache: &CachedSlot, receiver: &mut *mut Object, cls: *const Class,
+        sel: Sel) -> Imp {
+    let original = *receiver;
+    let mut version = 0;
+    let slot = objc_slot_lookup_version(receiver, sel, &mut version);
+    if slot.is_null() {
+        return objc_msg_lookup(*receiver, sel);
+    }
+    // The slot itself may be freed once the version changes, so only its
+    // implementation is kept
+    let imp = (*slot).method;
+    if *receiver == original {
+        cache.set(cls, imp, version);
+    }
+    imp
+}
+
+#[cfg(feature = "gnustep_slot_cache")]
+pub unsafe fn send_unverified_cached<T, A, R>(cache: &CachedSlot, obj: *const T,
+        sel: Sel, args: A, ret: Option<&Encoding>) -> Result<R, MessageError>
         where T: Message, A: MessageArguments, R: Any {
     if obj.is_null() {
-        return mem::zeroed();
+        return send_to_nil(sel, ret);
+    }
+
+    let mut receiver = obj as *mut T as *mut Object;
+    let cls = (*receiver).class() as *const Class;
+    let imp = match cache.get(cls) {
+        Some(imp) => imp,
+        None => lookup_cached(cache, &mut receiver, cls, sel),
+    };
+    objc_try!({
+        A::invoke(imp, receiver, sel, args)
+    })
+}
+
+#[cfg(not(gnustep_msgsend))]
+pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A,
+        ret: Option<&Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    if obj.is_null() {
+        return send_to_nil(sel, ret);
     }
 
     let receiver = obj as *mut T as *mut Object;
@@ -23,8 +268,23 @@ pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A)
     })
 }
 
+#[cfg(gnustep_msgsend)]
+pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A,
+        ret: Option<&Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    if obj.is_null() {
+        return send_to_nil(sel, ret);
+    }
+
+    let receiver = obj as *mut T as *mut Object;
+    let msg_send_fn = msg_send_fn::<R>(ret);
+    objc_try!({
+        A::invoke(msg_send_fn, receiver, sel, args)
+    })
+}
+
 pub unsafe fn send_super_unverified<T, A, R>(obj: *const T, superclass: &Class,
-        sel: Sel, args: A) -> Result<R, MessageError>
+        sel: Sel, args: A, _ret: Option<&Encoding>) -> Result<R, MessageError>
         where T: Message, A: MessageArguments, R: Any {
     let receiver = obj as *mut T as *mut Object;
     let sup = Super { receiver: receiver, superclass: superclass };
@@ -0,0 +1,41 @@
+use core::any::Any;
+
+use crate::Encoding;
+use crate::mock::lookup_method;
+use crate::runtime::{Class, Object, Imp, Sel};
+use super::{Message, MessageArguments, MessageError};
+use super::nil::send_to_nil;
+
+/// Looks up the implementation of `sel` starting at `cls`, panicking like
+/// `-doesNotRecognizeSelector:` if there's none.
+unsafe fn lookup(receiver: *mut Object, cls: *const Class, sel: Sel) -> Imp {
+    match lookup_method(cls, sel) {
+        Some(imp) => imp,
+        None => panic!("-[{:?} {:?}]: unrecognized selector sent to instance {:p}",
+            (*receiver).class(), sel, receiver),
+    }
+}
+
+pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A,
+        ret: Option<&Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    if obj.is_null() {
+        return send_to_nil(sel, ret);
+    }
+
+    let receiver = obj as *mut T as *mut Object;
+    let msg_send_fn = lookup(receiver, (*receiver).class(), sel);
+    objc_try!({
+        A::invoke(msg_send_fn, receiver, sel, args)
+    })
+}
+
+pub unsafe fn send_super_unverified<T, A, R>(obj: *const T, superclass: &Class,
+        sel: Sel, args: A, _ret: Option<&Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    let receiver = obj as *mut T as *mut Object;
+    let msg_send_fn = lookup(receiver, superclass, sel);
+    objc_try!({
+        A::invoke(msg_send_fn, receiver, sel, args)
+    })
+}
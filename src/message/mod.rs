@@ -1,7 +1,9 @@
-use std::any::Any;
-use std::error::Error;
-use std::fmt;
-use std::mem;
+#[cfg(feature = "exception")]
+use alloc::{borrow::ToOwned, format};
+use alloc::string::{String, ToString};
+use core::any::Any;
+use core::fmt;
+use core::mem;
 
 use crate::runtime::{Class, Imp, Object, Sel};
 use crate::{Encode, EncodeArguments};
@@ -24,19 +26,47 @@ macro_rules! objc_try {
     ($b:block) => (Ok($b))
 }
 
+mod abi;
+mod nil;
+mod prepared;
 mod verify;
 
-#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[cfg(all(any(target_os = "macos", target_os = "ios"),
+          not(any(feature = "objfw", feature = "gcc_libobjc", feature = "mock-runtime"))))]
 #[path = "apple/mod.rs"]
 mod platform;
-#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+#[cfg(feature = "objfw")]
+#[path = "objfw.rs"]
+mod platform;
+// GCC's libobjc looks up methods the same way as GNUstep
+#[cfg(all(any(not(any(target_os = "macos", target_os = "ios")), feature = "gcc_libobjc"),
+          not(any(feature = "objfw", feature = "mock-runtime"))))]
 #[path = "gnustep.rs"]
 mod platform;
+#[cfg(feature = "mock-runtime")]
+#[path = "mock.rs"]
+mod platform;
 
 use self::platform::{send_unverified, send_super_unverified};
-use self::verify::{VerificationError, verify_message_signature};
+#[cfg(all(feature = "gnustep_slot_cache",
+          not(any(target_os = "macos", target_os = "ios", feature = "objfw"))))]
+use self::platform::send_unverified_cached;
+#[cfg(all(feature = "gnustep_slot_cache",
+          not(any(target_os = "macos", target_os = "ios", feature = "objfw"))))]
+pub use self::platform::CachedSlot;
+pub use self::nil::{NilPolicy, nil_policy, set_nil_policy};
+pub use self::prepared::PreparedMessage;
+use self::verify::{VerificationError, verify_message_signature,
+    verify_message_signature_with};
+#[cfg(not(feature = "verify_message"))]
+use self::verify::{runtime_verification_active, verify_message_at_runtime};
+pub use self::verify::{VerificationLevel, set_verification_level,
+    verification_level};
+pub use self::verify::{RuntimeVerification, VerificationReporter,
+    runtime_verification, set_runtime_verification, set_verification_reporter};
 
 /// Specifies the superclass of an instance.
+#[cfg_attr(feature = "mock-runtime", allow(dead_code))]
 #[repr(C)]
 pub struct Super {
     /// Specifies an instance of a class.
@@ -55,6 +85,15 @@ pub unsafe trait Message {
     return type. For more information, see Apple's documentation:
     <https://developer.apple.com/library/mac/documentation/Cocoa/Reference/ObjCRuntimeRef/index.html#//apple_ref/doc/uid/TP40001418-CH1g-88778>
 
+    With the `"verify_message"` feature, the return type must implement
+    `Encode` and the version is chosen from its encoding. Otherwise it is
+    guessed from the size and alignment of the type, which only recognizes
+    `f32`, `f64` and 64-bit integers as scalars; use `send_message_encoded`
+    to choose it from the encoding without verifying the message.
+    Messages returning `long double` or complex types, which would use
+    `objc_msgSend_fpret` on x86-64 or `objc_msgSend_fp2ret`, aren't
+    supported because Rust has no equivalent types.
+
     If the selector is known at compile-time, it is recommended to use the
     `msg_send!` macro rather than this method.
     */
@@ -73,6 +112,20 @@ pub unsafe trait Message {
         send_message(self, sel, args)
     }
 
+    /**
+    Sends a message to self with the given selector and arguments, choosing
+    the version of `objc_msgSend` from the encoding of the return type.
+
+    This is how `send_message` and `msg_send!` always choose it with the
+    `"verify_message"` feature, but unlike them the message isn't verified
+    against the method's encoding.
+    */
+    unsafe fn send_message_encoded<A, R>(&self, sel: Sel, args: A)
+            -> Result<R, MessageError>
+            where Self: Sized, A: MessageArguments, R: Any + Encode {
+        send_message_encoded(self, sel, args)
+    }
+
     /**
     Verifies that the argument and return types match the encoding of the
     method for the given selector.
@@ -102,6 +155,35 @@ pub unsafe trait Message {
         verify_message_signature::<A, R>(obj.class(), sel)
             .map_err(MessageError::from)
     }
+
+    /**
+    Verifies that the argument and return types match the encoding of the
+    method for the given selector, comparing encodings at the given
+    `VerificationLevel` rather than the one set with `set_verification_level`.
+
+    # Example
+    ``` no_run
+    # #[macro_use] extern crate objc;
+    # use objc::runtime::Object;
+    # use objc::{Message, VerificationLevel};
+    # fn main() {
+    let obj: &Object;
+    # obj = unsafe { msg_send![class!(NSObject), new] };
+    let sel = sel!(isEqual:);
+    // A Rust bool is accepted in place of BOOL where they share an ABI
+    let result = obj.verify_message_with_level::<(&Object,), bool>(sel,
+        VerificationLevel::AbiCompatible);
+    assert!(result.is_ok());
+    # }
+    ```
+    */
+    fn verify_message_with_level<A, R>(&self, sel: Sel, level: VerificationLevel)
+            -> Result<(), MessageError>
+            where Self: Sized, A: EncodeArguments, R: Encode {
+        let obj = unsafe { &*(self as *const _ as *const Object) };
+        verify_message_signature_with::<A, R>(obj.class(), sel, level)
+            .map_err(MessageError::from)
+    }
 }
 
 unsafe impl Message for Object { }
@@ -110,6 +192,11 @@ unsafe impl Message for Class { }
 
 /// Types that may be used as the arguments of an Objective-C message.
 pub trait MessageArguments: Sized {
+    /// The sizes of each argument, used when verifying messages at runtime,
+    /// or `None` if they aren't known and only the return type is verified.
+    #[doc(hidden)]
+    const SIZES: Option<&'static [usize]> = None;
+
     /// Invoke an `Imp` with the given object, selector, and arguments.
     ///
     /// This method is the primitive used when sending messages and should not
@@ -122,6 +209,8 @@ pub trait MessageArguments: Sized {
 macro_rules! message_args_impl {
     ($($a:ident : $t:ident),*) => (
         impl<$($t),*> MessageArguments for ($($t,)*) {
+            const SIZES: Option<&'static [usize]> = Some(&[$(mem::size_of::<$t>()),*]);
+
             unsafe fn invoke<R>(imp: Imp, obj: *mut Object, sel: Sel, ($($a,)*): Self) -> R
                     where R: Any {
                 let imp: unsafe extern fn(*mut Object, Sel $(, $t)*) -> R =
@@ -164,7 +253,8 @@ impl fmt::Display for MessageError {
     }
 }
 
-impl Error for MessageError {
+#[cfg(feature = "std")]
+impl std::error::Error for MessageError {
     fn description(&self) -> &str {
         &self.0
     }
@@ -182,7 +272,11 @@ impl<'a> From<VerificationError<'a>> for MessageError {
 pub unsafe fn send_message<T, A, R>(obj: *const T, sel: Sel, args: A)
         -> Result<R, MessageError>
         where T: Message, A: MessageArguments, R: Any {
-    send_unverified(obj, sel, args)
+    if runtime_verification_active() && !obj.is_null() {
+        let cls = (*(obj as *const Object)).class();
+        verify_message_at_runtime::<A, R>(cls, sel);
+    }
+    send_unverified(obj, sel, args, None)
 }
 
 #[doc(hidden)]
@@ -199,7 +293,70 @@ pub unsafe fn send_message<T, A, R>(obj: *const T, sel: Sel, args: A)
     };
 
     verify_message_signature::<A, R>(cls, sel)?;
-    send_unverified(obj, sel, args)
+    send_unverified(obj, sel, args, Some(&R::ENCODING))
+}
+
+#[doc(hidden)]
+#[inline(always)]
+pub unsafe fn send_message_encoded<T, A, R>(obj: *const T, sel: Sel, args: A)
+        -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any + Encode {
+    send_unverified(obj, sel, args, Some(&R::ENCODING))
+}
+
+/// Caches the method lookup for a message at each `msg_send!` call site, if
+/// the runtime supports it.
+#[cfg(not(all(feature = "gnustep_slot_cache",
+              not(any(target_os = "macos", target_os = "ios", feature = "objfw")))))]
+#[doc(hidden)]
+pub struct CachedSlot;
+
+#[cfg(not(all(feature = "gnustep_slot_cache",
+              not(any(target_os = "macos", target_os = "ios", feature = "objfw")))))]
+impl CachedSlot {
+    /// Constructs a new `CachedSlot`.
+    pub const fn new() -> CachedSlot {
+        CachedSlot
+    }
+}
+
+#[cfg(not(all(feature = "gnustep_slot_cache",
+              not(any(target_os = "macos", target_os = "ios", feature = "objfw")))))]
+#[inline(always)]
+unsafe fn send_unverified_cached<T, A, R>(_cache: &CachedSlot, obj: *const T,
+        sel: Sel, args: A, ret: Option<&crate::Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    send_unverified(obj, sel, args, ret)
+}
+
+#[doc(hidden)]
+#[inline(always)]
+#[cfg(not(feature = "verify_message"))]
+pub unsafe fn send_message_cached<T, A, R>(cache: &CachedSlot, obj: *const T,
+        sel: Sel, args: A) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    if runtime_verification_active() && !obj.is_null() {
+        let cls = (*(obj as *const Object)).class();
+        verify_message_at_runtime::<A, R>(cls, sel);
+    }
+    send_unverified_cached(cache, obj, sel, args, None)
+}
+
+#[doc(hidden)]
+#[inline(always)]
+#[cfg(feature = "verify_message")]
+pub unsafe fn send_message_cached<T, A, R>(cache: &CachedSlot, obj: *const T,
+        sel: Sel, args: A) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments + EncodeArguments,
+        R: Any + Encode {
+    let cls = if obj.is_null() {
+        return Err(VerificationError::NilReceiver(sel).into());
+    } else {
+        (*(obj as *const Object)).class()
+    };
+
+    verify_message_signature::<A, R>(cls, sel)?;
+    send_unverified_cached(cache, obj, sel, args, Some(&R::ENCODING))
 }
 
 #[doc(hidden)]
@@ -208,7 +365,10 @@ pub unsafe fn send_message<T, A, R>(obj: *const T, sel: Sel, args: A)
 pub unsafe fn send_super_message<T, A, R>(obj: *const T, superclass: &Class,
         sel: Sel, args: A) -> Result<R, MessageError>
         where T: Message, A: MessageArguments, R: Any {
-    send_super_unverified(obj, superclass, sel, args)
+    if runtime_verification_active() && !obj.is_null() {
+        verify_message_at_runtime::<A, R>(superclass, sel);
+    }
+    send_super_unverified(obj, superclass, sel, args, None)
 }
 
 #[doc(hidden)]
@@ -223,10 +383,11 @@ pub unsafe fn send_super_message<T, A, R>(obj: *const T, superclass: &Class,
     }
 
     verify_message_signature::<A, R>(superclass, sel)?;
-    send_super_unverified(obj, superclass, sel, args)
+    send_super_unverified(obj, superclass, sel, args, Some(&R::ENCODING))
 }
 
-#[cfg(test)]
+// These tests declare classes, which isn't supported on ObjFW
+#[cfg(all(test, not(feature = "objfw")))]
 mod tests {
     use crate::test_utils;
     use crate::runtime::Object;
@@ -252,10 +413,20 @@ mod tests {
         assert!(result == expected);
     }
 
+    #[test]
+    fn test_send_message_encoded() {
+        let obj = test_utils::custom_object();
+        let result: test_utils::CustomStruct = unsafe {
+            obj.send_message_encoded(sel!(customStruct), ()).unwrap()
+        };
+        let expected = test_utils::CustomStruct { a: 1, b:2, c: 3, d: 4 };
+        assert!(result == expected);
+    }
+
     #[cfg(not(feature = "verify_message"))]
     #[test]
     fn test_send_message_nil() {
-        let nil: *mut Object = ::std::ptr::null_mut();
+        let nil: *mut Object = ::core::ptr::null_mut();
         let result: usize = unsafe {
             msg_send![nil, hash]
         };
@@ -270,6 +441,82 @@ mod tests {
             msg_send![nil, doubleValue]
         };
         assert!(result == 0.0);
+
+        // Structs returned in memory are left undefined by the runtime
+        let result = unsafe {
+            super::send_message::<_, _, [usize; 5]>(nil, sel!(bounds), ())
+        };
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_send_message_replaced_method() {
+        use crate::declare::{ClassDecl, MethodImplementation};
+        use crate::runtime::{self, Class, Sel};
+
+        extern fn initialize(_this: &Class, _cmd: Sel) { }
+        extern fn get_one(_this: &Object, _cmd: Sel) -> u32 { 1 }
+        extern fn get_two(_this: &Object, _cmd: Sel) -> u32 { 2 }
+
+        let mut decl = ClassDecl::root("ReplacedMethodObject", initialize).unwrap();
+        unsafe {
+            add_method!(decl, sel!(number), get_one as extern fn(&Object, Sel) -> u32);
+        }
+        let cls = decl.register();
+
+        unsafe {
+            let obj = runtime::class_createInstance(cls, 0);
+            let mut results = Vec::new();
+            for i in 0..4 {
+                if i == 2 {
+                    let method = cls.instance_method(sel!(number)).unwrap();
+                    let imp = (get_two as extern fn(&Object, Sel) -> u32).imp();
+                    runtime::method_setImplementation(method as *const _ as *mut _, imp);
+                }
+                // The same call site must see the replaced implementation
+                let result: u32 = msg_send![obj, number];
+                results.push(result);
+            }
+            runtime::object_dispose(obj);
+            assert_eq!(results, [1, 1, 2, 2]);
+        }
+    }
+
+    #[test]
+    fn test_send_message_cached_subclass() {
+        use crate::declare::ClassDecl;
+        use crate::runtime::{self, Class, Sel};
+
+        extern fn initialize(_this: &Class, _cmd: Sel) { }
+        extern fn get_one(_this: &Object, _cmd: Sel) -> u32 { 1 }
+        extern fn get_two(_this: &Object, _cmd: Sel) -> u32 { 2 }
+
+        let mut decl = ClassDecl::root("CachedBaseObject", initialize).unwrap();
+        unsafe {
+            add_method!(decl, sel!(number), get_one as extern fn(&Object, Sel) -> u32);
+        }
+        let base = decl.register();
+        let mut decl = ClassDecl::new("CachedSubObject", base).unwrap();
+        unsafe {
+            add_method!(decl, sel!(number), get_two as extern fn(&Object, Sel) -> u32);
+        }
+        let sub = decl.register();
+
+        unsafe {
+            let objs = [runtime::class_createInstance(base, 0),
+                        runtime::class_createInstance(sub, 0)];
+            let mut results = Vec::new();
+            for i in 0..4 {
+                // The implementation cached for one class must not be used
+                // for another at the same call site
+                let result: u32 = msg_send![objs[i % 2], number];
+                results.push(result);
+            }
+            for &obj in &objs {
+                runtime::object_dispose(obj);
+            }
+            assert_eq!(results, [1, 2, 1, 2]);
+        }
     }
 
     #[test]
@@ -287,6 +534,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_send_message_super_raw_pointer() {
+        let obj = test_utils::custom_subclass_object();
+        let superclass = test_utils::custom_class();
+        // The receiver's address is taken without creating a reference to it
+        let ptr = &*obj as *const Object as *mut Object;
+        unsafe {
+            let _: () = msg_send![ptr, setFoo:4u32];
+            let foo: u32 = msg_send![super(ptr, superclass), foo];
+            assert!(foo == 4);
+            let foo: u32 = msg_send![ptr, foo];
+            assert!(foo == 6);
+        }
+    }
+
     #[test]
     fn test_verify_message() {
         let obj = test_utils::custom_object();
@@ -298,4 +560,76 @@ mod tests {
         // Unimplemented selector
         assert!(obj.verify_message::<(u32,), ()>(sel!(setFoo)).is_err());
     }
+
+    #[cfg(not(feature = "verify_message"))]
+    #[test]
+    fn test_runtime_verification() {
+        use std::sync::Mutex;
+        use crate::runtime::{Class, Sel};
+        use crate::{MessageError, RuntimeVerification};
+
+        static REPORTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        fn reporter(_cls: &Class, sel: Sel, _err: &MessageError) {
+            REPORTS.lock().unwrap().push(sel.name().to_owned());
+        }
+
+        crate::set_verification_reporter(reporter);
+        crate::set_runtime_verification(RuntimeVerification::Enabled);
+        let obj = test_utils::custom_object();
+        unsafe {
+            // The method exists and the sizes match, so nothing is reported
+            let _: () = msg_send![obj, setFoo:4u32];
+            // The return type is too large, but the message is still sent
+            let _: u64 = msg_send![obj, foo];
+            let _: u64 = msg_send![obj, foo];
+        }
+        crate::set_runtime_verification(RuntimeVerification::Disabled);
+
+        let reports = REPORTS.lock().unwrap();
+        assert_eq!(*reports, ["foo"]);
+    }
+
+    #[cfg(not(feature = "verify_message"))]
+    #[test]
+    fn test_message_arguments_without_sizes() {
+        use core::any::Any;
+        use crate::runtime::{Imp, Sel};
+        use super::MessageArguments;
+
+        // Implementations outside this crate don't have to give the sizes
+        struct Foo(u32);
+
+        impl MessageArguments for Foo {
+            unsafe fn invoke<R>(imp: Imp, obj: *mut Object, sel: Sel, args: Self) -> R
+                    where R: Any {
+                <(u32,)>::invoke(imp, obj, sel, (args.0,))
+            }
+        }
+
+        assert!(Foo::SIZES.is_none());
+        let obj = test_utils::custom_object();
+        let result: u32 = unsafe {
+            obj.send_message::<_, ()>(sel!(setFoo:), Foo(5)).unwrap();
+            msg_send![obj, foo]
+        };
+        assert_eq!(result, 5);
+    }
+
+    #[test]
+    fn test_verify_message_levels() {
+        use crate::VerificationLevel;
+
+        let obj = test_utils::custom_object();
+        let sel = sel!(setFoo:);
+        // setFoo: takes a u32, which shares an ABI with i32 but isn't equal
+        assert!(obj.verify_message_with_level::<(i32,), ()>(sel,
+            VerificationLevel::Exact).is_err());
+        assert!(obj.verify_message_with_level::<(i32,), ()>(sel,
+            VerificationLevel::AbiCompatible).is_ok());
+        assert!(obj.verify_message_with_level::<(i32,), ()>(sel,
+            VerificationLevel::Lenient).is_ok());
+        // A float is passed differently than an integer of the same size
+        assert!(obj.verify_message_with_level::<(f32,), ()>(sel,
+            VerificationLevel::Lenient).is_err());
+    }
 }
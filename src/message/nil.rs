@@ -0,0 +1,135 @@
+use core::any::{Any, TypeId};
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::Encoding;
+use crate::runtime::Sel;
+use super::MessageError;
+use super::abi::{Arch, returned_in_registers};
+use super::verify::VerificationError;
+
+/**
+What happens when a message is sent to a nil receiver.
+
+Apple's runtime returns zero from messages to nil, but this is only defined
+for some return types; GNUstep's runtime leaves the result to the caller.
+The policy applies to messages sent with `msg_send!` without the
+`verify_message` feature, since verified messages to nil always fail.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NilPolicy {
+    /// Return zero, following the rules of Apple's runtime: objects,
+    /// pointers, scalars and structs returned in registers are zeroed, but
+    /// the message fails with a `MessageError` for structs returned in memory,
+    /// whose value is undefined.
+    ///
+    /// The message also fails for return types that zero may not be a valid
+    /// value of, like references, `NonNull` and function pointers; their
+    /// `Option` should be returned instead.
+    Zeroed,
+    /// Fail the message with a `MessageError`.
+    Error,
+    /// Panic.
+    Panic,
+}
+
+static NIL_POLICY: AtomicUsize = AtomicUsize::new(0);
+
+impl NilPolicy {
+    fn from_usize(policy: usize) -> NilPolicy {
+        match policy {
+            1 => NilPolicy::Error,
+            2 => NilPolicy::Panic,
+            _ => NilPolicy::Zeroed,
+        }
+    }
+}
+
+/// Returns the `NilPolicy` used for messages to nil.
+pub fn nil_policy() -> NilPolicy {
+    NilPolicy::from_usize(NIL_POLICY.load(Ordering::Relaxed))
+}
+
+/// Sets the `NilPolicy` used for messages to nil.
+/// The default policy is `NilPolicy::Zeroed`.
+pub fn set_nil_policy(policy: NilPolicy) {
+    NIL_POLICY.store(policy as usize, Ordering::Relaxed);
+}
+
+/// Returns whether Apple's runtime defines the value returned from a message
+/// to nil with return type `R`, which is zero.
+fn zero_is_defined<R: Any>(ret: Option<&Encoding>) -> bool {
+    match Arch::CURRENT {
+        Some(arch) => returned_in_registers::<R>(ret, arch),
+        None => mem::size_of::<R>() <= mem::size_of::<usize>(),
+    }
+}
+
+/// Returns whether zero is a valid value of `R`, as far as can be told
+/// without a bound on `R`.
+///
+/// Types that can't be zero, like references, `NonNull`, function pointers
+/// and structs containing them, have a niche that `Option<R>` uses for
+/// `None`, making it the same size as `R`. Any other type with a niche is
+/// also rejected, except for `bool` and `char` whose zero is valid.
+pub fn zero_is_valid<R: Any>() -> bool {
+    let type_id = TypeId::of::<R>();
+    mem::size_of::<Option<R>>() != mem::size_of::<R>() ||
+        type_id == TypeId::of::<bool>() || type_id == TypeId::of::<char>()
+}
+
+/// Returns the result of sending the message `sel` to nil, with the
+/// `ret` encoding of `R` if known, according to the current `NilPolicy`.
+pub unsafe fn send_to_nil<R: Any>(sel: Sel, ret: Option<&Encoding>)
+        -> Result<R, MessageError> {
+    match nil_policy() {
+        NilPolicy::Zeroed if zero_is_defined::<R>(ret) && zero_is_valid::<R>() =>
+            Ok(mem::zeroed()),
+        NilPolicy::Zeroed | NilPolicy::Error =>
+            Err(VerificationError::NilReceiver(sel).into()),
+        NilPolicy::Panic => panic!("{}", VerificationError::NilReceiver(sel)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+
+    use crate::runtime::{Object, Sel};
+    use super::{send_to_nil, zero_is_defined, zero_is_valid};
+
+    #[test]
+    fn test_zero_is_defined() {
+        assert!(zero_is_defined::<()>(None));
+        assert!(zero_is_defined::<usize>(None));
+        assert!(zero_is_defined::<f64>(None));
+        assert!(!zero_is_defined::<[usize; 5]>(None));
+    }
+
+    #[test]
+    fn test_zero_is_valid() {
+        assert!(zero_is_valid::<()>());
+        assert!(zero_is_valid::<bool>());
+        assert!(zero_is_valid::<*const Object>());
+        assert!(zero_is_valid::<Option<&'static Object>>());
+        assert!(zero_is_valid::<Option<extern fn()>>());
+        assert!(!zero_is_valid::<&'static Object>());
+        assert!(!zero_is_valid::<NonNull<Object>>());
+        assert!(!zero_is_valid::<extern fn()>());
+    }
+
+    #[test]
+    fn test_send_to_nil() {
+        let sel = sel!(hash);
+        unsafe {
+            let hash: usize = send_to_nil(sel, None).unwrap();
+            assert!(hash == 0);
+            let ptr: *const Sel = send_to_nil(sel, None).unwrap();
+            assert!(ptr.is_null());
+            assert!(send_to_nil::<[usize; 5]>(sel, None).is_err());
+            assert!(send_to_nil::<&'static Object>(sel, None).is_err());
+            let obj: Option<&'static Object> = send_to_nil(sel, None).unwrap();
+            assert!(obj.is_none());
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use core::any::Any;
+
+use crate::Encoding;
+use crate::runtime::{Class, Object, Imp, Sel};
+use super::{Message, MessageArguments, MessageError, Super};
+use super::abi::{Arch, ReturnKind, classify_type};
+use super::nil::send_to_nil;
+
+extern {
+    fn objc_msg_lookup(receiver: *mut Object, op: Sel) -> Imp;
+    fn objc_msg_lookup_stret(receiver: *mut Object, op: Sel) -> Imp;
+    fn objc_msg_lookup_super(sup: *const Super, sel: Sel) -> Imp;
+    fn objc_msg_lookup_super_stret(sup: *const Super, sel: Sel) -> Imp;
+}
+
+/// Returns whether values of type `R` are returned in memory, in which case
+/// the stret lookup functions must be used so that unimplemented methods are
+/// forwarded correctly.
+fn is_stret<R: Any>(ret: Option<&Encoding>) -> bool {
+    match Arch::CURRENT {
+        Some(arch) => classify_type::<R>(ret, arch) == ReturnKind::Stret,
+        None => false,
+    }
+}
+
+pub unsafe fn send_unverified<T, A, R>(obj: *const T, sel: Sel, args: A,
+        ret: Option<&Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    if obj.is_null() {
+        return send_to_nil(sel, ret);
+    }
+
+    let receiver = obj as *mut T as *mut Object;
+    let msg_send_fn = if is_stret::<R>(ret) {
+        objc_msg_lookup_stret(receiver, sel)
+    } else {
+        objc_msg_lookup(receiver, sel)
+    };
+    objc_try!({
+        A::invoke(msg_send_fn, receiver, sel, args)
+    })
+}
+
+pub unsafe fn send_super_unverified<T, A, R>(obj: *const T, superclass: &Class,
+        sel: Sel, args: A, ret: Option<&Encoding>) -> Result<R, MessageError>
+        where T: Message, A: MessageArguments, R: Any {
+    let receiver = obj as *mut T as *mut Object;
+    let sup = Super { receiver: receiver, superclass: superclass };
+    let msg_send_fn = if is_stret::<R>(ret) {
+        objc_msg_lookup_super_stret(&sup, sel)
+    } else {
+        objc_msg_lookup_super(&sup, sel)
+    };
+    objc_try!({
+        A::invoke(msg_send_fn, receiver, sel, args)
+    })
+}
@@ -0,0 +1,139 @@
+use core::any::Any;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr;
+
+use crate::runtime::{Class, Imp, Object, Sel, self};
+use crate::{Encode, EncodeArguments};
+use super::{Message, MessageArguments, MessageError};
+use super::nil::send_to_nil;
+use super::verify::verify_message_signature;
+
+/**
+A message whose implementation has been looked up ahead of time, so that it
+can be sent repeatedly without going through the runtime's dispatch.
+
+The method for the selector is looked up on the class the message is
+prepared for, and its encoding is verified once against the argument types
+`A` and return type `R`. If the message is sent to an instance of a different
+class, the method is looked up and verified again for that class.
+
+The implementation is only looked up when the class changes, so if the
+method's implementation is replaced afterwards, for example with
+`method_setImplementation`, the message must be prepared again.
+
+# Example
+``` no_run
+# #[macro_use] extern crate objc;
+# use objc::PreparedMessage;
+# use objc::runtime::Object;
+# fn main() {
+# let objects: Vec<&Object> = Vec::new();
+let hash = PreparedMessage::<(), usize>::new(class!(NSObject), sel!(hash))
+    .unwrap();
+for obj in objects {
+    let _hash = unsafe { hash.send(obj, ()) }.unwrap();
+}
+# }
+```
+*/
+pub struct PreparedMessage<A, R> {
+    sel: Sel,
+    cls: Cell<*const Class>,
+    imp: Cell<Imp>,
+    marker: PhantomData<fn(A) -> R>,
+}
+
+fn prepare<A, R>(cls: &Class, sel: Sel) -> Result<Imp, MessageError>
+        where A: EncodeArguments, R: Encode {
+    verify_message_signature::<A, R>(cls, sel)?;
+    Ok(unsafe { runtime::class_getMethodImplementation(cls, sel) })
+}
+
+impl<A, R> PreparedMessage<A, R>
+        where A: MessageArguments + EncodeArguments, R: Any + Encode {
+    /// Prepares a message with the given selector for instances of `cls`.
+    /// Returns a `MessageError` if `cls` has no method for the selector or
+    /// its encoding does not match the argument and return types.
+    pub fn new(cls: &Class, sel: Sel) -> Result<Self, MessageError> {
+        let imp = prepare::<A, R>(cls, sel)?;
+        Ok(PreparedMessage {
+            sel,
+            cls: Cell::new(cls),
+            imp: Cell::new(imp),
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns the selector of self.
+    pub fn sel(&self) -> Sel {
+        self.sel
+    }
+
+    /// Sends self to the given object with the given arguments.
+    ///
+    /// Returns a `MessageError` if the object's class differs from the one
+    /// self was prepared for and the method fails verification for it.
+    /// Messages to nil follow the current `NilPolicy`, like `msg_send!`.
+    /// Unsafe because the caller must ensure the object is valid.
+    pub unsafe fn send<T>(&self, obj: *const T, args: A) -> Result<R, MessageError>
+            where T: Message {
+        if obj.is_null() {
+            return send_to_nil(self.sel, Some(&R::ENCODING));
+        }
+
+        let receiver = obj as *mut T as *mut Object;
+        let cls = (*receiver).class();
+        if !ptr::eq(cls, self.cls.get()) {
+            let imp = prepare::<A, R>(cls, self.sel)?;
+            self.cls.set(cls);
+            self.imp.set(imp);
+        }
+
+        let imp = self.imp.get();
+        objc_try!({
+            A::invoke(imp, receiver, self.sel, args)
+        })
+    }
+}
+
+#[cfg(all(test, not(feature = "objfw")))]
+mod tests {
+    use crate::runtime::{Object, Sel};
+    use crate::test_utils;
+    use super::PreparedMessage;
+
+    #[test]
+    fn test_prepared_message() {
+        let cls = test_utils::custom_class();
+        let set_foo = PreparedMessage::<(u32,), ()>::new(cls, Sel::register("setFoo:"))
+            .unwrap();
+        let foo = PreparedMessage::<(), u32>::new(cls, Sel::register("foo"))
+            .unwrap();
+
+        let obj = test_utils::custom_object();
+        unsafe {
+            set_foo.send(&*obj, (4,)).unwrap();
+            assert!(foo.send(&*obj, ()).unwrap() == 4);
+        }
+
+        // The subclass overrides foo to return foo + 2
+        let subclass_obj = test_utils::custom_subclass_object();
+        unsafe {
+            set_foo.send(&*subclass_obj, (4,)).unwrap();
+            assert!(foo.send(&*subclass_obj, ()).unwrap() == 6);
+            assert!(foo.send(&*obj, ()).unwrap() == 4);
+        }
+
+        // Like msg_send!, messages to nil return zero by default
+        let nil: *const Object = ::core::ptr::null();
+        assert!(unsafe { foo.send(nil, ()) }.unwrap() == 0);
+    }
+
+    #[test]
+    fn test_prepared_message_errors() {
+        let cls = test_utils::custom_class();
+        assert!(PreparedMessage::<(), u64>::new(cls, Sel::register("foo")).is_err());
+        assert!(PreparedMessage::<(), u32>::new(cls, Sel::register("bar")).is_err());
+    }
+}
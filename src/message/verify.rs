@@ -1,14 +1,359 @@
-use std::fmt;
+// Runtime verification is redundant when every message is already verified
+#![cfg_attr(feature = "verify_message", allow(dead_code))]
 
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::parse::ParsedEncoding;
 use crate::runtime::{Class, Method, Object, Sel};
 use crate::{Encode, Encoding, EncodeArguments};
+use super::{MessageArguments, MessageError};
+
+/**
+How strictly the encodings of a method are compared with the types used to
+message it.
+
+The Objective-C compiler doesn't always encode the same type the same way, so
+an exact comparison can reject types that are perfectly compatible.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationLevel {
+    /// The encodings must be identical, ignoring qualifiers like `const`.
+    Exact,
+    /// The types must have the same size and alignment and be passed the same
+    /// way in the calling convention, such as `BOOL` as either `c` or `B`.
+    AbiCompatible,
+    /// In addition to ABI-compatible types, the types may differ in ways that
+    /// the encodings can't fully describe: qualifiers anywhere in the type,
+    /// struct names, opaque structs, unknown types, typed objects like
+    /// `@"NSString"` or blocks in place of `@`, and `void` pointers in place
+    /// of any pointer.
+    Lenient,
+}
+
+static VERIFICATION_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+impl VerificationLevel {
+    fn from_usize(level: usize) -> VerificationLevel {
+        match level {
+            1 => VerificationLevel::AbiCompatible,
+            2 => VerificationLevel::Lenient,
+            _ => VerificationLevel::Exact,
+        }
+    }
+}
+
+/// Returns the `VerificationLevel` used when verifying messages.
+pub fn verification_level() -> VerificationLevel {
+    VerificationLevel::from_usize(VERIFICATION_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Sets the `VerificationLevel` used when verifying messages, including the
+/// messages sent with the `verify_message` feature enabled.
+/// The default level is `VerificationLevel::Exact`.
+pub fn set_verification_level(level: VerificationLevel) {
+    VERIFICATION_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Returns whether `expected`, an encoding string from the runtime, is
+/// compatible with `enc` at the given level.
+fn encoding_matches(expected: &str, enc: &Encoding, level: VerificationLevel) -> bool {
+    if *expected == *enc {
+        return true;
+    }
+    if level == VerificationLevel::Exact {
+        return false;
+    }
+
+    let expected = match ParsedEncoding::parse(expected) {
+        Some(expected) => expected,
+        None => return false,
+    };
+    let actual = ParsedEncoding::from_encoding(enc);
+    abi_compatible(&expected, &actual) ||
+        (level == VerificationLevel::Lenient && lenient_eq(&expected, &actual))
+}
+
+fn abi_compatible(a: &ParsedEncoding, b: &ParsedEncoding) -> bool {
+    match (a.layout(), b.layout()) {
+        (Some(a_layout), Some(b_layout)) if a_layout == b_layout => (),
+        _ => return false,
+    }
+    match (a.scalars(), b.scalars()) {
+        (Some(a_scalars), Some(b_scalars)) => a_scalars == b_scalars,
+        _ => false,
+    }
+}
+
+fn lenient_eq(a: &ParsedEncoding, b: &ParsedEncoding) -> bool {
+    use crate::parse::ParsedEncoding::*;
+
+    fn is_object(enc: &ParsedEncoding) -> bool {
+        matches!(*enc, Object(_) | Block | Class)
+    }
+
+    fn is_bool(enc: &ParsedEncoding) -> bool {
+        matches!(*enc, Char | Bool)
+    }
+
+    fn fields_eq(a: &Option<Vec<ParsedEncoding>>, b: &Option<Vec<ParsedEncoding>>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) =>
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| lenient_eq(a, b)),
+            _ => false,
+        }
+    }
+
+    match (a, b) {
+        (Unknown, _) | (_, Unknown) => true,
+        (a, b) if is_object(a) && is_object(b) => true,
+        (a, b) if is_bool(a) && is_bool(b) => true,
+        (Pointer(a), Pointer(b)) =>
+            **a == Void || **b == Void || lenient_eq(a, b),
+        (String, Pointer(t)) | (Pointer(t), String) =>
+            matches!(**t, Char | Void | Unknown),
+        (Array(a_len, a), Array(b_len, b)) => a_len == b_len && lenient_eq(a, b),
+        (Complex(a), Complex(b)) => lenient_eq(a, b),
+        (Struct(a_name, a), Struct(b_name, b)) |
+        (Union(a_name, a), Union(b_name, b)) => {
+            // Opaque aggregates can only be matched by their name
+            if a.is_none() || b.is_none() {
+                a_name == b_name
+            } else {
+                fields_eq(a, b)
+            }
+        }
+        (a, b) => a == b,
+    }
+}
+
+/**
+When messages are verified at runtime, for types that don't implement `Encode`.
+
+Unlike the `verify_message` feature, runtime verification doesn't require
+`Encode` for the arguments and return types of every message. Instead, it
+checks that the method exists and that the number and sizes of the arguments
+and return type match its encoding. Mismatches are passed to the
+[`VerificationReporter`](type.VerificationReporter.html) once for each
+selector and class, and the message is sent regardless.
+
+The initial mode is read from the `OBJC_VERIFY_MESSAGE` environment variable:
+`always` (or `1`) for `Enabled`, `debug` for `DebugOnly`, and `Disabled`
+otherwise. Without the `std` feature, the initial mode is `Disabled`.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuntimeVerification {
+    /// Messages aren't verified at runtime.
+    Disabled,
+    /// Messages are verified at runtime only in builds with debug assertions.
+    DebugOnly,
+    /// Messages are always verified at runtime.
+    Enabled,
+}
+
+/// A function that is passed a message that failed runtime verification,
+/// with the class it was sent to and the reason it failed.
+pub type VerificationReporter = fn(&Class, Sel, &MessageError);
+
+// 0 means the mode hasn't yet been read from the environment
+static RUNTIME_VERIFICATION: AtomicUsize = AtomicUsize::new(0);
+static REPORTER: SpinLock<Option<VerificationReporter>> = SpinLock::new(None);
+// The classes and selectors already reported, created on the first report
+static REPORTED: SpinLock<Option<BTreeSet<(usize, usize)>>> = SpinLock::new(None);
+
+/// A minimal lock for the reporting state, which is only held briefly and
+/// must be usable without `std`.
+struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> { }
+
+struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> SpinLock<T> {
+    const fn new(value: T) -> SpinLock<T> {
+        SpinLock { locked: AtomicBool::new(false), value: UnsafeCell::new(value) }
+    }
+
+    fn lock(&self) -> SpinLockGuard<'_, T> {
+        while self.locked.compare_exchange_weak(false, true,
+                Ordering::Acquire, Ordering::Relaxed).is_err() {
+            core::hint::spin_loop();
+        }
+        SpinLockGuard { lock: self }
+    }
+}
+
+impl<'a, T> Deref for SpinLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for SpinLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinLockGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+impl RuntimeVerification {
+    #[cfg(feature = "std")]
+    fn from_env() -> RuntimeVerification {
+        match std::env::var("OBJC_VERIFY_MESSAGE").as_ref().map(|s| s.as_str()) {
+            Ok("always") | Ok("1") => RuntimeVerification::Enabled,
+            Ok("debug") => RuntimeVerification::DebugOnly,
+            _ => RuntimeVerification::Disabled,
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn from_env() -> RuntimeVerification {
+        RuntimeVerification::Disabled
+    }
+
+    fn is_active(self) -> bool {
+        match self {
+            RuntimeVerification::Disabled => false,
+            RuntimeVerification::DebugOnly => cfg!(debug_assertions),
+            RuntimeVerification::Enabled => true,
+        }
+    }
+}
+
+/// Returns when messages are verified at runtime.
+pub fn runtime_verification() -> RuntimeVerification {
+    match RUNTIME_VERIFICATION.load(Ordering::Relaxed) {
+        0 => {
+            let mode = RuntimeVerification::from_env();
+            // Don't overwrite a mode that was set in the meantime
+            let _ = RUNTIME_VERIFICATION.compare_exchange(0, mode as usize + 1,
+                Ordering::Relaxed, Ordering::Relaxed);
+            runtime_verification()
+        }
+        1 => RuntimeVerification::Disabled,
+        2 => RuntimeVerification::DebugOnly,
+        _ => RuntimeVerification::Enabled,
+    }
+}
+
+/// Sets when messages are verified at runtime, overriding the
+/// `OBJC_VERIFY_MESSAGE` environment variable.
+pub fn set_runtime_verification(mode: RuntimeVerification) {
+    RUNTIME_VERIFICATION.store(mode as usize + 1, Ordering::Relaxed);
+}
+
+/// Sets the function that messages failing runtime verification are
+/// reported to. By default, they are printed to stderr, or ignored without
+/// the `std` feature.
+pub fn set_verification_reporter(reporter: VerificationReporter) {
+    *REPORTER.lock() = Some(reporter);
+}
+
+#[cfg(feature = "std")]
+fn default_reporter(cls: &Class, sel: Sel, err: &MessageError) {
+    eprintln!("objc: sending {:?} to {:?} failed verification: {}", sel, cls, err);
+}
+
+#[cfg(not(feature = "std"))]
+fn default_reporter(_cls: &Class, _sel: Sel, _err: &MessageError) { }
+
+#[inline]
+pub fn runtime_verification_active() -> bool {
+    runtime_verification().is_active()
+}
+
+/// Verifies a message to `cls` by the sizes of its argument and return types,
+/// reporting it at most once for the selector and class if it fails.
+pub fn verify_message_at_runtime<A, R>(cls: &Class, sel: Sel)
+        where A: MessageArguments {
+    let err = match verify_message_sizes::<A, R>(cls, sel) {
+        Ok(()) => return,
+        Err(err) => MessageError::from(err),
+    };
+
+    let key = (cls as *const Class as usize, sel.as_ptr() as usize);
+    {
+        let mut reported = REPORTED.lock();
+        if !reported.get_or_insert_with(BTreeSet::new).insert(key) {
+            return;
+        }
+    }
+
+    let reporter = REPORTER.lock().unwrap_or(default_reporter);
+    reporter(cls, sel, &err);
+}
+
+fn verify_message_sizes<A, R>(cls: &Class, sel: Sel)
+        -> Result<(), VerificationError<'_>>
+        where A: MessageArguments {
+    fn encoding_size(encoding: &str) -> Option<usize> {
+        ParsedEncoding::parse(encoding)
+            .and_then(|enc| enc.layout())
+            .map(|layout| layout.size)
+    }
+
+    let method = match cls.instance_method(sel) {
+        Some(method) => method,
+        None => return Err(VerificationError::MethodNotFound(cls, sel)),
+    };
+
+    // Sizes are only compared when they can be determined from the encoding
+    let size = mem::size_of::<R>();
+    match encoding_size(&method.return_type()) {
+        Some(expected) if expected != size =>
+            return Err(VerificationError::MismatchedReturnSize(method, size)),
+        _ => (),
+    }
+
+    let sizes = match A::SIZES {
+        Some(sizes) => sizes,
+        None => return Ok(()),
+    };
+
+    // The first two arguments are always self and the selector
+    let count = 2 + sizes.len();
+    if count != method.arguments_count() {
+        return Err(VerificationError::MismatchedArgumentsCount(method, count));
+    }
+
+    for (i, &size) in sizes.iter().enumerate() {
+        let i = i + 2;
+        let expected = method.argument_type(i).and_then(|enc| encoding_size(&enc));
+        match expected {
+            Some(expected) if expected != size =>
+                return Err(VerificationError::MismatchedArgumentSize(method, i, size)),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
 
 pub enum VerificationError<'a> {
     NilReceiver(Sel),
     MethodNotFound(&'a Class, Sel),
     MismatchedReturn(&'a Method, Encoding<'static>),
+    MismatchedReturnSize(&'a Method, usize),
     MismatchedArgumentsCount(&'a Method, usize),
     MismatchedArgument(&'a Method, usize, Encoding<'static>),
+    MismatchedArgumentSize(&'a Method, usize, usize),
 }
 
 impl<'a> fmt::Display for VerificationError<'a> {
@@ -25,6 +370,11 @@ impl<'a> fmt::Display for VerificationError<'a> {
                 write!(f, "Return type code {} does not match expected {} for method {:?}",
                     ret, expected_ret, method.name())
             }
+            VerificationError::MismatchedReturnSize(method, size) => {
+                let expected_ret = method.return_type();
+                write!(f, "Return type of size {} does not match expected {} for method {:?}",
+                    size, expected_ret, method.name())
+            }
             VerificationError::MismatchedArgumentsCount(method, count) => {
                 let expected_count = method.arguments_count();
                 write!(f, "Method {:?} accepts {} arguments, but {} were given",
@@ -35,6 +385,11 @@ impl<'a> fmt::Display for VerificationError<'a> {
                 write!(f, "Method {:?} expected argument at index {} with type code {} but was given {}",
                     method.name(), i, expected, arg)
             }
+            VerificationError::MismatchedArgumentSize(method, i, size) => {
+                let expected = method.argument_type(i).unwrap();
+                write!(f, "Method {:?} expected argument at index {} with type code {} but was given a type of size {}",
+                    method.name(), i, expected, size)
+            }
         }
     }
 }
@@ -42,6 +397,12 @@ impl<'a> fmt::Display for VerificationError<'a> {
 pub fn verify_message_signature<A, R>(cls: &Class, sel: Sel)
         -> Result<(), VerificationError>
         where A: EncodeArguments, R: Encode {
+    verify_message_signature_with::<A, R>(cls, sel, verification_level())
+}
+
+pub fn verify_message_signature_with<A, R>(cls: &Class, sel: Sel,
+        level: VerificationLevel) -> Result<(), VerificationError<'_>>
+        where A: EncodeArguments, R: Encode {
     let method = match cls.instance_method(sel) {
         Some(method) => method,
         None => return Err(VerificationError::MethodNotFound(cls, sel)),
@@ -49,7 +410,7 @@ pub fn verify_message_signature<A, R>(cls: &Class, sel: Sel)
 
     let ret = R::ENCODING;
     let expected_ret = method.return_type();
-    if ret != *expected_ret {
+    if !encoding_matches(&expected_ret, &ret, level) {
         return Err(VerificationError::MismatchedReturn(method, ret));
     }
 
@@ -64,10 +425,56 @@ pub fn verify_message_signature<A, R>(cls: &Class, sel: Sel)
 
     for (i, arg) in self_and_cmd.iter().chain(args).copied().enumerate() {
         let expected = method.argument_type(i).unwrap();
-        if arg != *expected {
+        if !encoding_matches(&expected, &arg, level) {
             return Err(VerificationError::MismatchedArgument(method, i, arg));
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::{Object, BOOL};
+    use crate::{Encode, Encoding};
+    use super::{VerificationLevel, encoding_matches};
+
+    #[test]
+    fn test_exact() {
+        let level = VerificationLevel::Exact;
+        assert!(encoding_matches("r*", &<*const u8>::ENCODING, level));
+        assert!(encoding_matches("@", &<*mut Object>::ENCODING, level));
+        assert!(!encoding_matches("@\"NSString\"", &<*mut Object>::ENCODING, level));
+        assert!(!encoding_matches("I", &i32::ENCODING, level));
+    }
+
+    #[test]
+    fn test_abi_compatible() {
+        let level = VerificationLevel::AbiCompatible;
+        assert!(encoding_matches("B", &BOOL::ENCODING, level));
+        assert!(encoding_matches("I", &i32::ENCODING, level));
+        assert!(encoding_matches("@?", &<*mut Object>::ENCODING, level));
+        assert!(encoding_matches("{_NSRange=QQ}",
+            &Encoding::Struct("NSRange", &[Encoding::ULongLong, Encoding::ULongLong]), level));
+        assert!(!encoding_matches("f", &u32::ENCODING, level));
+        assert!(!encoding_matches("{Foo=ff}", &u64::ENCODING, level));
+    }
+
+    #[test]
+    fn test_lenient() {
+        let level = VerificationLevel::Lenient;
+        let point = Encoding::Struct("Point", &[Encoding::Double, Encoding::Double]);
+        assert!(encoding_matches("^{CGPoint=dd}", &Encoding::Pointer(&point), level));
+        assert!(encoding_matches("^{CGPoint}",
+            &Encoding::Pointer(&Encoding::Struct("CGPoint", &[])), level));
+        assert!(encoding_matches("^v", &Encoding::Pointer(&point), level));
+        assert!(encoding_matches("[4r^c]", &Encoding::Array(4, &Encoding::String), level));
+        assert!(!encoding_matches("{CGPoint=ff}", &point, level));
+        // The layout of an opaque struct is unknown, so only its name matches
+        let cg_point = Encoding::Struct("CGPoint", &[Encoding::Double, Encoding::Double]);
+        assert!(encoding_matches("{CGPoint}", &cg_point, level));
+        assert!(!encoding_matches("{CGPoint}", &cg_point, VerificationLevel::AbiCompatible));
+        assert!(!encoding_matches("^{CGPoint=dd}", &Encoding::Pointer(&point),
+            VerificationLevel::Exact));
+    }
+}
@@ -0,0 +1,689 @@
+//! A fake Objective-C runtime implemented in Rust, used in place of libobjc
+//! when the `"mock-runtime"` feature is enabled.
+//!
+//! It implements the runtime functions this crate uses with the same
+//! signatures, so `declare`, `rc` and `msg_send!` work without linking
+//! libobjc. Its state is kept in a global table protected by a lock, which is
+//! never held while a method implementation runs.
+//!
+//! Messages are dispatched by looking up the method's implementation and
+//! calling it directly, like GNUstep's runtime without `objc_msgSend`.
+//! A root `NSObject` class with the basic memory management methods is
+//! registered up front; no other classes are present. `+initialize` isn't
+//! sent, and a message that no method responds to panics.
+
+#![allow(non_snake_case)]
+
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::vec::Vec;
+use core::ffi::{CStr, c_char, c_int, c_uint, c_void};
+use core::mem;
+use core::ptr;
+use core::str;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::runtime::{BOOL, Class, Imp, Ivar, Method, NO, Protocol, Sel, YES};
+
+mod nsobject;
+mod object;
+
+pub use self::object::{class_createInstance, object_dispose, object_getClass,
+    objc_retain, objc_release, objc_autorelease,
+    objc_autoreleasePoolPush, objc_autoreleasePoolPop,
+    objc_initWeak, objc_loadWeakRetained, objc_destroyWeak, objc_copyWeak};
+
+extern {
+    fn malloc(size: usize) -> *mut c_void;
+    fn calloc(count: usize, size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+/// A registered selector, which a `Sel` points to.
+struct SelEntry {
+    name: CString,
+    types: Option<CString>,
+    // The untyped selector with the same name, which methods are looked up by
+    untyped: *const SelEntry,
+}
+
+/// A class or metaclass, which a `Class` points to.
+/// Like an object, it starts with a pointer to its class.
+#[repr(C)]
+struct MockClass {
+    isa: *const MockClass,
+    superclass: *const MockClass,
+    name: CString,
+    instance_size: usize,
+    ivars: Vec<Box<MockIvar>>,
+    methods: Vec<Box<MockMethod>>,
+    protocols: Vec<*const MockProtocol>,
+    registered: bool,
+}
+
+/// An instance variable, which an `Ivar` points to.
+struct MockIvar {
+    name: CString,
+    types: CString,
+    offset: isize,
+}
+
+/// A method, which a `Method` points to.
+struct MockMethod {
+    sel: Sel,
+    imp: Imp,
+    types: CString,
+}
+
+/// A method declared by a protocol.
+struct MethodDescription {
+    sel: Sel,
+    types: CString,
+    required: bool,
+    instance: bool,
+}
+
+/// A protocol, which a `Protocol` points to.
+struct MockProtocol {
+    name: CString,
+    protocols: Vec<*const MockProtocol>,
+    methods: Vec<MethodDescription>,
+    registered: bool,
+}
+
+struct State {
+    // Selectors by name; the untyped selector is always first
+    sels: HashMap<Vec<u8>, Vec<Box<SelEntry>>>,
+    // Classes by name, whether or not they've been registered yet
+    classes: HashMap<Vec<u8>, *mut MockClass>,
+    protocols: HashMap<Vec<u8>, *mut MockProtocol>,
+    objects: object::Objects,
+}
+
+// The raw pointers in the state are only dereferenced with the lock held
+unsafe impl Send for State { }
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+/// Calls `f` with the runtime's state, initializing it on first use.
+fn with_state<R, F>(f: F) -> R where F: FnOnce(&mut State) -> R {
+    // A panicking test may poison the lock, but the state stays consistent
+    let mut state = STATE.lock().unwrap_or_else(|err| err.into_inner());
+    let state = state.get_or_insert_with(State::new);
+    f(state)
+}
+
+impl State {
+    fn new() -> State {
+        let mut state = State {
+            sels: HashMap::new(),
+            classes: HashMap::new(),
+            protocols: HashMap::new(),
+            objects: object::Objects::new(),
+        };
+        unsafe {
+            nsobject::register(&mut state);
+        }
+        state
+    }
+
+    fn register_sel(&mut self, name: &CStr, types: Option<&CStr>) -> Sel {
+        let entries = self.sels.entry(name.to_bytes().to_vec()).or_insert_with(|| {
+            let mut untyped = Box::new(SelEntry {
+                name: name.to_owned(),
+                types: None,
+                untyped: ptr::null(),
+            });
+            untyped.untyped = &*untyped;
+            alloc::vec![untyped]
+        });
+        let existing = entries.iter().position(|entry| entry.types.as_deref() == types);
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                let untyped: *const SelEntry = &*entries[0];
+                entries.push(Box::new(SelEntry {
+                    name: name.to_owned(),
+                    types: types.map(CStr::to_owned),
+                    untyped: untyped,
+                }));
+                entries.len() - 1
+            }
+        };
+        let entry: *const SelEntry = &*entries[index];
+        unsafe { Sel::from_ptr(entry as *const c_void) }
+    }
+
+    unsafe fn allocate_class_pair(&mut self, superclass: *const MockClass,
+            name: &CStr) -> *mut MockClass {
+        if self.classes.contains_key(name.to_bytes()) {
+            return ptr::null_mut();
+        }
+
+        let new_class = |isa, superclass, instance_size| Box::into_raw(Box::new(MockClass {
+            isa: isa,
+            superclass: superclass,
+            name: name.to_owned(),
+            instance_size: instance_size,
+            ivars: Vec::new(),
+            methods: Vec::new(),
+            protocols: Vec::new(),
+            registered: false,
+        }));
+        let cls = if superclass.is_null() {
+            // The metaclass of a root class is an instance of itself and
+            // a subclass of the root class
+            let metaclass = new_class(ptr::null(), ptr::null(), mem::size_of::<MockClass>());
+            let cls = new_class(metaclass, ptr::null(), mem::size_of::<*const Class>());
+            (*metaclass).isa = metaclass;
+            (*metaclass).superclass = cls;
+            cls
+        } else {
+            let supermeta = (*superclass).isa;
+            let metaclass = new_class((*supermeta).isa, supermeta, mem::size_of::<MockClass>());
+            new_class(metaclass, superclass, (*superclass).instance_size)
+        };
+        self.classes.insert(name.to_bytes().to_vec(), cls);
+        self.objects.add_class(cls as *const Class);
+        self.objects.add_class((*cls).isa as *const Class);
+        cls
+    }
+
+    unsafe fn add_method(&mut self, cls: *mut MockClass, sel: Sel, imp: Imp,
+            types: &CStr) -> bool {
+        let key = sel_entry(sel).untyped;
+        if (*cls).methods.iter().any(|method| sel_entry(method.sel).untyped == key) {
+            return false;
+        }
+        (*cls).methods.push(Box::new(MockMethod {
+            sel: sel,
+            imp: imp,
+            types: types.to_owned(),
+        }));
+        true
+    }
+
+    fn get_class(&self, name: &CStr) -> *const MockClass {
+        match self.classes.get(name.to_bytes()) {
+            Some(&cls) if unsafe { (*cls).registered } => cls,
+            _ => ptr::null(),
+        }
+    }
+
+    fn registered_classes(&self) -> Vec<*const Class> {
+        self.classes.values()
+            .filter(|&&cls| unsafe { (*cls).registered })
+            .map(|&cls| cls as *const Class)
+            .collect()
+    }
+}
+
+fn sel_entry<'a>(sel: Sel) -> &'a SelEntry {
+    unsafe { &*(sel.as_ptr() as *const SelEntry) }
+}
+
+fn to_bool(value: bool) -> BOOL {
+    if value { YES } else { NO }
+}
+
+/// Copies `items` into a malloc'd array, or returns null if it's empty.
+unsafe fn copy_list<T: Copy>(items: &[T], outCount: *mut c_uint) -> *mut T {
+    if !outCount.is_null() {
+        *outCount = items.len() as c_uint;
+    }
+    if items.is_empty() {
+        return ptr::null_mut();
+    }
+    let list = malloc(mem::size_of_val(items)) as *mut T;
+    ptr::copy_nonoverlapping(items.as_ptr(), list, items.len());
+    list
+}
+
+/// Copies `s` into a malloc'd C string.
+unsafe fn copy_str(s: &str) -> *mut c_char {
+    let copy = malloc(s.len() + 1) as *mut c_char;
+    ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, copy, s.len());
+    *copy.add(s.len()) = 0;
+    copy
+}
+
+/// Finds the method for `sel` on `cls` or its superclasses.
+unsafe fn find_method(cls: *const MockClass, sel: Sel) -> *mut MockMethod {
+    let key = sel_entry(sel).untyped;
+    let mut cls = cls as *mut MockClass;
+    while !cls.is_null() {
+        for method in (*cls).methods.iter_mut() {
+            if sel_entry(method.sel).untyped == key {
+                return &mut **method;
+            }
+        }
+        cls = (*cls).superclass as *mut MockClass;
+    }
+    ptr::null_mut()
+}
+
+/// Returns the implementation of the method for `sel` on `cls` or its
+/// superclasses, which messages are dispatched to.
+pub(crate) fn lookup_method(cls: *const Class, sel: Sel) -> Option<Imp> {
+    with_state(|_| unsafe {
+        let method = find_method(cls as *const MockClass, sel);
+        if method.is_null() { None } else { Some((*method).imp) }
+    })
+}
+
+/// The implementation returned for selectors a class doesn't respond to.
+extern fn forward() {
+    eprintln!("objc mock runtime: unrecognized selector sent to instance");
+    std::process::abort();
+}
+
+unsafe fn protocol_conforms(proto: *const MockProtocol, other: *const MockProtocol) -> bool {
+    proto == other || (*proto).name == (*other).name ||
+        (*proto).protocols.iter().any(|&p| protocol_conforms(p, other))
+}
+
+pub unsafe fn sel_registerName(name: *const c_char) -> Sel {
+    with_state(|state| state.register_sel(CStr::from_ptr(name), None))
+}
+
+pub unsafe fn sel_getName(sel: Sel) -> *const c_char {
+    sel_entry(sel).name.as_ptr()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub unsafe fn sel_registerTypedName_np(name: *const c_char, types: *const c_char) -> Sel {
+    let types = if types.is_null() { None } else { Some(CStr::from_ptr(types)) };
+    with_state(|state| state.register_sel(CStr::from_ptr(name), types))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub unsafe fn sel_getType_np(sel: Sel) -> *const c_char {
+    sel_entry(sel).types.as_ref().map_or(ptr::null(), |types| types.as_ptr())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub unsafe fn sel_copyTypes_np(selName: *const c_char, types: *mut *const c_char, count: c_uint) -> c_uint {
+    with_state(|state| {
+        let name = CStr::from_ptr(selName).to_bytes();
+        let all_types: Vec<*const c_char> = state.sels.get(name).into_iter()
+            .flat_map(|entries| entries.iter())
+            .filter_map(|entry| entry.types.as_ref().map(|types| types.as_ptr()))
+            .collect();
+        if !types.is_null() {
+            for (i, &t) in all_types.iter().take(count as usize).enumerate() {
+                *types.add(i) = t;
+            }
+        }
+        all_types.len() as c_uint
+    })
+}
+
+pub unsafe fn class_getName(cls: *const Class) -> *const c_char {
+    (*(cls as *const MockClass)).name.as_ptr()
+}
+
+pub unsafe fn class_getSuperclass(cls: *const Class) -> *const Class {
+    (*(cls as *const MockClass)).superclass as *const Class
+}
+
+pub unsafe fn class_getInstanceSize(cls: *const Class) -> usize {
+    with_state(|_| (*(cls as *const MockClass)).instance_size)
+}
+
+pub unsafe fn class_getInstanceMethod(cls: *const Class, sel: Sel) -> *const Method {
+    with_state(|_| find_method(cls as *const MockClass, sel) as *const Method)
+}
+
+pub unsafe fn class_getMethodImplementation(cls: *const Class, name: Sel) -> Imp {
+    lookup_method(cls, name).unwrap_or(forward)
+}
+
+pub unsafe fn class_copyMethodList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Method {
+    with_state(|_| {
+        let methods: Vec<*const Method> = (*(cls as *const MockClass)).methods.iter()
+            .map(|method| &**method as *const MockMethod as *const Method)
+            .collect();
+        copy_list(&methods, outCount)
+    })
+}
+
+pub unsafe fn class_copyIvarList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Ivar {
+    with_state(|_| {
+        let ivars: Vec<*const Ivar> = (*(cls as *const MockClass)).ivars.iter()
+            .map(|ivar| &**ivar as *const MockIvar as *const Ivar)
+            .collect();
+        copy_list(&ivars, outCount)
+    })
+}
+
+pub unsafe fn class_addMethod(cls: *mut Class, name: Sel, imp: Imp, types: *const c_char) -> BOOL {
+    with_state(|state| {
+        to_bool(state.add_method(cls as *mut MockClass, name, imp, CStr::from_ptr(types)))
+    })
+}
+
+pub unsafe fn class_conformsToProtocol(cls: *const Class, proto: *const Protocol) -> BOOL {
+    with_state(|_| {
+        let proto = proto as *const MockProtocol;
+        to_bool((*(cls as *const MockClass)).protocols.iter()
+            .any(|&p| protocol_conforms(p, proto)))
+    })
+}
+
+pub unsafe fn class_respondsToSelector(cls: *const Class, sel: Sel) -> BOOL {
+    to_bool(lookup_method(cls, sel).is_some())
+}
+
+pub unsafe fn class_getInstanceVariable(cls: *const Class, name: *const c_char) -> *const Ivar {
+    with_state(|_| {
+        let name = CStr::from_ptr(name);
+        let mut cls = cls as *const MockClass;
+        while !cls.is_null() {
+            if let Some(ivar) = (*cls).ivars.iter().find(|ivar| *ivar.name == *name) {
+                return &**ivar as *const MockIvar as *const Ivar;
+            }
+            cls = (*cls).superclass;
+        }
+        ptr::null()
+    })
+}
+
+pub unsafe fn class_addIvar(cls: *mut Class, name: *const c_char, size: usize, alignment: u8, types: *const c_char) -> BOOL {
+    with_state(|_| {
+        let cls = cls as *mut MockClass;
+        let name = CStr::from_ptr(name);
+        if (*cls).registered || (*cls).ivars.iter().any(|ivar| *ivar.name == *name) {
+            return NO;
+        }
+        let align = 1usize << alignment;
+        let offset = ((*cls).instance_size + align - 1) & !(align - 1);
+        (*cls).instance_size = offset + size;
+        (*cls).ivars.push(Box::new(MockIvar {
+            name: name.to_owned(),
+            types: CStr::from_ptr(types).to_owned(),
+            offset: offset as isize,
+        }));
+        YES
+    })
+}
+
+pub unsafe fn class_addProtocol(cls: *mut Class, proto: *const Protocol) -> BOOL {
+    with_state(|_| {
+        let cls = cls as *mut MockClass;
+        let proto = proto as *const MockProtocol;
+        if (*cls).protocols.contains(&proto) {
+            return NO;
+        }
+        (*cls).protocols.push(proto);
+        YES
+    })
+}
+
+pub unsafe fn class_copyProtocolList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Protocol {
+    with_state(|_| {
+        let protocols: Vec<*const Protocol> = (*(cls as *const MockClass)).protocols.iter()
+            .map(|&proto| proto as *const Protocol)
+            .collect();
+        copy_list(&protocols, outCount)
+    })
+}
+
+pub unsafe fn objc_allocateClassPair(superclass: *const Class, name: *const c_char, _extraBytes: usize) -> *mut Class {
+    with_state(|state| {
+        state.allocate_class_pair(superclass as *const MockClass, CStr::from_ptr(name))
+            as *mut Class
+    })
+}
+
+pub unsafe fn objc_disposeClassPair(cls: *mut Class) {
+    with_state(|state| {
+        let cls = cls as *mut MockClass;
+        let metaclass = (*cls).isa as *mut MockClass;
+        state.classes.remove((*cls).name.to_bytes());
+        state.objects.remove_class(cls as *const Class);
+        state.objects.remove_class(metaclass as *const Class);
+        drop(Box::from_raw(metaclass));
+        drop(Box::from_raw(cls));
+    })
+}
+
+pub unsafe fn objc_registerClassPair(cls: *mut Class) {
+    with_state(|_| {
+        let cls = cls as *mut MockClass;
+        (*cls).registered = true;
+        (*((*cls).isa as *mut MockClass)).registered = true;
+    })
+}
+
+pub unsafe fn objc_getClassList(buffer: *mut *const Class, bufferLen: c_int) -> c_int {
+    with_state(|state| {
+        let classes = state.registered_classes();
+        if !buffer.is_null() {
+            for (i, &cls) in classes.iter().take(bufferLen.max(0) as usize).enumerate() {
+                *buffer.add(i) = cls;
+            }
+        }
+        classes.len() as c_int
+    })
+}
+
+pub unsafe fn objc_copyClassList(outCount: *mut c_uint) -> *mut *const Class {
+    with_state(|state| copy_list(&state.registered_classes(), outCount))
+}
+
+pub unsafe fn objc_getClass(name: *const c_char) -> *const Class {
+    with_state(|state| state.get_class(CStr::from_ptr(name)) as *const Class)
+}
+
+pub unsafe fn objc_getProtocol(name: *const c_char) -> *const Protocol {
+    with_state(|state| {
+        match state.protocols.get(CStr::from_ptr(name).to_bytes()) {
+            Some(&proto) if (*proto).registered => proto as *const Protocol,
+            _ => ptr::null(),
+        }
+    })
+}
+
+pub unsafe fn objc_copyProtocolList(outCount: *mut c_uint) -> *mut *const Protocol {
+    with_state(|state| {
+        let protocols: Vec<*const Protocol> = state.protocols.values()
+            .filter(|&&proto| (*proto).registered)
+            .map(|&proto| proto as *const Protocol)
+            .collect();
+        copy_list(&protocols, outCount)
+    })
+}
+
+pub unsafe fn objc_allocateProtocol(name: *const c_char) -> *mut Protocol {
+    with_state(|state| {
+        let name = CStr::from_ptr(name);
+        if state.protocols.contains_key(name.to_bytes()) {
+            return ptr::null_mut();
+        }
+        let proto = Box::into_raw(Box::new(MockProtocol {
+            name: name.to_owned(),
+            protocols: Vec::new(),
+            methods: Vec::new(),
+            registered: false,
+        }));
+        state.protocols.insert(name.to_bytes().to_vec(), proto);
+        proto as *mut Protocol
+    })
+}
+
+pub unsafe fn objc_registerProtocol(proto: *mut Protocol) {
+    with_state(|_| (*(proto as *mut MockProtocol)).registered = true)
+}
+
+pub unsafe fn protocol_getName(proto: *const Protocol) -> *const c_char {
+    (*(proto as *const MockProtocol)).name.as_ptr()
+}
+
+pub unsafe fn protocol_isEqual(proto: *const Protocol, other: *const Protocol) -> BOOL {
+    let proto = proto as *const MockProtocol;
+    let other = other as *const MockProtocol;
+    to_bool(proto == other || (*proto).name == (*other).name)
+}
+
+pub unsafe fn protocol_conformsToProtocol(proto: *const Protocol, other: *const Protocol) -> BOOL {
+    with_state(|_| {
+        to_bool(protocol_conforms(proto as *const MockProtocol, other as *const MockProtocol))
+    })
+}
+
+pub unsafe fn protocol_copyProtocolList(proto: *const Protocol, outCount: *mut c_uint) -> *mut *const Protocol {
+    with_state(|_| {
+        let protocols: Vec<*const Protocol> = (*(proto as *const MockProtocol)).protocols.iter()
+            .map(|&proto| proto as *const Protocol)
+            .collect();
+        copy_list(&protocols, outCount)
+    })
+}
+
+pub unsafe fn protocol_copyMethodDescriptionList(proto: *const Protocol, isRequiredMethod: BOOL, isInstanceMethod: BOOL,
+                                                 outCount: *mut c_uint) -> *mut crate::runtime::MethodDescription {
+    // The same layout as a MethodDescription, whose fields are private
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct RawDescription {
+        sel: Sel,
+        types: *const c_char,
+    }
+
+    with_state(|_| {
+        let methods: Vec<RawDescription> = (*(proto as *const MockProtocol)).methods.iter()
+            .filter(|method| method.required == (isRequiredMethod != NO) &&
+                method.instance == (isInstanceMethod != NO))
+            .map(|method| RawDescription { sel: method.sel, types: method.types.as_ptr() })
+            .collect();
+        copy_list(&methods, outCount) as *mut crate::runtime::MethodDescription
+    })
+}
+
+pub unsafe fn protocol_addMethodDescription(proto: *mut Protocol, name: Sel, types: *const c_char, isRequiredMethod: BOOL,
+                                            isInstanceMethod: BOOL) {
+    with_state(|_| {
+        (*(proto as *mut MockProtocol)).methods.push(MethodDescription {
+            sel: name,
+            types: CStr::from_ptr(types).to_owned(),
+            required: isRequiredMethod != NO,
+            instance: isInstanceMethod != NO,
+        });
+    })
+}
+
+pub unsafe fn protocol_addProtocol(proto: *mut Protocol, addition: *const Protocol) {
+    with_state(|_| {
+        (*(proto as *mut MockProtocol)).protocols.push(addition as *const MockProtocol);
+    })
+}
+
+pub unsafe fn ivar_getName(ivar: *const Ivar) -> *const c_char {
+    (*(ivar as *const MockIvar)).name.as_ptr()
+}
+
+pub unsafe fn ivar_getOffset(ivar: *const Ivar) -> isize {
+    (*(ivar as *const MockIvar)).offset
+}
+
+pub unsafe fn ivar_getTypeEncoding(ivar: *const Ivar) -> *const c_char {
+    (*(ivar as *const MockIvar)).types.as_ptr()
+}
+
+pub unsafe fn method_getName(method: *const Method) -> Sel {
+    (*(method as *const MockMethod)).sel
+}
+
+pub unsafe fn method_getImplementation(method: *const Method) -> Imp {
+    with_state(|_| (*(method as *const MockMethod)).imp)
+}
+
+/// Returns the encoding of the type at `index` in the type encoding of a
+/// method, where the return type is at index 0.
+unsafe fn method_type<'a>(method: *const Method, index: usize) -> Option<&'a str> {
+    crate::parse::method_type(&(*(method as *const MockMethod)).types, index)
+}
+
+pub unsafe fn method_copyReturnType(method: *const Method) -> *mut c_char {
+    method_type(method, 0).map_or(ptr::null_mut(), |ty| copy_str(ty))
+}
+
+pub unsafe fn method_copyArgumentType(method: *const Method, index: c_uint) -> *mut c_char {
+    method_type(method, index as usize + 1).map_or(ptr::null_mut(), |ty| copy_str(ty))
+}
+
+pub unsafe fn method_getNumberOfArguments(method: *const Method) -> c_uint {
+    crate::parse::method_arguments_count(&(*(method as *const MockMethod)).types) as c_uint
+}
+
+pub unsafe fn method_setImplementation(method: *mut Method, imp: Imp) -> Imp {
+    with_state(|_| mem::replace(&mut (*(method as *mut MockMethod)).imp, imp))
+}
+
+pub unsafe fn method_exchangeImplementations(m1: *mut Method, m2: *mut Method) {
+    with_state(|_| {
+        let m1 = m1 as *mut MockMethod;
+        let m2 = m2 as *mut MockMethod;
+        mem::swap(&mut (*m1).imp, &mut (*m2).imp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::declare::ClassDecl;
+    use crate::runtime::{BOOL, Class, NO, Object, Sel};
+
+    #[test]
+    fn test_duplicate_class() {
+        let superclass = class!(NSObject);
+        let decl = ClassDecl::new("MockDuplicateObject", superclass).unwrap();
+        assert!(ClassDecl::new("MockDuplicateObject", superclass).is_none());
+        // A class isn't visible until it's registered
+        assert!(Class::get("MockDuplicateObject").is_none());
+        let cls = decl.register();
+        assert!(Class::get("MockDuplicateObject") == Some(cls));
+        assert!(Class::classes().contains(&cls));
+    }
+
+    #[test]
+    fn test_class_methods() {
+        let cls = class!(NSObject);
+        let metaclass = cls.metaclass();
+        // The root metaclass inherits the root class's instance methods
+        assert!(metaclass.superclass() == Some(cls));
+        assert!(metaclass.metaclass() == metaclass);
+        assert!(metaclass.responds_to(sel!(alloc)));
+        assert!(metaclass.responds_to(sel!(hash)));
+        assert!(!cls.responds_to(sel!(alloc)));
+    }
+
+    #[test]
+    fn test_nsobject() {
+        unsafe {
+            let obj: *mut Object = msg_send![class!(NSObject), new];
+            assert!((*obj).class() == class!(NSObject));
+            let is_equal: BOOL = msg_send![obj, isEqual:obj];
+            assert!(is_equal != NO);
+            let hash: usize = msg_send![obj, hash];
+            assert!(hash == obj as usize);
+            let _: () = msg_send![obj, release];
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unrecognized_selector() {
+        unsafe {
+            let obj: *mut Object = msg_send![class!(NSObject), new];
+            let _: () = msg_send![obj, mockUnimplementedMethod];
+        }
+    }
+
+    #[test]
+    fn test_registered_sel() {
+        let sel = Sel::register("mockSel");
+        assert!(Sel::register("mockSel") == sel);
+        assert!(sel.name() == "mockSel");
+    }
+}
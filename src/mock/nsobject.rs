@@ -0,0 +1,136 @@
+//! The root `NSObject` class registered by the mock runtime.
+
+use alloc::ffi::CString;
+use alloc::string::ToString;
+use core::ffi::CStr;
+
+use crate::runtime::{BOOL, Class, Imp, Object, Sel, self};
+use crate::{Encode, Encoding};
+use super::{MockClass, State, to_bool};
+use super::object::retain_count;
+
+extern fn initialize(_cls: &Class, _cmd: Sel) { }
+
+extern fn alloc(cls: &Class, _cmd: Sel) -> *mut Object {
+    unsafe { runtime::class_createInstance(cls, 0) }
+}
+
+extern fn new(cls: &Class, _cmd: Sel) -> *mut Object {
+    unsafe {
+        let obj: *mut Object = msg_send![cls, alloc];
+        msg_send![obj, init]
+    }
+}
+
+extern fn class_self(cls: &Class, _cmd: Sel) -> *const Class {
+    cls
+}
+
+extern fn init(this: *mut Object, _cmd: Sel) -> *mut Object {
+    this
+}
+
+extern fn dealloc(this: *mut Object, _cmd: Sel) {
+    unsafe { runtime::object_dispose(this); }
+}
+
+extern fn retain(this: *mut Object, _cmd: Sel) -> *mut Object {
+    unsafe { runtime::objc_retain(this) }
+}
+
+extern fn release(this: *mut Object, _cmd: Sel) {
+    unsafe { runtime::objc_release(this); }
+}
+
+extern fn autorelease(this: *mut Object, _cmd: Sel) -> *mut Object {
+    unsafe { runtime::objc_autorelease(this); }
+    this
+}
+
+extern fn get_retain_count(this: *mut Object, _cmd: Sel) -> usize {
+    retain_count(this)
+}
+
+extern fn hash(this: *mut Object, _cmd: Sel) -> usize {
+    this as usize
+}
+
+extern fn is_equal(this: *mut Object, _cmd: Sel, other: *mut Object) -> BOOL {
+    to_bool(this == other)
+}
+
+extern fn class(this: &Object, _cmd: Sel) -> *const Class {
+    this.class()
+}
+
+extern fn responds_to_selector(this: &Object, _cmd: Sel, sel: Sel) -> BOOL {
+    to_bool(this.responds_to(sel))
+}
+
+extern fn is_kind_of_class(this: &Object, _cmd: Sel, cls: &Class) -> BOOL {
+    to_bool(this.is_kind_of(cls))
+}
+
+fn method_types(ret: &Encoding, args: &[Encoding]) -> CString {
+    let mut types = ret.to_string() + &<*mut Object>::ENCODING.to_string() +
+        &Sel::ENCODING.to_string();
+    for arg in args {
+        types += &arg.to_string();
+    }
+    CString::new(types).unwrap()
+}
+
+unsafe fn add_method(state: &mut State, cls: *mut MockClass, name: &str, imp: Imp,
+        ret: &Encoding, args: &[Encoding]) {
+    let name = CString::new(name).unwrap();
+    let sel = state.register_sel(&name, None);
+    let types = method_types(ret, args);
+    state.add_method(cls, sel, imp, &types);
+}
+
+/// Declares and registers `NSObject`.
+pub(super) unsafe fn register(state: &mut State) {
+    use core::mem::transmute;
+
+    let name = CStr::from_bytes_with_nul(b"NSObject\0").unwrap();
+    let cls = state.allocate_class_pair(core::ptr::null(), name);
+    let metaclass = (*cls).isa as *mut MockClass;
+    let object = <*mut Object>::ENCODING;
+
+    let f: extern fn(&Class, Sel) = initialize;
+    add_method(state, metaclass, "initialize", transmute(f), &<()>::ENCODING, &[]);
+    let f: extern fn(&Class, Sel) -> *mut Object = alloc;
+    add_method(state, metaclass, "alloc", transmute(f), &object, &[]);
+    let f: extern fn(&Class, Sel) -> *mut Object = new;
+    add_method(state, metaclass, "new", transmute(f), &object, &[]);
+    let f: extern fn(&Class, Sel) -> *const Class = class_self;
+    add_method(state, metaclass, "class", transmute(f), &<&Class>::ENCODING, &[]);
+
+    let f: extern fn(*mut Object, Sel) -> *mut Object = init;
+    add_method(state, cls, "init", transmute(f), &object, &[]);
+    let f: extern fn(*mut Object, Sel) = dealloc;
+    add_method(state, cls, "dealloc", transmute(f), &<()>::ENCODING, &[]);
+    let f: extern fn(*mut Object, Sel) -> *mut Object = retain;
+    add_method(state, cls, "retain", transmute(f), &object, &[]);
+    let f: extern fn(*mut Object, Sel) = release;
+    add_method(state, cls, "release", transmute(f), &<()>::ENCODING, &[]);
+    let f: extern fn(*mut Object, Sel) -> *mut Object = autorelease;
+    add_method(state, cls, "autorelease", transmute(f), &object, &[]);
+    let f: extern fn(*mut Object, Sel) -> usize = get_retain_count;
+    add_method(state, cls, "retainCount", transmute(f), &usize::ENCODING, &[]);
+    let f: extern fn(*mut Object, Sel) -> usize = hash;
+    add_method(state, cls, "hash", transmute(f), &usize::ENCODING, &[]);
+    let f: extern fn(*mut Object, Sel, *mut Object) -> BOOL = is_equal;
+    add_method(state, cls, "isEqual:", transmute(f), &BOOL::ENCODING, &[object]);
+    let f: extern fn(&Object, Sel) -> *const Class = class;
+    add_method(state, cls, "class", transmute(f), &<&Class>::ENCODING, &[]);
+    let f: extern fn(&Object, Sel, Sel) -> BOOL = responds_to_selector;
+    add_method(state, cls, "respondsToSelector:", transmute(f), &BOOL::ENCODING,
+        &[Sel::ENCODING]);
+    let f: extern fn(&Object, Sel, &Class) -> BOOL = is_kind_of_class;
+    add_method(state, cls, "isKindOfClass:", transmute(f), &BOOL::ENCODING,
+        &[<&Class>::ENCODING]);
+
+    (*cls).registered = true;
+    (*metaclass).registered = true;
+}
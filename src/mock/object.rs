@@ -0,0 +1,208 @@
+//! Instances, reference counting, weak references and autorelease pools for
+//! the mock runtime.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ffi::c_void;
+use core::mem;
+use core::ptr;
+use std::collections::{HashMap, HashSet};
+
+use crate::runtime::{Class, Object, Sel};
+use super::{MockClass, calloc, free, lookup_method, with_state};
+
+/// The bookkeeping for objects, kept in the runtime's state.
+pub(super) struct Objects {
+    // Classes are objects too, but are never deallocated
+    classes: HashSet<usize>,
+    // The retain counts of objects that have been retained, minus one
+    extra_retains: HashMap<usize, usize>,
+    // The locations of the weak references to each object
+    weak_refs: HashMap<usize, Vec<*mut *mut Object>>,
+}
+
+impl Objects {
+    pub(super) fn new() -> Objects {
+        Objects {
+            classes: HashSet::new(),
+            extra_retains: HashMap::new(),
+            weak_refs: HashMap::new(),
+        }
+    }
+
+    pub(super) fn add_class(&mut self, cls: *const Class) {
+        self.classes.insert(cls as usize);
+    }
+
+    pub(super) fn remove_class(&mut self, cls: *const Class) {
+        self.classes.remove(&(cls as usize));
+    }
+
+    fn retain(&mut self, obj: *mut Object) {
+        if !self.classes.contains(&(obj as usize)) {
+            *self.extra_retains.entry(obj as usize).or_insert(0) += 1;
+        }
+    }
+
+    /// Releases `obj`, returning whether it should be deallocated.
+    fn release(&mut self, obj: *mut Object) -> bool {
+        if self.classes.contains(&(obj as usize)) {
+            return false;
+        }
+        match self.extra_retains.get_mut(&(obj as usize)) {
+            Some(count) if *count > 1 => { *count -= 1; false }
+            Some(_) => { self.extra_retains.remove(&(obj as usize)); false }
+            None => {
+                // Weak references can't load an object once it's deallocating
+                self.clear_weak_refs(obj);
+                true
+            }
+        }
+    }
+
+    pub(super) fn retain_count(&self, obj: *mut Object) -> usize {
+        self.extra_retains.get(&(obj as usize)).map_or(1, |count| count + 1)
+    }
+
+    unsafe fn add_weak_ref(&mut self, location: *mut *mut Object) {
+        let obj = *location;
+        if !obj.is_null() {
+            self.weak_refs.entry(obj as usize).or_default().push(location);
+        }
+    }
+
+    unsafe fn remove_weak_ref(&mut self, location: *mut *mut Object) {
+        let obj = *location;
+        if let Some(locations) = self.weak_refs.get_mut(&(obj as usize)) {
+            locations.retain(|&l| l != location);
+            if locations.is_empty() {
+                self.weak_refs.remove(&(obj as usize));
+            }
+        }
+    }
+
+    fn clear_weak_refs(&mut self, obj: *mut Object) {
+        for location in self.weak_refs.remove(&(obj as usize)).unwrap_or_default() {
+            unsafe { *location = ptr::null_mut(); }
+        }
+    }
+}
+
+thread_local! {
+    // The objects autoreleased on this thread, for every pool
+    static AUTORELEASED: RefCell<Vec<*mut Object>> = RefCell::new(Vec::new());
+}
+
+pub unsafe fn class_createInstance(cls: *const Class, extraBytes: usize) -> *mut Object {
+    if cls.is_null() {
+        return ptr::null_mut();
+    }
+    let size = with_state(|_| (*(cls as *const MockClass)).instance_size);
+    let obj = calloc(1, size + extraBytes) as *mut *const Class;
+    *obj = cls;
+    obj as *mut Object
+}
+
+pub unsafe fn object_dispose(obj: *mut Object) -> *mut Object {
+    if !obj.is_null() {
+        with_state(|state| {
+            state.objects.clear_weak_refs(obj);
+            state.objects.extra_retains.remove(&(obj as usize));
+        });
+        free(obj as *mut c_void);
+    }
+    ptr::null_mut()
+}
+
+pub unsafe fn object_getClass(obj: *const Object) -> *const Class {
+    if obj.is_null() {
+        ptr::null()
+    } else {
+        *(obj as *const *const Class)
+    }
+}
+
+pub unsafe fn objc_retain(obj: *mut Object) -> *mut Object {
+    if !obj.is_null() {
+        with_state(|state| state.objects.retain(obj));
+    }
+    obj
+}
+
+pub unsafe fn objc_release(obj: *mut Object) {
+    if obj.is_null() || !with_state(|state| state.objects.release(obj)) {
+        return;
+    }
+
+    let dealloc = sel!(dealloc);
+    match lookup_method(object_getClass(obj), dealloc) {
+        Some(imp) => {
+            let imp: unsafe extern fn(*mut Object, Sel) = mem::transmute(imp);
+            imp(obj, dealloc);
+        }
+        None => { object_dispose(obj); }
+    }
+}
+
+/// Returns the retain count of `obj`, which is 1 unless it's been retained.
+pub(super) fn retain_count(obj: *mut Object) -> usize {
+    with_state(|state| state.objects.retain_count(obj))
+}
+
+pub unsafe fn objc_autorelease(obj: *mut Object) {
+    if !obj.is_null() {
+        AUTORELEASED.with(|objects| objects.borrow_mut().push(obj));
+    }
+}
+
+pub unsafe fn objc_autoreleasePoolPush() -> *mut c_void {
+    // The context is the number of objects autoreleased by outer pools,
+    // offset by one so that it's never null
+    let count = AUTORELEASED.with(|objects| objects.borrow().len());
+    (count + 1) as *mut c_void
+}
+
+pub unsafe fn objc_autoreleasePoolPop(context: *mut c_void) {
+    let count = context as usize - 1;
+    // Releasing an object may autorelease more, so they're popped one by one
+    loop {
+        let obj = AUTORELEASED.with(|objects| {
+            let mut objects = objects.borrow_mut();
+            if objects.len() > count { objects.pop() } else { None }
+        });
+        match obj {
+            Some(obj) => objc_release(obj),
+            None => break,
+        }
+    }
+}
+
+pub unsafe fn objc_initWeak(location: *mut *mut Object, obj: *mut Object) -> *mut Object {
+    *location = obj;
+    with_state(|state| state.objects.add_weak_ref(location));
+    obj
+}
+
+pub unsafe fn objc_loadWeakRetained(location: *mut *mut Object) -> *mut Object {
+    with_state(|state| {
+        let obj = *location;
+        if !obj.is_null() {
+            state.objects.retain(obj);
+        }
+        obj
+    })
+}
+
+pub unsafe fn objc_destroyWeak(location: *mut *mut Object) {
+    with_state(|state| {
+        state.objects.remove_weak_ref(location);
+        *location = ptr::null_mut();
+    })
+}
+
+pub unsafe fn objc_copyWeak(to: *mut *mut Object, from: *mut *mut Object) {
+    with_state(|state| {
+        *to = *from;
+        state.objects.add_weak_ref(to);
+    })
+}
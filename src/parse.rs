@@ -0,0 +1,649 @@
+//! Parsing of the encoding strings reported by the runtime into an owned
+//! representation that can be inspected and compared structurally.
+
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::format;
+use alloc::vec::Vec;
+use core::ffi::{CStr, c_int, c_long, c_short};
+use core::mem;
+use core::str;
+
+use crate::Encoding;
+
+/// Type qualifiers that may prefix an encoding, like `r` for `const`.
+const QUALIFIERS: &[u8] = b"rnNoORVA";
+
+/// An owned, parsed Objective-C type encoding.
+///
+/// Unlike `Encoding`, this can represent everything the runtime may report,
+/// including class-typed objects like `@"NSString"` and opaque structs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParsedEncoding {
+    Char,
+    Short,
+    Int,
+    Long,
+    LongLong,
+    UChar,
+    UShort,
+    UInt,
+    ULong,
+    ULongLong,
+    Float,
+    Double,
+    LongDouble,
+    Bool,
+    Void,
+    String,
+    /// An object, with the name of its class or protocol if one was given.
+    Object(Option<String>),
+    Block,
+    Class,
+    Sel,
+    Unknown,
+    BitField(u32),
+    Pointer(Box<ParsedEncoding>),
+    Array(u32, Box<ParsedEncoding>),
+    /// A struct with its name and fields, or no fields if it is opaque.
+    Struct(String, Option<Vec<ParsedEncoding>>),
+    /// A union with its name and members, or no members if it is opaque.
+    Union(String, Option<Vec<ParsedEncoding>>),
+    Complex(Box<ParsedEncoding>),
+}
+
+/// The size and alignment of a type, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+}
+
+/// The register class of a scalar value in the calling convention.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarClass {
+    Integer,
+    Float,
+}
+
+/// A scalar leaf of a type, with its offset from the start of the type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scalar {
+    pub offset: usize,
+    pub size: usize,
+    pub class: ScalarClass,
+}
+
+impl ParsedEncoding {
+    /// Parses an encoding string, returning `None` if it is malformed or has
+    /// trailing characters.
+    pub fn parse(s: &str) -> Option<ParsedEncoding> {
+        let mut parser = Parser { s: s.as_bytes(), pos: 0 };
+        let enc = parser.parse_type()?;
+        if parser.pos == parser.s.len() { Some(enc) } else { None }
+    }
+
+    /// Converts an `Encoding` into its owned, parsed representation.
+    pub fn from_encoding(enc: &Encoding) -> ParsedEncoding {
+        use crate::Encoding as E;
+        match *enc {
+            E::Char => ParsedEncoding::Char,
+            E::Short => ParsedEncoding::Short,
+            E::Int => ParsedEncoding::Int,
+            E::Long => ParsedEncoding::Long,
+            E::LongLong => ParsedEncoding::LongLong,
+            E::UChar => ParsedEncoding::UChar,
+            E::UShort => ParsedEncoding::UShort,
+            E::UInt => ParsedEncoding::UInt,
+            E::ULong => ParsedEncoding::ULong,
+            E::ULongLong => ParsedEncoding::ULongLong,
+            E::Float => ParsedEncoding::Float,
+            E::Double => ParsedEncoding::Double,
+            E::Bool => ParsedEncoding::Bool,
+            E::Void => ParsedEncoding::Void,
+            E::String => ParsedEncoding::String,
+            E::Object => ParsedEncoding::Object(None),
+            E::Block => ParsedEncoding::Block,
+            E::Class => ParsedEncoding::Class,
+            E::Sel => ParsedEncoding::Sel,
+            E::Unknown => ParsedEncoding::Unknown,
+            E::BitField(b) => ParsedEncoding::BitField(b),
+            E::Pointer(t) =>
+                ParsedEncoding::Pointer(Box::new(ParsedEncoding::from_encoding(t))),
+            E::Array(len, item) =>
+                ParsedEncoding::Array(len, Box::new(ParsedEncoding::from_encoding(item))),
+            E::Struct(name, fields) => ParsedEncoding::Struct(name.to_owned(),
+                Some(fields.iter().map(ParsedEncoding::from_encoding).collect())),
+            E::Union(name, members) => ParsedEncoding::Union(name.to_owned(),
+                Some(members.iter().map(ParsedEncoding::from_encoding).collect())),
+        }
+    }
+
+    /// Returns a C declaration of `name` with the type of self, like
+    /// `int *name`, or only the type if `name` is empty.
+    pub fn c_declaration(&self, name: &str) -> String {
+        self.declare(name.to_owned())
+    }
+
+    fn declare(&self, declarator: String) -> String {
+        use self::ParsedEncoding::*;
+        let base = match *self {
+            Char => "char".to_owned(),
+            Short => "short".to_owned(),
+            Int => "int".to_owned(),
+            Long => "long".to_owned(),
+            LongLong => "long long".to_owned(),
+            UChar => "unsigned char".to_owned(),
+            UShort => "unsigned short".to_owned(),
+            UInt => "unsigned int".to_owned(),
+            ULong => "unsigned long".to_owned(),
+            ULongLong => "unsigned long long".to_owned(),
+            Float => "float".to_owned(),
+            Double => "double".to_owned(),
+            LongDouble => "long double".to_owned(),
+            Bool => "bool".to_owned(),
+            // Pointers to unknown types are usually function pointers
+            Void | Unknown => "void".to_owned(),
+            String => return Char.declare(format!("*{}", declarator)),
+            // A quoted name of only protocols, like `<NSCopying>`, is an `id`
+            Object(Some(ref name)) if name.starts_with('<') => format!("id{}", name),
+            Object(Some(ref name)) => return format!("{} *{}", name, declarator),
+            Object(None) => "id".to_owned(),
+            Block => return format!("void (^{})()", declarator),
+            Class => "Class".to_owned(),
+            Sel => "SEL".to_owned(),
+            BitField(bits) => return format!("unsigned int {}: {}", declarator, bits),
+            Pointer(ref pointee) => return pointee.declare(format!("*{}", declarator)),
+            Array(len, ref item) => {
+                // Pointers to arrays need parentheses, like `int (*name)[4]`
+                let declarator = if declarator.starts_with('*') {
+                    format!("({})", declarator)
+                } else {
+                    declarator
+                };
+                return item.declare(format!("{}[{}]", declarator, len));
+            }
+            Struct(ref name, ref fields) => aggregate_declaration("struct", name, fields),
+            Union(ref name, ref members) => aggregate_declaration("union", name, members),
+            Complex(ref item) => return format!("_Complex {}", item.declare(declarator)),
+        };
+        if declarator.is_empty() { base } else { format!("{} {}", base, declarator) }
+    }
+
+    /// Returns the size and alignment of self on the current target, or
+    /// `None` if they cannot be determined from the encoding alone.
+    pub fn layout(&self) -> Option<Layout> {
+        use self::ParsedEncoding::*;
+        let (size, align) = match *self {
+            Char | UChar | Bool => (1, 1),
+            Short | UShort => scalar_layout::<c_short>(),
+            Int | UInt => scalar_layout::<c_int>(),
+            Long | ULong => scalar_layout::<c_long>(),
+            LongLong | ULongLong => scalar_layout::<i64>(),
+            Float => scalar_layout::<f32>(),
+            Double => scalar_layout::<f64>(),
+            LongDouble => long_double_layout(),
+            Void => (0, 1),
+            String | Object(_) | Block | Class | Sel | Pointer(_) =>
+                scalar_layout::<*const u8>(),
+            Unknown => return None,
+            BitField(bits) => {
+                // Bitfields are approximated as occupying whole bytes
+                let size = (bits as usize + 7) / 8;
+                (size, 1)
+            }
+            Array(len, ref item) => {
+                let item = item.layout()?;
+                (item.size * len as usize, item.align)
+            }
+            Complex(ref item) => {
+                let item = item.layout()?;
+                (item.size * 2, item.align)
+            }
+            Struct(_, ref fields) => {
+                let fields = fields.as_ref()?;
+                let mut size = 0;
+                let mut align = 1;
+                for field in fields {
+                    let layout = field.layout()?;
+                    size = round_up(size, layout.align) + layout.size;
+                    align = align.max(layout.align);
+                }
+                (round_up(size, align), align)
+            }
+            Union(_, ref members) => {
+                let members = members.as_ref()?;
+                let mut size = 0;
+                let mut align = 1;
+                for member in members {
+                    let layout = member.layout()?;
+                    size = size.max(layout.size);
+                    align = align.max(layout.align);
+                }
+                (round_up(size, align), align)
+            }
+        };
+        Some(Layout { size, align })
+    }
+
+    /// Returns the scalar leaves of self in memory order, or `None` if the
+    /// layout of self cannot be determined.
+    pub fn scalars(&self) -> Option<Vec<Scalar>> {
+        let mut scalars = Vec::new();
+        self.push_scalars(0, &mut scalars)?;
+        scalars.sort_by_key(|s| s.offset);
+        Some(scalars)
+    }
+
+    fn push_scalars(&self, offset: usize, out: &mut Vec<Scalar>) -> Option<()> {
+        use self::ParsedEncoding::*;
+        match *self {
+            Void => (),
+            Float | Double | LongDouble => {
+                let size = self.layout()?.size;
+                out.push(Scalar { offset, size, class: ScalarClass::Float });
+            }
+            Array(len, ref item) => {
+                let size = item.layout()?.size;
+                for i in 0..len as usize {
+                    item.push_scalars(offset + i * size, out)?;
+                }
+            }
+            Complex(ref item) => {
+                let size = item.layout()?.size;
+                item.push_scalars(offset, out)?;
+                item.push_scalars(offset + size, out)?;
+            }
+            Struct(_, ref fields) => {
+                let mut field_offset = 0;
+                for field in fields.as_ref()? {
+                    let layout = field.layout()?;
+                    field_offset = round_up(field_offset, layout.align);
+                    field.push_scalars(offset + field_offset, out)?;
+                    field_offset += layout.size;
+                }
+            }
+            Union(_, ref members) => {
+                for member in members.as_ref()? {
+                    member.push_scalars(offset, out)?;
+                }
+            }
+            _ => {
+                let size = self.layout()?.size;
+                out.push(Scalar { offset, size, class: ScalarClass::Integer });
+            }
+        }
+        Some(())
+    }
+}
+
+/// Splits the type encoding of a method, like `v16@0:8`, into the encodings
+/// of its return type and arguments without their stack offsets, or returns
+/// `None` if it is malformed.
+#[cfg_attr(not(any(feature = "objfw", feature = "mock-runtime", feature = "class-dump")),
+    allow(dead_code))]
+pub fn split_method_types(s: &str) -> Option<Vec<&str>> {
+    let mut parser = Parser { s: s.as_bytes(), pos: 0 };
+    let mut types = Vec::new();
+    while parser.pos < parser.s.len() {
+        let start = parser.pos;
+        parser.parse_type()?;
+        types.push(&s[start..parser.pos]);
+        // Skip the offset, which may be negative or missing entirely
+        parser.eat(b'-');
+        while parser.peek().map_or(false, |c| c.is_ascii_digit()) {
+            parser.pos += 1;
+        }
+    }
+    Some(types)
+}
+
+/// Returns the type of a struct or union, with its fields inline if it is
+/// anonymous since there is no other way to refer to it.
+fn aggregate_declaration(keyword: &str, name: &str, fields: &Option<Vec<ParsedEncoding>>)
+        -> String {
+    match *fields {
+        Some(ref fields) if name.is_empty() || name == "?" => {
+            let mut decl = format!("{} {{ ", keyword);
+            for (i, field) in fields.iter().enumerate() {
+                decl += &field.c_declaration(&format!("field{}", i));
+                decl += "; ";
+            }
+            decl + "}"
+        }
+        _ if name.is_empty() || name == "?" => keyword.to_owned(),
+        _ => format!("{} {}", keyword, name),
+    }
+}
+
+fn scalar_layout<T>() -> (usize, usize) {
+    (mem::size_of::<T>(), mem::align_of::<T>())
+}
+
+#[cfg(target_arch = "x86_64")]
+fn long_double_layout() -> (usize, usize) { (16, 16) }
+
+#[cfg(target_arch = "x86")]
+fn long_double_layout() -> (usize, usize) {
+    if cfg!(any(target_os = "macos", target_os = "ios")) { (16, 16) } else { (12, 4) }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+fn long_double_layout() -> (usize, usize) {
+    if cfg!(all(target_arch = "aarch64", not(any(target_os = "macos", target_os = "ios")))) {
+        (16, 16)
+    } else {
+        (8, 8)
+    }
+}
+
+/// Rounds `n` up to a multiple of `align`.
+pub(crate) fn round_up(n: usize, align: usize) -> usize {
+    (n + align - 1) / align * align
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_int(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits = core::str::from_utf8(&self.s[start..self.pos]).ok()?;
+        digits.parse().ok()
+    }
+
+    /// Parses a quoted string, like a class name or a struct field name.
+    fn parse_quoted(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self.peek()? != b'"' {
+            self.pos += 1;
+        }
+        let name = core::str::from_utf8(&self.s[start..self.pos]).ok()?;
+        self.pos += 1;
+        Some(name.to_owned())
+    }
+
+    fn parse_type(&mut self) -> Option<ParsedEncoding> {
+        self.parse_type_in(false)
+    }
+
+    /// Parses a single type. In a struct with named fields, a quoted string
+    /// after an object may name the next field instead of the object's class.
+    fn parse_type_in(&mut self, named_fields: bool) -> Option<ParsedEncoding> {
+        while self.peek().map_or(false, |c| QUALIFIERS.contains(&c)) {
+            self.pos += 1;
+        }
+
+        let enc = match self.next()? {
+            b'c' => ParsedEncoding::Char,
+            b's' => ParsedEncoding::Short,
+            b'i' => ParsedEncoding::Int,
+            b'l' => ParsedEncoding::Long,
+            b'q' => ParsedEncoding::LongLong,
+            b'C' => ParsedEncoding::UChar,
+            b'S' => ParsedEncoding::UShort,
+            b'I' => ParsedEncoding::UInt,
+            b'L' => ParsedEncoding::ULong,
+            b'Q' => ParsedEncoding::ULongLong,
+            b'f' => ParsedEncoding::Float,
+            b'd' => ParsedEncoding::Double,
+            b'D' => ParsedEncoding::LongDouble,
+            b'B' => ParsedEncoding::Bool,
+            b'v' => ParsedEncoding::Void,
+            b'*' => ParsedEncoding::String,
+            b'#' => ParsedEncoding::Class,
+            b':' => ParsedEncoding::Sel,
+            b'?' => ParsedEncoding::Unknown,
+            b'@' => self.parse_object(named_fields)?,
+            b'b' => ParsedEncoding::BitField(self.parse_int()?),
+            b'^' => ParsedEncoding::Pointer(Box::new(self.parse_type()?)),
+            b'j' => ParsedEncoding::Complex(Box::new(self.parse_type()?)),
+            b'[' => {
+                let len = self.parse_int()?;
+                let item = self.parse_type()?;
+                if !self.eat(b']') {
+                    return None;
+                }
+                ParsedEncoding::Array(len, Box::new(item))
+            }
+            b'{' => {
+                let (name, fields) = self.parse_aggregate(b'}')?;
+                ParsedEncoding::Struct(name, fields)
+            }
+            b'(' => {
+                let (name, members) = self.parse_aggregate(b')')?;
+                ParsedEncoding::Union(name, members)
+            }
+            _ => return None,
+        };
+        Some(enc)
+    }
+
+    fn parse_object(&mut self, named_fields: bool) -> Option<ParsedEncoding> {
+        if self.eat(b'?') {
+            // Skip the extended signature of a block, like `@?<v@?>`
+            if self.eat(b'<') {
+                let mut depth = 1;
+                while depth > 0 {
+                    match self.next()? {
+                        b'<' => depth += 1,
+                        b'>' => depth -= 1,
+                        _ => (),
+                    }
+                }
+            }
+            return Some(ParsedEncoding::Block);
+        }
+
+        if self.peek() != Some(b'"') {
+            return Some(ParsedEncoding::Object(None));
+        }
+
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.parse_quoted()?;
+        // A class name is followed by the next field's name or the end of
+        // the struct; otherwise the quoted string named the next field
+        if named_fields && !matches!(self.peek(), None | Some(b'"') | Some(b'}')) {
+            self.pos = start;
+            return Some(ParsedEncoding::Object(None));
+        }
+        Some(ParsedEncoding::Object(Some(name)))
+    }
+
+    fn parse_aggregate(&mut self, close: u8)
+            -> Option<(String, Option<Vec<ParsedEncoding>>)> {
+        let start = self.pos;
+        while !matches!(self.peek()?, b'=' | b'}' | b')') {
+            self.pos += 1;
+        }
+        let name = core::str::from_utf8(&self.s[start..self.pos]).ok()?.to_owned();
+
+        if self.eat(close) {
+            return Some((name, None));
+        }
+        if !self.eat(b'=') {
+            return None;
+        }
+
+        let named_fields = self.peek() == Some(b'"');
+        let mut fields = Vec::new();
+        while !self.eat(close) {
+            if named_fields {
+                if !self.eat(b'"') {
+                    return None;
+                }
+                self.parse_quoted()?;
+            }
+            fields.push(self.parse_type_in(named_fields)?);
+        }
+        Some((name, Some(fields)))
+    }
+}
+
+/// Returns the encoding of the type at `index` in the type encoding of a
+/// method, where the return type is at index 0, as the runtimes without
+/// their own `method_copyArgumentType` and `method_copyReturnType` need.
+pub fn method_type(types: &CStr, index: usize) -> Option<&str> {
+    str::from_utf8(types.to_bytes()).ok()
+        .and_then(split_method_types)
+        .and_then(|types| types.get(index).cloned())
+}
+
+/// Returns the number of arguments in the type encoding of a method,
+/// including `self` and `_cmd`, or 0 if it is malformed.
+pub fn method_arguments_count(types: &CStr) -> usize {
+    str::from_utf8(types.to_bytes()).ok()
+        .and_then(split_method_types)
+        .map_or(0, |types| types.len().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Encode;
+    use core::ffi::CStr;
+
+    use super::{Layout, ParsedEncoding, ScalarClass, method_arguments_count, method_type,
+        split_method_types};
+
+    #[test]
+    fn test_parse_qualifiers() {
+        assert_eq!(ParsedEncoding::parse("r*"), Some(ParsedEncoding::String));
+        assert_eq!(ParsedEncoding::parse("Vv"), Some(ParsedEncoding::Void));
+        assert_eq!(ParsedEncoding::parse("^r^v"),
+            Some(ParsedEncoding::Pointer(Box::new(
+                ParsedEncoding::Pointer(Box::new(ParsedEncoding::Void))))));
+    }
+
+    #[test]
+    fn test_parse_objects() {
+        assert_eq!(ParsedEncoding::parse("@"), Some(ParsedEncoding::Object(None)));
+        assert_eq!(ParsedEncoding::parse("@\"NSString\""),
+            Some(ParsedEncoding::Object(Some("NSString".to_owned()))));
+        assert_eq!(ParsedEncoding::parse("@?"), Some(ParsedEncoding::Block));
+        assert_eq!(ParsedEncoding::parse("@?<v@?@>"), Some(ParsedEncoding::Block));
+    }
+
+    #[test]
+    fn test_parse_structs() {
+        let point = ParsedEncoding::parse("{CGPoint=dd}").unwrap();
+        assert_eq!(point, ParsedEncoding::Struct("CGPoint".to_owned(),
+            Some(vec![ParsedEncoding::Double, ParsedEncoding::Double])));
+
+        let opaque = ParsedEncoding::parse("^{__CFString}").unwrap();
+        assert_eq!(opaque, ParsedEncoding::Pointer(Box::new(
+            ParsedEncoding::Struct("__CFString".to_owned(), None))));
+
+        let named = ParsedEncoding::parse("{Pair=\"first\"@\"NSString\"\"second\"@}").unwrap();
+        assert_eq!(named, ParsedEncoding::Struct("Pair".to_owned(), Some(vec![
+            ParsedEncoding::Object(Some("NSString".to_owned())),
+            ParsedEncoding::Object(None),
+        ])));
+
+        let untyped = ParsedEncoding::parse("{Pair=\"first\"@\"second\"i}").unwrap();
+        assert_eq!(untyped, ParsedEncoding::Struct("Pair".to_owned(), Some(vec![
+            ParsedEncoding::Object(None),
+            ParsedEncoding::Int,
+        ])));
+
+        assert!(ParsedEncoding::parse("{CGPoint=dd").is_none());
+        assert!(ParsedEncoding::parse("ii").is_none());
+    }
+
+    #[test]
+    fn test_split_method_types() {
+        assert_eq!(split_method_types("v16@0:8"), Some(vec!["v", "@", ":"]));
+        assert_eq!(split_method_types("r^{CGPoint=dd}24@0:8Q16"),
+            Some(vec!["r^{CGPoint=dd}", "@", ":", "Q"]));
+        assert_eq!(split_method_types("i@:ii"), Some(vec!["i", "@", ":", "i", "i"]));
+        assert_eq!(split_method_types("v12@0:4i-4"), Some(vec!["v", "@", ":", "i"]));
+        assert_eq!(split_method_types("v@:{Foo"), None);
+    }
+
+    #[test]
+    fn test_method_type() {
+        let types = CStr::from_bytes_with_nul(b"r^{CGPoint=dd}24@0:8Q16\0").unwrap();
+        assert_eq!(method_type(types, 0), Some("r^{CGPoint=dd}"));
+        assert_eq!(method_type(types, 1), Some("@"));
+        assert_eq!(method_type(types, 3), Some("Q"));
+        assert_eq!(method_type(types, 4), None);
+        assert_eq!(method_arguments_count(types), 3);
+
+        let malformed = CStr::from_bytes_with_nul(b"v@:{Foo\0").unwrap();
+        assert_eq!(method_type(malformed, 0), None);
+        assert_eq!(method_arguments_count(malformed), 0);
+        let invalid = CStr::from_bytes_with_nul(b"v@:\xff\0").unwrap();
+        assert_eq!(method_type(invalid, 0), None);
+        assert_eq!(method_arguments_count(invalid), 0);
+    }
+
+    #[test]
+    fn test_from_encoding() {
+        let enc = <[u32; 4]>::ENCODING;
+        assert_eq!(ParsedEncoding::from_encoding(&enc),
+            ParsedEncoding::parse(&enc.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_c_declaration() {
+        let decl = |enc: &str, name: &str| ParsedEncoding::parse(enc).unwrap().c_declaration(name);
+        assert_eq!(decl("i", "count"), "int count");
+        assert_eq!(decl("r*", ""), "char *");
+        assert_eq!(decl("@\"NSString\"", "name"), "NSString *name");
+        assert_eq!(decl("@\"<NSCopying>\"", "key"), "id<NSCopying> key");
+        assert_eq!(decl("^^{CGPoint=dd}", "points"), "struct CGPoint **points");
+        assert_eq!(decl("[4^i]", "ints"), "int *ints[4]");
+        assert_eq!(decl("^[4i]", "ints"), "int (*ints)[4]");
+        assert_eq!(decl("^?", "callback"), "void *callback");
+        assert_eq!(decl("@?", "handler"), "void (^handler)()");
+        assert_eq!(decl("b3", "flags"), "unsigned int flags: 3");
+        assert_eq!(decl("{?=iC}", "pair"), "struct { int field0; unsigned char field1; } pair");
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = ParsedEncoding::parse("{Foo=cid}").unwrap().layout();
+        assert_eq!(layout, Some(Layout { size: 16, align: 8 }));
+
+        let layout = ParsedEncoding::parse("(Bar=c[3i])").unwrap().layout();
+        assert_eq!(layout, Some(Layout { size: 12, align: 4 }));
+
+        assert!(ParsedEncoding::parse("{Opaque}").unwrap().layout().is_none());
+    }
+
+    #[test]
+    fn test_scalars() {
+        let scalars = ParsedEncoding::parse("{Foo=cfd}").unwrap().scalars().unwrap();
+        let offsets: Vec<_> = scalars.iter().map(|s| (s.offset, s.class)).collect();
+        assert_eq!(offsets, [
+            (0, ScalarClass::Integer),
+            (4, ScalarClass::Float),
+            (8, ScalarClass::Float),
+        ]);
+    }
+}
@@ -1,4 +1,4 @@
-use std::os::raw::c_void;
+use core::ffi::c_void;
 use crate::runtime::{objc_autoreleasePoolPush, objc_autoreleasePoolPop};
 
 // we use a struct to ensure that objc_autoreleasePoolPop during unwinding.
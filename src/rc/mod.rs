@@ -7,6 +7,10 @@ A `StrongPtr` retains an object and releases the object when dropped.
 A `WeakPtr` will not retain the object, but can be upgraded to a `StrongPtr`
 and safely fails if the object has been deallocated.
 
+GCC's libobjc has no weak references, so `WeakPtr` isn't available with the
+`"gcc_libobjc"` feature, and autorelease pools require the `NSAutoreleasePool`
+class from a Foundation library like GNUstep's.
+
 These utilities are not intended to provide a fully safe interface, but can be
 useful when writing higher-level Rust wrappers for Objective-C code.
 
@@ -41,15 +45,25 @@ assert!(weak.load().is_null());
 */
 
 mod strong;
+#[cfg(not(feature = "gcc_libobjc"))]
 mod weak;
 mod autorelease;
 
 pub use self::strong::StrongPtr;
+#[cfg(not(feature = "gcc_libobjc"))]
 pub use self::weak::WeakPtr;
 pub use self::autorelease::autoreleasepool;
 
+/// Stands in for `WeakPtr` with GCC's libobjc, which has no weak references,
+/// so that using it explains why it's unavailable. It can't be constructed.
+#[cfg(feature = "gcc_libobjc")]
+#[deprecated(note = "GCC's libobjc has no weak references; \
+    WeakPtr requires Apple's runtime, GNUstep's libobjc2 or ObjFW")]
+pub enum WeakPtr { }
+
 // These tests use NSObject, which isn't present for GNUstep
-#[cfg(all(test, any(target_os = "macos", target_os = "ios")))]
+#[cfg(all(test, any(target_os = "macos", target_os = "ios", feature = "mock-runtime"),
+          not(any(feature = "objfw", feature = "gcc_libobjc"))))]
 mod tests {
     use crate::runtime::Object;
     use super::StrongPtr;
@@ -121,3 +135,54 @@ mod tests {
         assert!(retain_count(*cloned) == 1);
     }
 }
+
+#[cfg(all(test, feature = "objfw"))]
+mod objfw_tests {
+    use crate::runtime::Object;
+    use super::StrongPtr;
+    use super::autoreleasepool;
+
+    fn retain_count(obj: *mut Object) -> u32 {
+        unsafe { msg_send![obj, retainCount] }
+    }
+
+    #[test]
+    fn test_strong_clone() {
+        let obj = unsafe {
+            StrongPtr::new(msg_send![class!(OFObject), new])
+        };
+        assert!(retain_count(*obj) == 1);
+
+        let cloned = obj.clone();
+        assert!(retain_count(*obj) == 2);
+
+        drop(cloned);
+        assert!(retain_count(*obj) == 1);
+    }
+
+    #[test]
+    fn test_weak() {
+        let obj = unsafe {
+            StrongPtr::new(msg_send![class!(OFObject), new])
+        };
+        let weak = obj.weak();
+        assert!(*weak.load() == *obj);
+
+        drop(obj);
+        assert!(weak.load().is_null());
+    }
+
+    #[test]
+    fn test_autorelease() {
+        let obj = unsafe {
+            StrongPtr::new(msg_send![class!(OFObject), new])
+        };
+        let cloned = obj.clone();
+
+        autoreleasepool(|| {
+            obj.autorelease();
+            assert!(retain_count(*cloned) == 2);
+        });
+        assert!(retain_count(*cloned) == 1);
+    }
+}
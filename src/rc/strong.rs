@@ -1,11 +1,20 @@
-use std::fmt;
-use std::mem;
-use std::ops::Deref;
+use core::fmt;
+use core::mem;
+use core::ops::Deref;
 
 use crate::runtime::{Object, self};
+#[cfg(not(feature = "gcc_libobjc"))]
 use super::WeakPtr;
 
 /// A pointer that strongly references an object, ensuring it won't be deallocated.
+///
+/// It has the same ABI as an object pointer, but doesn't implement `Encode`:
+/// only methods of the `new` and `copy` families return a retained object it
+/// may take ownership of, and sending it as an argument would move it without
+/// ever releasing its reference. Receive a `*mut Object` from those methods
+/// and wrap it with `StrongPtr::new`, and dereference a `StrongPtr` to send
+/// the object pointer.
+#[repr(transparent)]
 pub struct StrongPtr(*mut Object);
 
 impl StrongPtr {
@@ -37,6 +46,9 @@ impl StrongPtr {
     }
 
     /// Returns a `WeakPtr` to self.
+    ///
+    /// Not available with GCC's libobjc, which has no weak references.
+    #[cfg(not(feature = "gcc_libobjc"))]
     pub fn weak(&self) -> WeakPtr {
         unsafe { WeakPtr::new(self.0) }
     }
@@ -1,5 +1,6 @@
-use std::cell::UnsafeCell;
-use std::ptr;
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::ptr;
 
 use crate::runtime::{Object, self};
 use super::StrongPtr;
@@ -3,32 +3,52 @@
 //! For more information on foreign functions, see Apple's documentation:
 //! <https://developer.apple.com/library/mac/documentation/Cocoa/Reference/ObjCRuntimeRef/index.html>
 
-use std::ffi::{CStr, CString};
-use std::fmt;
-use std::os::raw::{c_char, c_int, c_uint, c_void};
-use std::ptr;
-use std::str;
+use alloc::borrow::Cow;
+use alloc::ffi::CString;
+use alloc::format;
+#[cfg(not(any(target_os = "macos", target_os = "ios", feature = "objfw")))]
+use alloc::vec::Vec;
+use core::ffi::{CStr, c_char, c_uint, c_void};
+#[cfg(not(feature = "mock-runtime"))]
+use core::ffi::c_int;
+use core::fmt;
+use core::mem;
+use core::ptr;
+use core::str;
+use alloc::string::String;
 use malloc_buf::Malloc;
 
-use crate::Encode;
+use crate::{Encode, Message};
+#[cfg(not(feature = "objfw"))]
+pub use crate::snapshot::{ClassDiff, ClassSnapshot, Changes, IvarSnapshot, ProtocolMethodSnapshot,
+    ProtocolDiff, ProtocolSnapshot, RuntimeSnapshot, SnapshotDiff};
 
 /// The Objective-C `BOOL` type.
 ///
 /// To convert an Objective-C `BOOL` into a Rust `bool`, compare it with `NO`.
-#[cfg(not(target_arch = "aarch64"))]
-pub type BOOL = ::std::os::raw::c_schar;
+#[cfg(not(any(target_arch = "aarch64", feature = "objfw", feature = "gcc_libobjc")))]
+pub type BOOL = ::core::ffi::c_schar;
 /// The equivalent of true for Objective-C's `BOOL` type.
-#[cfg(not(target_arch = "aarch64"))]
+#[cfg(not(any(target_arch = "aarch64", feature = "objfw", feature = "gcc_libobjc")))]
 pub const YES: BOOL = 1;
 /// The equivalent of false for Objective-C's `BOOL` type.
-#[cfg(not(target_arch = "aarch64"))]
+#[cfg(not(any(target_arch = "aarch64", feature = "objfw", feature = "gcc_libobjc")))]
 pub const NO: BOOL = 0;
 
-#[cfg(target_arch = "aarch64")]
+// GCC's libobjc always uses unsigned char for BOOL
+#[cfg(feature = "gcc_libobjc")]
+pub type BOOL = ::core::ffi::c_uchar;
+#[cfg(feature = "gcc_libobjc")]
+pub const YES: BOOL = 1;
+#[cfg(feature = "gcc_libobjc")]
+pub const NO: BOOL = 0;
+
+// ObjFW always uses bool for BOOL
+#[cfg(all(any(target_arch = "aarch64", feature = "objfw"), not(feature = "gcc_libobjc")))]
 pub type BOOL = bool;
-#[cfg(target_arch = "aarch64")]
+#[cfg(all(any(target_arch = "aarch64", feature = "objfw"), not(feature = "gcc_libobjc")))]
 pub const YES: BOOL = true;
-#[cfg(target_arch = "aarch64")]
+#[cfg(all(any(target_arch = "aarch64", feature = "objfw"), not(feature = "gcc_libobjc")))]
 pub const NO: BOOL = false;
 
 /// A type that represents a method selector.
@@ -71,10 +91,31 @@ pub struct Object {
     _priv: PrivateMarker,
 }
 
+/// A description of a method declared by a protocol, with its selector and
+/// type encoding.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MethodDescription {
+    name: Sel,
+    types: *const c_char,
+}
+
 /// A pointer to the start of a method implementation.
 pub type Imp = unsafe extern fn();
 
-#[link(name = "objc", kind = "dylib")]
+/// A nullable function pointer, like an `Imp` or a callback, that can be sent
+/// in messages and used in declared methods, encoded as `^?`.
+///
+/// Function pointers can't implement `Encode` themselves, since the trait is
+/// defined in `objc-encode`; transmute other function types to an `Imp` to
+/// wrap them. It has the same ABI as an `Option<Imp>`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug)]
+pub struct FnPointer(pub Option<Imp>);
+
+#[cfg(not(feature = "mock-runtime"))]
+#[cfg_attr(not(feature = "objfw"), link(name = "objc", kind = "dylib"))]
+#[cfg_attr(feature = "objfw", link(name = "objfwrt", kind = "dylib"))]
 extern {
     pub fn sel_registerName(name: *const c_char) -> Sel;
     pub fn sel_getName(sel: Sel) -> *const c_char;
@@ -83,40 +124,25 @@ extern {
     pub fn class_getSuperclass(cls: *const Class) -> *const Class;
     pub fn class_getInstanceSize(cls: *const Class) -> usize;
     pub fn class_getInstanceMethod(cls: *const Class, sel: Sel) -> *const Method;
-    pub fn class_getInstanceVariable(cls: *const Class, name: *const c_char) -> *const Ivar;
+    pub fn class_getMethodImplementation(cls: *const Class, name: Sel) -> Imp;
     pub fn class_copyMethodList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Method;
     pub fn class_copyIvarList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Ivar;
     pub fn class_addMethod(cls: *mut Class, name: Sel, imp: Imp, types: *const c_char) -> BOOL;
-    pub fn class_addIvar(cls: *mut Class, name: *const c_char, size: usize, alignment: u8, types: *const c_char) -> BOOL;
-    pub fn class_addProtocol(cls: *mut Class, proto: *const Protocol) -> BOOL;
     pub fn class_conformsToProtocol(cls: *const Class, proto: *const Protocol) -> BOOL;
-    pub fn class_copyProtocolList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Protocol;
+    pub fn class_respondsToSelector(cls: *const Class, sel: Sel) -> BOOL;
 
     pub fn objc_allocateClassPair(superclass: *const Class, name: *const c_char, extraBytes: usize) -> *mut Class;
     pub fn objc_disposeClassPair(cls: *mut Class);
     pub fn objc_registerClassPair(cls: *mut Class);
 
-    pub fn class_createInstance(cls: *const Class, extraBytes: usize) -> *mut Object;
-    pub fn object_dispose(obj: *mut Object) -> *mut Object;
+    #[cfg(not(feature = "gcc_libobjc"))]
     pub fn object_getClass(obj: *const Object) -> *const Class;
 
     pub fn objc_getClassList(buffer: *mut *const Class, bufferLen: c_int) -> c_int;
-    pub fn objc_copyClassList(outCount: *mut c_uint) -> *mut *const Class;
     pub fn objc_getClass(name: *const c_char) -> *const Class;
-    pub fn objc_getProtocol(name: *const c_char) -> *const Protocol;
-    pub fn objc_copyProtocolList(outCount: *mut c_uint) -> *mut *const Protocol;
-    pub fn objc_allocateProtocol(name: *const c_char) -> *mut Protocol;
-    pub fn objc_registerProtocol(proto: *mut Protocol);
 
-    pub fn objc_autoreleasePoolPush() -> *mut c_void;
-    pub fn objc_autoreleasePoolPop(context: *mut c_void);
-
-    pub fn protocol_addMethodDescription(proto: *mut Protocol, name: Sel, types: *const c_char, isRequiredMethod: BOOL,
-                                         isInstanceMethod: BOOL);
-    pub fn protocol_addProtocol(proto: *mut Protocol, addition: *const Protocol);
     pub fn protocol_getName(proto: *const Protocol) -> *const c_char;
     pub fn protocol_isEqual(proto: *const Protocol, other: *const Protocol) -> BOOL;
-    pub fn protocol_copyProtocolList(proto: *const Protocol, outCount: *mut c_uint) -> *mut *const Protocol;
     pub fn protocol_conformsToProtocol(proto: *const Protocol, other: *const Protocol) -> BOOL;
 
     pub fn ivar_getName(ivar: *const Ivar) -> *const c_char;
@@ -124,12 +150,56 @@ extern {
     pub fn ivar_getTypeEncoding(ivar: *const Ivar) -> *const c_char;
 
     pub fn method_getName(method: *const Method) -> Sel;
+}
+
+// ObjFW doesn't support these, though some are emulated below
+#[cfg(not(any(feature = "objfw", feature = "mock-runtime")))]
+#[link(name = "objc", kind = "dylib")]
+extern {
+    pub fn class_getInstanceVariable(cls: *const Class, name: *const c_char) -> *const Ivar;
+    pub fn class_addIvar(cls: *mut Class, name: *const c_char, size: usize, alignment: u8, types: *const c_char) -> BOOL;
+    pub fn class_addProtocol(cls: *mut Class, proto: *const Protocol) -> BOOL;
+    pub fn class_copyProtocolList(cls: *const Class, outCount: *mut c_uint) -> *mut *const Protocol;
+
+    pub fn class_createInstance(cls: *const Class, extraBytes: usize) -> *mut Object;
+    pub fn object_dispose(obj: *mut Object) -> *mut Object;
+
+    pub fn objc_getProtocol(name: *const c_char) -> *const Protocol;
+    pub fn objc_copyProtocolList(outCount: *mut c_uint) -> *mut *const Protocol;
+
+    pub fn protocol_copyProtocolList(proto: *const Protocol, outCount: *mut c_uint) -> *mut *const Protocol;
+    pub fn protocol_copyMethodDescriptionList(proto: *const Protocol, isRequiredMethod: BOOL, isInstanceMethod: BOOL,
+                                              outCount: *mut c_uint) -> *mut MethodDescription;
+
     pub fn method_getImplementation(method: *const Method) -> Imp;
     pub fn method_copyReturnType(method: *const Method) -> *mut c_char;
     pub fn method_copyArgumentType(method: *const Method, index: c_uint) -> *mut c_char;
     pub fn method_getNumberOfArguments(method: *const Method) -> c_uint;
     pub fn method_setImplementation(method: *mut Method, imp: Imp) -> Imp;
     pub fn method_exchangeImplementations(m1: *mut Method, m2: *mut Method);
+}
+
+// Neither ObjFW nor GCC's libobjc can declare protocols at runtime
+#[cfg(not(any(feature = "objfw", feature = "gcc_libobjc", feature = "mock-runtime")))]
+#[link(name = "objc", kind = "dylib")]
+extern {
+    pub fn objc_allocateProtocol(name: *const c_char) -> *mut Protocol;
+    pub fn objc_registerProtocol(proto: *mut Protocol);
+
+    pub fn protocol_addMethodDescription(proto: *mut Protocol, name: Sel, types: *const c_char, isRequiredMethod: BOOL,
+                                         isInstanceMethod: BOOL);
+    pub fn protocol_addProtocol(proto: *mut Protocol, addition: *const Protocol);
+}
+
+// GCC's libobjc doesn't support these, though some are emulated below
+#[cfg(not(any(feature = "gcc_libobjc", feature = "mock-runtime")))]
+#[cfg_attr(not(feature = "objfw"), link(name = "objc", kind = "dylib"))]
+#[cfg_attr(feature = "objfw", link(name = "objfwrt", kind = "dylib"))]
+extern {
+    pub fn objc_copyClassList(outCount: *mut c_uint) -> *mut *const Class;
+
+    pub fn objc_autoreleasePoolPush() -> *mut c_void;
+    pub fn objc_autoreleasePoolPop(context: *mut c_void);
 
     pub fn objc_retain(obj: *mut Object) -> *mut Object;
     pub fn objc_release(obj: *mut Object);
@@ -141,22 +211,406 @@ extern {
     pub fn objc_copyWeak(to: *mut *mut Object, from: *mut *mut Object);
 }
 
+#[cfg(feature = "gcc_libobjc")]
+#[link(name = "objc", kind = "dylib")]
+extern {
+    pub fn sel_registerTypedName(name: *const c_char, types: *const c_char) -> Sel;
+    pub fn sel_getTypeEncoding(sel: Sel) -> *const c_char;
+    pub fn sel_copyTypedSelectorList(name: *const c_char, outCount: *mut c_uint) -> *mut Sel;
+}
+
+// The mock runtime implements the same functions in Rust
+#[cfg(feature = "mock-runtime")]
+pub use crate::mock::{sel_registerName, sel_getName, class_getName, class_getSuperclass,
+    class_getInstanceSize, class_getInstanceMethod, class_getMethodImplementation,
+    class_copyMethodList, class_copyIvarList, class_addMethod, class_conformsToProtocol,
+    class_respondsToSelector, objc_allocateClassPair, objc_disposeClassPair,
+    objc_registerClassPair, object_getClass, objc_getClassList, objc_getClass,
+    protocol_getName, protocol_isEqual, protocol_conformsToProtocol, ivar_getName,
+    ivar_getOffset, ivar_getTypeEncoding, method_getName};
+#[cfg(feature = "mock-runtime")]
+pub use crate::mock::{class_getInstanceVariable, class_addIvar, class_addProtocol,
+    class_copyProtocolList, class_createInstance, object_dispose, objc_getProtocol,
+    objc_copyProtocolList, protocol_copyProtocolList, protocol_copyMethodDescriptionList,
+    method_getImplementation,
+    method_copyReturnType, method_copyArgumentType, method_getNumberOfArguments,
+    method_setImplementation, method_exchangeImplementations};
+#[cfg(feature = "mock-runtime")]
+pub use crate::mock::{objc_allocateProtocol, objc_registerProtocol,
+    protocol_addMethodDescription, protocol_addProtocol};
+#[cfg(feature = "mock-runtime")]
+pub use crate::mock::{objc_copyClassList, objc_autoreleasePoolPush, objc_autoreleasePoolPop,
+    objc_retain, objc_release, objc_autorelease, objc_loadWeakRetained, objc_initWeak,
+    objc_destroyWeak, objc_copyWeak};
+#[cfg(all(feature = "mock-runtime", not(any(target_os = "macos", target_os = "ios"))))]
+pub use crate::mock::{sel_registerTypedName_np, sel_getType_np, sel_copyTypes_np};
+
+#[cfg(feature = "objfw")]
+#[link(name = "objfwrt", kind = "dylib")]
+extern {
+    pub fn method_getTypeEncoding(method: *const Method) -> *const c_char;
+}
+
+extern {
+    fn malloc(size: usize) -> *mut c_void;
+}
+
+#[cfg(any(feature = "objfw", feature = "gcc_libobjc"))]
+extern {
+    fn free(ptr: *mut c_void);
+}
+
+/// Takes ownership of a list copied from the runtime, which is null if it's
+/// empty. `Malloc` would replace null with a pointer that's misaligned for
+/// most types, so an empty buffer is allocated instead.
+unsafe fn copied_list<T>(list: *mut T, count: c_uint) -> Malloc<[T]> {
+    let list = if list.is_null() {
+        malloc(mem::size_of::<T>().max(1)) as *mut T
+    } else {
+        list
+    };
+    Malloc::from_array(list, count as usize)
+}
+
+/// Looks up an instance variable of a class or its superclasses by name.
+#[cfg(feature = "objfw")]
+#[allow(non_snake_case)]
+pub unsafe fn class_getInstanceVariable(cls: *const Class, name: *const c_char) -> *const Ivar {
+    let name = CStr::from_ptr(name);
+    let mut cls = cls;
+    while !cls.is_null() {
+        let mut count: c_uint = 0;
+        let ivars = class_copyIvarList(cls, &mut count);
+        let mut found = ptr::null();
+        for i in 0..count as usize {
+            let ivar = *ivars.add(i);
+            if CStr::from_ptr(ivar_getName(ivar)) == name {
+                found = ivar;
+                break;
+            }
+        }
+        free(ivars as *mut c_void);
+        if !found.is_null() {
+            return found;
+        }
+        cls = class_getSuperclass(cls);
+    }
+    ptr::null()
+}
+
+/// Copies the encoding of the type at `index` in the type encoding of a
+/// method, where the return type is at index 0, into a malloc'd string.
+#[cfg(feature = "objfw")]
+unsafe fn copy_method_type(method: *const Method, index: usize) -> *mut c_char {
+    let types = CStr::from_ptr(method_getTypeEncoding(method));
+    match crate::parse::method_type(types, index) {
+        Some(ty) => {
+            let copy = malloc(ty.len() + 1) as *mut c_char;
+            ptr::copy_nonoverlapping(ty.as_ptr() as *const c_char, copy, ty.len());
+            *copy.add(ty.len()) = 0;
+            copy
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns a malloc'd string describing a method's return type.
+#[cfg(feature = "objfw")]
+#[allow(non_snake_case)]
+pub unsafe fn method_copyReturnType(method: *const Method) -> *mut c_char {
+    copy_method_type(method, 0)
+}
+
+/// Returns a malloc'd string describing a single parameter type of a method,
+/// or null if the method has no parameter at the given index.
+#[cfg(feature = "objfw")]
+#[allow(non_snake_case)]
+pub unsafe fn method_copyArgumentType(method: *const Method, index: c_uint) -> *mut c_char {
+    copy_method_type(method, index as usize + 1)
+}
+
+/// Returns the number of arguments accepted by a method.
+#[cfg(feature = "objfw")]
+#[allow(non_snake_case)]
+pub unsafe fn method_getNumberOfArguments(method: *const Method) -> c_uint {
+    let types = CStr::from_ptr(method_getTypeEncoding(method));
+    crate::parse::method_arguments_count(types) as c_uint
+}
+
+/// Returns the class of an object, or null for nil. GCC's libobjc only
+/// defines this inline in its header, reading the object's `isa`.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn object_getClass(obj: *const Object) -> *const Class {
+    if obj.is_null() {
+        ptr::null()
+    } else {
+        *(obj as *const *const Class)
+    }
+}
+
+/// Creates a malloc'd list of all registered classes.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn objc_copyClassList(outCount: *mut c_uint) -> *mut *const Class {
+    let count = objc_getClassList(ptr::null_mut(), 0);
+    let classes = malloc(count as usize * mem::size_of::<*const Class>()) as *mut *const Class;
+    // Fewer classes may be returned if any were unregistered since counting
+    let count = objc_getClassList(classes, count).min(count);
+    if !outCount.is_null() {
+        *outCount = count as c_uint;
+    }
+    classes
+}
+
+/// Creates an autorelease pool, which requires an `NSAutoreleasePool` class
+/// like the one provided by GNUstep's Foundation.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn objc_autoreleasePoolPush() -> *mut c_void {
+    let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+    pool as *mut c_void
+}
+
+/// Releases the autorelease pool created by `objc_autoreleasePoolPush`.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn objc_autoreleasePoolPop(context: *mut c_void) {
+    let pool = context as *mut Object;
+    let _: () = msg_send![pool, release];
+}
+
+/// Retains an object by sending it `retain`.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn objc_retain(obj: *mut Object) -> *mut Object {
+    if obj.is_null() {
+        return obj;
+    }
+    msg_send![obj, retain]
+}
+
+/// Releases an object by sending it `release`.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn objc_release(obj: *mut Object) {
+    if !obj.is_null() {
+        let _: () = msg_send![obj, release];
+    }
+}
+
+/// Autoreleases an object by sending it `autorelease`.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn objc_autorelease(obj: *mut Object) {
+    if !obj.is_null() {
+        let _: *mut Object = msg_send![obj, autorelease];
+    }
+}
+
+/// Registers a typed selector, like GNUstep's function of the same name.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn sel_registerTypedName_np(name: *const c_char, types: *const c_char) -> Sel {
+    sel_registerTypedName(name, types)
+}
+
+/// Returns the types of a typed selector, like GNUstep's function of the
+/// same name.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn sel_getType_np(sel: Sel) -> *const c_char {
+    sel_getTypeEncoding(sel)
+}
+
+/// Copies the types of up to `count` typed selectors with the given name
+/// into `types`, returning the number of typed selectors.
+#[cfg(feature = "gcc_libobjc")]
+#[allow(non_snake_case)]
+pub unsafe fn sel_copyTypes_np(selName: *const c_char, types: *mut *const c_char, count: c_uint) -> c_uint {
+    let mut total: c_uint = 0;
+    let sels = sel_copyTypedSelectorList(selName, &mut total);
+    if sels.is_null() {
+        return 0;
+    }
+    for i in 0..total.min(count) as usize {
+        *types.add(i) = sel_getTypeEncoding(*sels.add(i));
+    }
+    free(sels as *mut c_void);
+    total
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios", feature = "objfw",
+              feature = "gcc_libobjc", feature = "mock-runtime")))]
+#[link(name = "objc", kind = "dylib")]
+extern {
+    pub fn sel_registerTypedName_np(name: *const c_char, types: *const c_char) -> Sel;
+    pub fn sel_getType_np(sel: Sel) -> *const c_char;
+    pub fn sel_copyTypes_np(selName: *const c_char, types: *mut *const c_char, count: c_uint) -> c_uint;
+}
+
+/// An error returned when a name passed to the runtime contains a nul byte,
+/// which can't be represented in a C string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InteriorNulError {
+    position: usize,
+}
+
+impl InteriorNulError {
+    /// Returns the position of the nul byte in the name.
+    pub fn nul_position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for InteriorNulError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nul byte found in name at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InteriorNulError { }
+
+/// Calls `f` with `s` as a nul-terminated C string, copied into a buffer on
+/// the stack unless it's too long, so that lookups by name don't allocate.
+fn try_with_c_str<R, F>(s: &str, f: F) -> Result<R, InteriorNulError>
+        where F: FnOnce(&CStr) -> R {
+    const MAX_LEN: usize = 128;
+
+    let bytes = s.as_bytes();
+    if let Some(position) = bytes.iter().position(|&b| b == 0) {
+        return Err(InteriorNulError { position: position });
+    }
+    if bytes.len() < MAX_LEN {
+        let mut buf = [0u8; MAX_LEN];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        let c_str = CStr::from_bytes_with_nul(&buf[..bytes.len() + 1]).unwrap();
+        Ok(f(c_str))
+    } else {
+        let c_string = CString::new(s).unwrap();
+        Ok(f(&c_string))
+    }
+}
+
+/// Like `try_with_c_str`, but panics if `s` contains a nul byte.
+fn with_c_str<R, F>(s: &str, f: F) -> R where F: FnOnce(&CStr) -> R {
+    match try_with_c_str(s, f) {
+        Ok(result) => result,
+        Err(err) => panic!("{:?}: {}", s, err),
+    }
+}
+
+/// Converts a name from the runtime into a `str`, panicking with a message
+/// that points to the `name_cstr` accessors if it isn't valid UTF-8.
+fn name_to_str(name: &CStr) -> &str {
+    str::from_utf8(name.to_bytes()).unwrap_or_else(|_| {
+        panic!("name {:?} isn't valid UTF-8; use name_cstr instead", name)
+    })
+}
+
+/// Writes a name from the runtime, replacing any invalid UTF-8.
+fn fmt_name(name: &CStr, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Display::fmt(&String::from_utf8_lossy(name.to_bytes()), f)
+}
+
 impl Sel {
     /// Registers a method with the Objective-C runtime system,
     /// maps the method name to a selector, and returns the selector value.
+    ///
+    /// Panics if `name` contains a nul byte.
     pub fn register(name: &str) -> Sel {
-        let name = CString::new(name).unwrap();
+        with_c_str(name, Sel::register_cstr)
+    }
+
+    /// Registers a method with the given name, like `Sel::register`, but
+    /// returns an error if `name` contains a nul byte.
+    pub fn try_register(name: &str) -> Result<Sel, InteriorNulError> {
+        try_with_c_str(name, Sel::register_cstr)
+    }
+
+    /// Registers a method with the given C string name, like
+    /// `Sel::register`.
+    pub fn register_cstr(name: &CStr) -> Sel {
         unsafe {
             sel_registerName(name.as_ptr())
         }
     }
 
+    /// Registers a method with the given type encoding with the Objective-C
+    /// runtime system, mapping the method name and types to a selector.
+    ///
+    /// The GNUstep and GCC runtimes record the types with the selector, so
+    /// that selectors with the same name but different types are distinct.
+    /// Other runtimes don't support typed selectors, so the types are ignored
+    /// and this is equivalent to `Sel::register`.
+    pub fn register_typed(name: &str, types: &str) -> Sel {
+        #[cfg(not(any(target_os = "macos", target_os = "ios", feature = "objfw")))]
+        {
+            with_c_str(name, |name| with_c_str(types, |types| unsafe {
+                sel_registerTypedName_np(name.as_ptr(), types.as_ptr())
+            }))
+        }
+        #[cfg(any(target_os = "macos", target_os = "ios", feature = "objfw"))]
+        {
+            let _ = types;
+            Sel::register(name)
+        }
+    }
+
     /// Returns the name of the method specified by self.
+    ///
+    /// Panics if the name isn't valid UTF-8.
     pub fn name(&self) -> &str {
-        let name = unsafe {
+        name_to_str(self.name_cstr())
+    }
+
+    /// Returns the name of the method specified by self as a C string,
+    /// which may not be valid UTF-8.
+    pub fn name_cstr(&self) -> &CStr {
+        unsafe {
             CStr::from_ptr(sel_getName(*self))
-        };
-        str::from_utf8(name.to_bytes()).unwrap()
+        }
+    }
+
+    /// Returns the type encoding recorded with self, or `None` if self is
+    /// untyped. Any invalid UTF-8 in the encoding is replaced.
+    #[cfg(not(any(target_os = "macos", target_os = "ios", feature = "objfw")))]
+    pub fn types(&self) -> Option<Cow<'static, str>> {
+        unsafe {
+            let types = sel_getType_np(*self);
+            if types.is_null() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(CStr::from_ptr(types).to_bytes()))
+            }
+        }
+    }
+
+    /// Returns the type encodings of every typed selector registered with
+    /// the given name. Any invalid UTF-8 in the encodings is replaced.
+    /// Returns no encodings if `name` contains a nul byte, since no selector
+    /// can be registered with it.
+    #[cfg(not(any(target_os = "macos", target_os = "ios", feature = "objfw")))]
+    pub fn registered_types(name: &str) -> Vec<Cow<'static, str>> {
+        try_with_c_str(name, Sel::registered_types_cstr).unwrap_or_default()
+    }
+
+    /// Returns the type encodings of every typed selector registered with
+    /// the given C string name, like `Sel::registered_types`.
+    #[cfg(not(any(target_os = "macos", target_os = "ios", feature = "objfw")))]
+    pub fn registered_types_cstr(name: &CStr) -> Vec<Cow<'static, str>> {
+        unsafe {
+            let count = sel_copyTypes_np(name.as_ptr(), ptr::null_mut(), 0);
+            let mut types = alloc::vec![ptr::null(); count as usize];
+            // More types may have been registered since counting them
+            let count = sel_copyTypes_np(name.as_ptr(), types.as_mut_ptr(), count)
+                .min(count);
+            types.truncate(count as usize);
+            types.into_iter()
+                .map(|t| String::from_utf8_lossy(CStr::from_ptr(t).to_bytes()))
+                .collect()
+        }
     }
 
     /// Wraps a raw pointer to a selector into a `Sel` object.
@@ -196,17 +650,23 @@ impl Clone for Sel {
 
 impl fmt::Debug for Sel {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name())
+        fmt_name(self.name_cstr(), f)
     }
 }
 
 impl Ivar {
     /// Returns the name of self.
+    ///
+    /// Panics if the name isn't valid UTF-8.
     pub fn name(&self) -> &str {
-        let name = unsafe {
+        name_to_str(self.name_cstr())
+    }
+
+    /// Returns the name of self as a C string, which may not be valid UTF-8.
+    pub fn name_cstr(&self) -> &CStr {
+        unsafe {
             CStr::from_ptr(ivar_getName(self))
-        };
-        str::from_utf8(name.to_bytes()).unwrap()
+        }
     }
 
     /// Returns the offset of self.
@@ -218,11 +678,23 @@ impl Ivar {
     }
 
     /// Returns the `Encoding` of self.
+    ///
+    /// Panics if the encoding isn't valid UTF-8, like an object type naming
+    /// a class whose name isn't.
     pub fn type_encoding(&self) -> &str {
-        let encoding = unsafe {
+        let encoding = self.type_encoding_cstr();
+        str::from_utf8(encoding.to_bytes()).unwrap_or_else(|_| {
+            panic!("encoding {:?} isn't valid UTF-8; use type_encoding_cstr instead",
+                encoding)
+        })
+    }
+
+    /// Returns the type encoding of self as a C string, which may not be
+    /// valid UTF-8.
+    pub fn type_encoding_cstr(&self) -> &CStr {
+        unsafe {
             CStr::from_ptr(ivar_getTypeEncoding(self))
-        };
-        str::from_utf8(encoding.to_bytes()).unwrap()
+        }
     }
 }
 
@@ -263,6 +735,9 @@ impl Method {
     }
 
     /// Returns the implementation of self.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
     pub fn implementation(&self) -> Imp {
         unsafe {
             method_getImplementation(self)
@@ -270,11 +745,42 @@ impl Method {
     }
 }
 
+impl MethodDescription {
+    /// Returns the name of the described method.
+    pub fn name(&self) -> Sel {
+        self.name
+    }
+
+    /// Returns the type encoding of the described method, or `None` if the
+    /// runtime doesn't know it. Any invalid UTF-8 in the encoding is replaced.
+    pub fn types(&self) -> Option<Cow<'_, str>> {
+        if self.types.is_null() {
+            None
+        } else {
+            let types = unsafe { CStr::from_ptr(self.types) };
+            Some(String::from_utf8_lossy(types.to_bytes()))
+        }
+    }
+}
+
 impl Class {
     /// Returns the class definition of a specified class, or `None` if the
     /// class is not registered with the Objective-C runtime.
+    ///
+    /// Panics if `name` contains a nul byte.
     pub fn get(name: &str) -> Option<&'static Class> {
-        let name = CString::new(name).unwrap();
+        with_c_str(name, Class::get_cstr)
+    }
+
+    /// Returns the class definition of a specified class, like `Class::get`,
+    /// but returns an error if `name` contains a nul byte.
+    pub fn try_get(name: &str) -> Result<Option<&'static Class>, InteriorNulError> {
+        try_with_c_str(name, Class::get_cstr)
+    }
+
+    /// Returns the class definition of the class with the given C string
+    /// name, like `Class::get`.
+    pub fn get_cstr(name: &CStr) -> Option<&'static Class> {
         unsafe {
             let cls = objc_getClass(name.as_ptr());
             if cls.is_null() { None } else { Some(&*cls) }
@@ -286,7 +792,7 @@ impl Class {
         unsafe {
             let mut count: c_uint = 0;
             let classes = objc_copyClassList(&mut count);
-            Malloc::from_array(classes as *mut _, count as usize)
+            copied_list(classes as *mut _, count)
         }
     }
 
@@ -298,11 +804,17 @@ impl Class {
     }
 
     /// Returns the name of self.
+    ///
+    /// Panics if the name isn't valid UTF-8.
     pub fn name(&self) -> &str {
-        let name = unsafe {
+        name_to_str(self.name_cstr())
+    }
+
+    /// Returns the name of self as a C string, which may not be valid UTF-8.
+    pub fn name_cstr(&self) -> &CStr {
+        unsafe {
             CStr::from_ptr(class_getName(self))
-        };
-        str::from_utf8(name.to_bytes()).unwrap()
+        }
     }
 
     /// Returns the superclass of self, or `None` if self is a root class.
@@ -340,8 +852,23 @@ impl Class {
 
     /// Returns the ivar for a specified instance variable of self, or `None`
     /// if self has no ivar with the given name.
+    ///
+    /// Panics if `name` contains a nul byte.
     pub fn instance_variable(&self, name: &str) -> Option<&Ivar> {
-        let name = CString::new(name).unwrap();
+        with_c_str(name, |name| self.instance_variable_cstr(name))
+    }
+
+    /// Returns the ivar for a specified instance variable of self, like
+    /// `instance_variable`, but returns an error if `name` contains a nul
+    /// byte.
+    pub fn try_instance_variable(&self, name: &str)
+            -> Result<Option<&Ivar>, InteriorNulError> {
+        try_with_c_str(name, |name| self.instance_variable_cstr(name))
+    }
+
+    /// Returns the ivar for the instance variable of self with the given
+    /// C string name, like `instance_variable`.
+    pub fn instance_variable_cstr(&self, name: &CStr) -> Option<&Ivar> {
         unsafe {
             let ivar = class_getInstanceVariable(self, name.as_ptr());
             if ivar.is_null() { None } else { Some(&*ivar) }
@@ -353,9 +880,27 @@ impl Class {
         unsafe {
             let mut count: c_uint = 0;
             let methods = class_copyMethodList(self, &mut count);
-            Malloc::from_array(methods as *mut _, count as usize)
+            copied_list(methods as *mut _, count)
+        }
+
+    }
+
+    /// Checks whether this class is the specified class or a subclass of it.
+    pub fn is_subclass_of(&self, cls: &Class) -> bool {
+        let mut current = Some(self);
+        while let Some(c) = current {
+            if c == cls {
+                return true;
+            }
+            current = c.superclass();
         }
+        false
+    }
 
+    /// Checks whether instances of this class respond to the specified
+    /// selector.
+    pub fn responds_to(&self, sel: Sel) -> bool {
+        unsafe { class_respondsToSelector(self, sel) == YES }
     }
 
     /// Checks whether this class conforms to the specified protocol.
@@ -364,11 +909,14 @@ impl Class {
     }
 
     /// Get a list of the protocols to which this class conforms.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
     pub fn adopted_protocols(&self) -> Malloc<[&Protocol]> {
         unsafe {
             let mut count: c_uint = 0;
             let protos = class_copyProtocolList(self, &mut count);
-            Malloc::from_array(protos as *mut _, count as usize)
+            copied_list(protos as *mut _, count)
         }
     }
 
@@ -377,7 +925,7 @@ impl Class {
         unsafe {
             let mut count: c_uint = 0;
             let ivars = class_copyIvarList(self, &mut count);
-            Malloc::from_array(ivars as *mut _, count as usize)
+            copied_list(ivars as *mut _, count)
         }
     }
 }
@@ -394,15 +942,37 @@ impl Eq for Class { }
 
 impl fmt::Debug for Class {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name())
+        fmt_name(self.name_cstr(), f)
     }
 }
 
 impl Protocol {
     /// Returns the protocol definition of a specified protocol, or `None` if the
     /// protocol is not registered with the Objective-C runtime.
+    ///
+    /// Not available on ObjFW, which doesn't register protocols by name.
+    ///
+    /// Panics if `name` contains a nul byte.
+    #[cfg(not(feature = "objfw"))]
     pub fn get(name: &str) -> Option<&'static Protocol> {
-        let name = CString::new(name).unwrap();
+        with_c_str(name, Protocol::get_cstr)
+    }
+
+    /// Returns the protocol definition of a specified protocol, like
+    /// `Protocol::get`, but returns an error if `name` contains a nul byte.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
+    pub fn try_get(name: &str) -> Result<Option<&'static Protocol>, InteriorNulError> {
+        try_with_c_str(name, Protocol::get_cstr)
+    }
+
+    /// Returns the protocol definition of the protocol with the given
+    /// C string name, like `Protocol::get`.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
+    pub fn get_cstr(name: &CStr) -> Option<&'static Protocol> {
         unsafe {
             let proto = objc_getProtocol(name.as_ptr());
             if proto.is_null() { None } else { Some(&*proto) }
@@ -410,20 +980,43 @@ impl Protocol {
     }
 
     /// Obtains the list of registered protocol definitions.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
     pub fn protocols() -> Malloc<[&'static Protocol]> {
         unsafe {
             let mut count: c_uint = 0;
             let protocols = objc_copyProtocolList(&mut count);
-            Malloc::from_array(protocols as *mut _, count as usize)
+            copied_list(protocols as *mut _, count)
         }
     }
 
     /// Get a list of the protocols to which this protocol conforms.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
     pub fn adopted_protocols(&self) -> Malloc<[&Protocol]> {
         unsafe {
             let mut count: c_uint = 0;
             let protocols = protocol_copyProtocolList(self, &mut count);
-            Malloc::from_array(protocols as *mut _, count as usize)
+            copied_list(protocols as *mut _, count)
+        }
+    }
+
+    /// Get a list of the methods declared by this protocol, either the
+    /// required or optional ones and either its instance or class methods.
+    ///
+    /// Not available on ObjFW.
+    #[cfg(not(feature = "objfw"))]
+    pub fn method_descriptions(&self, required: bool, instance: bool)
+            -> Malloc<[MethodDescription]> {
+        let required = if required { YES } else { NO };
+        let instance = if instance { YES } else { NO };
+        unsafe {
+            let mut count: c_uint = 0;
+            let methods = protocol_copyMethodDescriptionList(self, required, instance,
+                &mut count);
+            copied_list(methods, count)
         }
     }
 
@@ -433,11 +1026,17 @@ impl Protocol {
     }
 
     /// Returns the name of self.
+    ///
+    /// Panics if the name isn't valid UTF-8.
     pub fn name(&self) -> &str {
-        let name = unsafe {
+        name_to_str(self.name_cstr())
+    }
+
+    /// Returns the name of self as a C string, which may not be valid UTF-8.
+    pub fn name_cstr(&self) -> &CStr {
+        unsafe {
             CStr::from_ptr(protocol_getName(self))
-        };
-        str::from_utf8(name.to_bytes()).unwrap()
+        }
     }
 }
 
@@ -451,7 +1050,49 @@ impl Eq for Protocol { }
 
 impl fmt::Debug for Protocol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name())
+        fmt_name(self.name_cstr(), f)
+    }
+}
+
+/**
+Types that represent instances of a specific Objective-C class, allowing an
+`Object` to be checked and downcast to them.
+
+Unsafe because references to an `Object` are cast to references of Self, so
+Self must be a zero-sized `#[repr(C)]` type like `Object` itself.
+
+# Example
+``` no_run
+# #[macro_use] extern crate objc;
+# use objc::runtime::{Class, ClassType, Object};
+# fn main() {
+#[repr(C)]
+struct NSString {
+    _priv: [u8; 0],
+}
+
+unsafe impl ClassType for NSString {
+    fn class() -> &'static Class {
+        class!(NSString)
+    }
+}
+
+# let obj: &Object = unsafe { &*(0 as *const Object) };
+if let Some(string) = obj.downcast_ref::<NSString>() {
+    // obj is an instance of NSString or one of its subclasses
+#   let _ = string;
+}
+# }
+```
+*/
+pub unsafe trait ClassType: Sized {
+    /// Returns the class whose instances are represented by Self.
+    fn class() -> &'static Class;
+
+    /// Returns the given object as a reference to Self, or `None` if it is
+    /// not an instance of `Self::class()` or one of its subclasses.
+    fn downcast(obj: &Object) -> Option<&Self> {
+        obj.downcast_ref()
     }
 }
 
@@ -463,6 +1104,48 @@ impl Object {
         }
     }
 
+    /// Checks whether self is an instance of the specified class or of one
+    /// of its subclasses.
+    ///
+    /// Unlike sending `isKindOfClass:`, this only uses the runtime, so it
+    /// works for any object, including instances of root classes that don't
+    /// implement the `NSObject` protocol.
+    pub fn is_kind_of(&self, cls: &Class) -> bool {
+        self.class().is_subclass_of(cls)
+    }
+
+    /// Checks whether self is an instance of exactly the specified class.
+    pub fn is_member_of(&self, cls: &Class) -> bool {
+        self.class() == cls
+    }
+
+    /// Checks whether self responds to the specified selector.
+    pub fn responds_to(&self, sel: Sel) -> bool {
+        self.class().responds_to(sel)
+    }
+
+    /// Returns a reference to self as the type `T`, or `None` if self is not
+    /// an instance of `T::class()` or one of its subclasses.
+    pub fn downcast_ref<T>(&self) -> Option<&T> where T: ClassType {
+        if self.is_kind_of(T::class()) {
+            let ptr = self as *const Object as *const T;
+            Some(unsafe { &*ptr })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to self as the type `T`, or `None` if
+    /// self is not an instance of `T::class()` or one of its subclasses.
+    pub fn downcast_mut<T>(&mut self) -> Option<&mut T> where T: ClassType {
+        if self.is_kind_of(T::class()) {
+            let ptr = self as *mut Object as *mut T;
+            Some(unsafe { &mut *ptr })
+        } else {
+            None
+        }
+    }
+
     /// Returns a reference to the ivar of self with the given name.
     /// Panics if self has no ivar with the given name.
     /// Unsafe because the caller must ensure that the ivar is actually
@@ -516,6 +1199,58 @@ impl Object {
             where T: Encode {
         *self.get_mut_ivar::<T>(name) = value;
     }
+
+    /// Returns the description of self by sending it `description`.
+    ///
+    /// If self doesn't respond to `description` or doesn't return a string,
+    /// this falls back to the `<ClassName: 0x...>` format of `Debug`.
+    /// The description is copied inside an autorelease pool, which with the
+    /// `"gcc_libobjc"` feature requires the `NSAutoreleasePool` class.
+    pub fn description(&self) -> String {
+        self.describe(sel!(description))
+    }
+
+    /// Returns the description of self for debugging by sending it
+    /// `debugDescription`, falling back like `description`.
+    pub fn debug_description(&self) -> String {
+        self.describe(sel!(debugDescription))
+    }
+
+    /// Returns an object that implements `Display` with the `description`
+    /// of self.
+    pub fn display(&self) -> Description<'_> {
+        Description { obj: self }
+    }
+
+    fn describe(&self, sel: Sel) -> String {
+        let description = crate::rc::autoreleasepool(|| unsafe {
+            if !self.responds_to(sel) {
+                return None;
+            }
+            let string: *mut Object = self.send_message(sel, ()).ok()?;
+            string.as_ref().and_then(|string| copy_string(string))
+        });
+        description.unwrap_or_else(|| format!("{:?}", self))
+    }
+}
+
+/// Copies the contents of a string object with its `UTF8String` or, for the
+/// constant strings of runtimes without Foundation, its `cString`.
+/// Returns `None` if it is not a string.
+unsafe fn copy_string(string: &Object) -> Option<String> {
+    let sel = if string.responds_to(sel!(UTF8String)) {
+        sel!(UTF8String)
+    } else if string.responds_to(sel!(cString)) {
+        sel!(cString)
+    } else {
+        return None;
+    };
+    let chars: *const c_char = string.send_message(sel, ()).ok()?;
+    if chars.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(chars).to_string_lossy().into_owned())
+    }
 }
 
 impl fmt::Debug for Object {
@@ -524,11 +1259,65 @@ impl fmt::Debug for Object {
     }
 }
 
-#[cfg(test)]
+/// Displays an object with its `description`, returned by `Object::display`.
+pub struct Description<'a> {
+    obj: &'a Object,
+}
+
+impl<'a> fmt::Display for Description<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.obj.description())
+    }
+}
+
+// These tests declare classes, which isn't supported on ObjFW
+#[cfg(all(test, not(feature = "objfw")))]
 mod tests {
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    use core::ffi::CStr;
+
     use crate::test_utils;
     use crate::Encode;
-    use super::{Class, Protocol, Sel};
+    use super::{Class, ClassType, Sel};
+    #[cfg(not(feature = "gcc_libobjc"))]
+    use super::Protocol;
+
+    #[test]
+    fn test_long_sel() {
+        let name = "a".repeat(200) + ":";
+        let sel = Sel::register(&name);
+        assert!(sel.name() == name);
+        assert!(Sel::register(&name) == sel);
+        assert!(Sel::try_register(&name) == Ok(sel));
+        assert!(Sel::register_cstr(sel.name_cstr()) == sel);
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    #[test]
+    fn test_typed_sel() {
+        let sel = Sel::register_typed("typedFoo:", "v@:i");
+        assert!(sel.name() == "typedFoo:");
+        assert!(sel.types().as_deref() == Some("v@:i"));
+        assert!(Sel::register("typedFoo:").types().is_none());
+
+        let other = Sel::register_typed("typedFoo:", "v@:d");
+        assert!(other != sel);
+        let types = Sel::registered_types("typedFoo:");
+        assert!(types.iter().any(|t| t == "v@:i"));
+        assert!(types.iter().any(|t| t == "v@:d"));
+        assert!(Sel::registered_types("typed\0Foo:").is_empty());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    #[test]
+    fn test_typed_sel_invalid_utf8() {
+        let name = CStr::from_bytes_with_nul(b"invalidTypes:\0").unwrap();
+        let types = CStr::from_bytes_with_nul(b"v@:{\xff=i}\0").unwrap();
+        let sel = unsafe { super::sel_registerTypedName_np(name.as_ptr(), types.as_ptr()) };
+        assert!(sel.types().as_deref() == Some("v@:{\u{fffd}=i}"));
+        let types = Sel::registered_types_cstr(name);
+        assert!(types.iter().any(|t| t == "v@:{\u{fffd}=i}"));
+    }
 
     #[test]
     fn test_ivar() {
@@ -536,7 +1325,11 @@ mod tests {
         let ivar = cls.instance_variable("_foo").unwrap();
         assert!(ivar.name() == "_foo");
         assert!(ivar.type_encoding() == &<u32>::ENCODING);
+        assert!(ivar.type_encoding_cstr().to_bytes() == b"I");
         assert!(ivar.offset() > 0);
+        assert!(ivar.name_cstr().to_bytes() == b"_foo");
+        assert!(cls.instance_variable_cstr(ivar.name_cstr()).is_some());
+        assert!(cls.try_instance_variable("_f\0o").err().unwrap().nul_position() == 2);
 
         let ivars = cls.instance_variables();
         assert!(ivars.len() > 0);
@@ -564,6 +1357,9 @@ mod tests {
         assert!(cls.superclass().is_none());
 
         assert!(Class::get(cls.name()) == Some(cls));
+        assert!(Class::get_cstr(cls.name_cstr()) == Some(cls));
+        assert!(Class::try_get("CustomObject") == Ok(Some(cls)));
+        assert!(Class::try_get("Custom\0Object").unwrap_err().nul_position() == 6);
 
         let metaclass = cls.metaclass();
         // The metaclass of a root class is a subclass of the root class
@@ -580,16 +1376,20 @@ mod tests {
         assert!(classes.len() > 0);
     }
 
+    #[cfg(not(feature = "gcc_libobjc"))]
     #[test]
     fn test_protocol() {
         let proto = test_utils::custom_protocol();
         assert!(proto.name() == "CustomProtocol");
+        assert!(Protocol::get_cstr(proto.name_cstr()) == Some(proto));
+        assert!(Protocol::try_get("Custom\0Protocol").is_err());
         let class = test_utils::custom_class();
         assert!(class.conforms_to(proto));
         let class_protocols = class.adopted_protocols();
         assert!(class_protocols.len() > 0);
     }
 
+    #[cfg(not(feature = "gcc_libobjc"))]
     #[test]
     fn test_protocol_method() {
         let class = test_utils::custom_class();
@@ -599,6 +1399,7 @@ mod tests {
         assert_eq!(result, 3);
     }
 
+    #[cfg(not(feature = "gcc_libobjc"))]
     #[test]
     fn test_subprotocols() {
         let sub_proto = test_utils::custom_subprotocol();
@@ -608,6 +1409,7 @@ mod tests {
         assert_eq!(adopted_protocols[0], super_proto);
     }
 
+    #[cfg(not(feature = "gcc_libobjc"))]
     #[test]
     fn test_protocols() {
         // Ensure that a protocol has been registered on linux
@@ -617,6 +1419,42 @@ mod tests {
         assert!(protocols.len() > 0);
     }
 
+    #[test]
+    fn test_is_kind_of() {
+        let obj = test_utils::custom_subclass_object();
+        let cls = test_utils::custom_class();
+        let subclass = test_utils::custom_subclass();
+        assert!(obj.is_kind_of(cls));
+        assert!(obj.is_kind_of(subclass));
+        assert!(obj.is_member_of(subclass));
+        assert!(!obj.is_member_of(cls));
+        assert!(!test_utils::custom_object().is_kind_of(subclass));
+
+        assert!(obj.responds_to(Sel::register("foo")));
+        assert!(!obj.responds_to(Sel::register("bar")));
+    }
+
+    #[test]
+    fn test_downcast() {
+        #[repr(C)]
+        struct CustomSubclassObject {
+            _priv: [u8; 0],
+        }
+
+        unsafe impl ClassType for CustomSubclassObject {
+            fn class() -> &'static Class {
+                test_utils::custom_subclass()
+            }
+        }
+
+        let mut obj = test_utils::custom_object();
+        assert!(obj.downcast_ref::<CustomSubclassObject>().is_none());
+        assert!(obj.downcast_mut::<CustomSubclassObject>().is_none());
+
+        let subclass_obj = test_utils::custom_subclass_object();
+        assert!(CustomSubclassObject::downcast(&subclass_obj).is_some());
+    }
+
     #[test]
     fn test_object() {
         let mut obj = test_utils::custom_object();
@@ -627,4 +1465,131 @@ mod tests {
         };
         assert!(result == 4);
     }
+
+    #[test]
+    fn test_description() {
+        use std::ffi::c_char;
+        use crate::declare::ClassDecl;
+        use super::{Object, class_createInstance, object_dispose};
+
+        extern fn description(this: &Object, _cmd: Sel) -> *const Object {
+            this
+        }
+
+        extern fn utf8_string(_this: &Object, _cmd: Sel) -> *const c_char {
+            b"A described object\0".as_ptr() as *const c_char
+        }
+
+        let mut decl = ClassDecl::new("DescribedObject", test_utils::custom_class()).unwrap();
+        unsafe {
+            decl.add_method(sel!(description),
+                description as extern fn(&Object, Sel) -> *const Object);
+            decl.add_method(sel!(UTF8String),
+                utf8_string as extern fn(&Object, Sel) -> *const c_char);
+        }
+        let cls = decl.register();
+
+        let obj = unsafe { &*class_createInstance(cls, 0) };
+        assert_eq!(obj.description(), "A described object");
+        assert_eq!(obj.display().to_string(), "A described object");
+        // Without debugDescription, the Debug format is used instead
+        let debug = format!("{:?}", obj);
+        assert!(debug.starts_with("<DescribedObject: "));
+        assert_eq!(obj.debug_description(), debug);
+        unsafe {
+            object_dispose(obj as *const Object as *mut Object);
+        }
+
+        let obj = test_utils::custom_object();
+        assert_eq!(obj.description(), format!("{:?}", *obj));
+    }
+}
+
+// GCC's libobjc lacks some runtime functions, which are emulated above
+#[cfg(all(test, feature = "gcc_libobjc"))]
+mod gcc_tests {
+    use alloc::ffi::CString;
+    use core::ptr;
+
+    use crate::test_utils;
+    use super::{Class, Sel, object_getClass, sel_copyTypes_np};
+
+    #[test]
+    fn test_object_get_class() {
+        let obj = test_utils::custom_object();
+        let cls = test_utils::custom_class();
+        unsafe {
+            assert!(ptr::eq(object_getClass(&*obj), cls));
+            assert!(object_getClass(ptr::null()).is_null());
+        }
+        assert!(obj.class() == cls);
+    }
+
+    #[test]
+    fn test_copy_class_list() {
+        let cls = test_utils::custom_class();
+        let classes = Class::classes();
+        assert!(classes.len() == Class::classes_count());
+        assert!(classes.contains(&cls));
+    }
+
+    #[test]
+    fn test_copy_types() {
+        Sel::register_typed("gccTypedFoo:", "v@:i");
+        Sel::register_typed("gccTypedFoo:", "v@:d");
+        let name = CString::new("gccTypedFoo:").unwrap();
+        let mut types = [ptr::null(); 1];
+        let count = unsafe { sel_copyTypes_np(name.as_ptr(), types.as_mut_ptr(), 1) };
+        assert!(count == 2);
+        assert!(!types[0].is_null());
+    }
+}
+
+// ObjFW's introspection is partly emulated from the method type encodings
+#[cfg(all(test, feature = "objfw"))]
+mod objfw_tests {
+    use core::ptr;
+
+    use super::Object;
+
+    #[test]
+    fn test_send_message() {
+        let cls = class!(OFObject);
+        unsafe {
+            let obj: *mut Object = msg_send![cls, new];
+            assert!((*obj).class() == cls);
+            let equal: bool = msg_send![obj, isEqual:obj];
+            assert!(equal);
+            let _: () = msg_send![obj, release];
+        }
+    }
+
+    #[test]
+    fn test_instance_variable() {
+        let cls = class!(OFObject);
+        let subclass = class!(OFNumber);
+        for ivar in cls.instance_variables().iter() {
+            let found = cls.instance_variable(ivar.name()).unwrap();
+            assert!(ptr::eq(found, *ivar));
+            // Instance variables of superclasses are found too
+            let found = subclass.instance_variable(ivar.name()).unwrap();
+            assert!(ptr::eq(found, *ivar));
+        }
+        assert!(cls.instance_variable("notAnIvar").is_none());
+    }
+
+    #[test]
+    fn test_method() {
+        let cls = class!(OFObject);
+        let method = cls.instance_method(sel!(isEqual:)).unwrap();
+        assert!(method.arguments_count() == 3);
+        assert!(&*method.argument_type(0).unwrap() == "@");
+        assert!(&*method.argument_type(1).unwrap() == ":");
+        assert!(&*method.argument_type(2).unwrap() == "@");
+        assert!(method.argument_type(3).is_none());
+        assert!(!method.return_type().is_empty());
+
+        let method = cls.instance_method(sel!(hash)).unwrap();
+        assert!(method.arguments_count() == 2);
+    }
 }
@@ -0,0 +1,477 @@
+//! Snapshots of the classes and protocols registered with the runtime, which
+//! can be compared to find what changed between them and, with the `"serde"`
+//! feature, saved and loaded.
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::runtime::{Class, Ivar, Method, Protocol};
+
+fn owned_name(name: &CStr) -> String {
+    String::from_utf8_lossy(name.to_bytes()).into_owned()
+}
+
+/// Returns the encodings of a method's return type and arguments, like
+/// `v@:i`, without the stack offsets some runtimes include.
+fn method_types(method: &Method) -> String {
+    let mut types = method.return_type().to_string();
+    for i in 0..method.arguments_count() {
+        if let Some(arg) = method.argument_type(i) {
+            types += &arg;
+        }
+    }
+    types
+}
+
+fn methods(cls: &Class) -> BTreeMap<String, String> {
+    cls.instance_methods().iter()
+        .map(|method| (owned_name(method.name().name_cstr()), method_types(method)))
+        .collect()
+}
+
+/// A snapshot of an instance variable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IvarSnapshot {
+    /// The name of the instance variable.
+    pub name: String,
+    /// The offset of the instance variable in its instances.
+    pub offset: isize,
+    /// The type encoding of the instance variable.
+    pub type_encoding: String,
+}
+
+impl IvarSnapshot {
+    fn capture(ivar: &Ivar) -> IvarSnapshot {
+        IvarSnapshot {
+            name: owned_name(ivar.name_cstr()),
+            offset: ivar.offset(),
+            type_encoding: owned_name(ivar.type_encoding_cstr()),
+        }
+    }
+}
+
+/// A snapshot of a class, with the methods of its metaclass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClassSnapshot {
+    /// The names of the superclasses of the class, starting with its direct
+    /// superclass and ending with its root class.
+    pub superclasses: Vec<String>,
+    /// The size of instances of the class.
+    pub instance_size: usize,
+    /// The instance variables declared by the class, in the runtime's order.
+    pub ivars: Vec<IvarSnapshot>,
+    /// The type encodings of the instance methods of the class, by selector.
+    pub instance_methods: BTreeMap<String, String>,
+    /// The type encodings of the class methods of the class, by selector.
+    pub class_methods: BTreeMap<String, String>,
+    /// The names of the protocols the class adopts.
+    pub protocols: Vec<String>,
+}
+
+impl ClassSnapshot {
+    /// Captures a snapshot of `cls`.
+    pub fn capture(cls: &Class) -> ClassSnapshot {
+        let mut superclasses = Vec::new();
+        let mut superclass = cls.superclass();
+        while let Some(cls) = superclass {
+            superclasses.push(owned_name(cls.name_cstr()));
+            superclass = cls.superclass();
+        }
+
+        let mut protocols: Vec<String> = cls.adopted_protocols().iter()
+            .map(|proto| owned_name(proto.name_cstr()))
+            .collect();
+        protocols.sort();
+
+        ClassSnapshot {
+            superclasses: superclasses,
+            instance_size: cls.instance_size(),
+            ivars: cls.instance_variables().iter().map(|&ivar| IvarSnapshot::capture(ivar)).collect(),
+            instance_methods: methods(cls),
+            class_methods: methods(cls.metaclass()),
+            protocols: protocols,
+        }
+    }
+}
+
+/// A snapshot of a method declared by a protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProtocolMethodSnapshot {
+    /// The type encoding of the method, if the runtime knows it.
+    pub types: Option<String>,
+    /// Whether classes adopting the protocol must implement the method.
+    pub required: bool,
+}
+
+/// A snapshot of a protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProtocolSnapshot {
+    /// The names of the protocols this protocol conforms to.
+    pub protocols: Vec<String>,
+    /// The instance methods declared by the protocol, by selector.
+    pub instance_methods: BTreeMap<String, ProtocolMethodSnapshot>,
+    /// The class methods declared by the protocol, by selector.
+    pub class_methods: BTreeMap<String, ProtocolMethodSnapshot>,
+}
+
+impl ProtocolSnapshot {
+    /// Captures a snapshot of `proto`.
+    pub fn capture(proto: &Protocol) -> ProtocolSnapshot {
+        let mut protocols: Vec<String> = proto.adopted_protocols().iter()
+            .map(|proto| owned_name(proto.name_cstr()))
+            .collect();
+        protocols.sort();
+
+        let methods = |instance| {
+            let mut methods = BTreeMap::new();
+            for &required in &[true, false] {
+                for method in proto.method_descriptions(required, instance).iter() {
+                    methods.insert(owned_name(method.name().name_cstr()), ProtocolMethodSnapshot {
+                        types: method.types().map(Cow::into_owned),
+                        required: required,
+                    });
+                }
+            }
+            methods
+        };
+
+        ProtocolSnapshot {
+            protocols: protocols,
+            instance_methods: methods(true),
+            class_methods: methods(false),
+        }
+    }
+}
+
+/**
+A snapshot of the classes and protocols registered with the runtime.
+
+Snapshots are plain data and can be compared with `diff`, for example to find
+what loading a library or registering a `ClassDecl` added.
+
+# Example
+``` no_run
+# use objc::runtime::RuntimeSnapshot;
+let before = RuntimeSnapshot::capture();
+// Load a plugin...
+let diff = before.diff(&RuntimeSnapshot::capture());
+println!("Added classes: {:?}", diff.classes.added);
+```
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuntimeSnapshot {
+    /// The registered classes, by name.
+    pub classes: BTreeMap<String, ClassSnapshot>,
+    /// The registered protocols, by name.
+    pub protocols: BTreeMap<String, ProtocolSnapshot>,
+}
+
+impl RuntimeSnapshot {
+    /// Captures a snapshot of every class and protocol registered with the
+    /// runtime.
+    pub fn capture() -> RuntimeSnapshot {
+        let classes = Class::classes().iter()
+            .map(|&cls| (owned_name(cls.name_cstr()), ClassSnapshot::capture(cls)))
+            .collect();
+        let protocols = Protocol::protocols().iter()
+            .map(|&proto| (owned_name(proto.name_cstr()), ProtocolSnapshot::capture(proto)))
+            .collect();
+        RuntimeSnapshot { classes: classes, protocols: protocols }
+    }
+
+    /// Returns the differences from self to a later snapshot, `other`.
+    pub fn diff(&self, other: &RuntimeSnapshot) -> SnapshotDiff {
+        let classes = Changes::between(&self.classes, &other.classes);
+        let class_diffs = classes.changed.iter()
+            .map(|name| (name.clone(), self.classes[name].diff(&other.classes[name])))
+            .collect();
+        let protocols = Changes::between(&self.protocols, &other.protocols);
+        let protocol_diffs = protocols.changed.iter()
+            .map(|name| (name.clone(), self.protocols[name].diff(&other.protocols[name])))
+            .collect();
+        SnapshotDiff {
+            classes: classes,
+            class_diffs: class_diffs,
+            protocols: protocols,
+            protocol_diffs: protocol_diffs,
+        }
+    }
+}
+
+/// The names of the items added, removed and changed between two snapshots.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Changes {
+    /// Items only in the later snapshot.
+    pub added: Vec<String>,
+    /// Items only in the earlier snapshot.
+    pub removed: Vec<String>,
+    /// Items in both snapshots that differ.
+    pub changed: Vec<String>,
+}
+
+impl Changes {
+    fn between<T: PartialEq>(old: &BTreeMap<String, T>, new: &BTreeMap<String, T>) -> Changes {
+        let mut changes = Changes::default();
+        for (name, old_item) in old {
+            match new.get(name) {
+                None => changes.removed.push(name.clone()),
+                Some(new_item) if new_item != old_item => changes.changed.push(name.clone()),
+                Some(_) => (),
+            }
+        }
+        changes.added = new.keys().filter(|name| !old.contains_key(*name)).cloned().collect();
+        changes
+    }
+
+    /// Returns whether nothing was added, removed or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The differences between two snapshots of the same class.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClassDiff {
+    /// Whether the superclass chain changed.
+    pub superclasses_changed: bool,
+    /// The instance variables that changed, by name.
+    pub ivars: Changes,
+    /// The instance methods that changed, by selector.
+    pub instance_methods: Changes,
+    /// The class methods that changed, by selector.
+    pub class_methods: Changes,
+    /// The adopted protocols that changed, by name.
+    pub protocols: Changes,
+}
+
+impl ClassSnapshot {
+    /// Returns the differences from self to a later snapshot of the same
+    /// class, `other`.
+    pub fn diff(&self, other: &ClassSnapshot) -> ClassDiff {
+        let ivars = |cls: &ClassSnapshot| -> BTreeMap<String, IvarSnapshot> {
+            cls.ivars.iter().map(|ivar| (ivar.name.clone(), ivar.clone())).collect()
+        };
+        let protocols = |cls: &ClassSnapshot| -> BTreeMap<String, ()> {
+            cls.protocols.iter().map(|name| (name.clone(), ())).collect()
+        };
+        ClassDiff {
+            superclasses_changed: self.superclasses != other.superclasses,
+            ivars: Changes::between(&ivars(self), &ivars(other)),
+            instance_methods: Changes::between(&self.instance_methods, &other.instance_methods),
+            class_methods: Changes::between(&self.class_methods, &other.class_methods),
+            protocols: Changes::between(&protocols(self), &protocols(other)),
+        }
+    }
+}
+
+/// The differences between two snapshots of the same protocol.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProtocolDiff {
+    /// The protocols conformed to that changed, by name.
+    pub protocols: Changes,
+    /// The instance methods that changed, by selector.
+    pub instance_methods: Changes,
+    /// The class methods that changed, by selector.
+    pub class_methods: Changes,
+}
+
+impl ProtocolSnapshot {
+    /// Returns the differences from self to a later snapshot of the same
+    /// protocol, `other`.
+    pub fn diff(&self, other: &ProtocolSnapshot) -> ProtocolDiff {
+        let protocols = |proto: &ProtocolSnapshot| -> BTreeMap<String, ()> {
+            proto.protocols.iter().map(|name| (name.clone(), ())).collect()
+        };
+        ProtocolDiff {
+            protocols: Changes::between(&protocols(self), &protocols(other)),
+            instance_methods: Changes::between(&self.instance_methods, &other.instance_methods),
+            class_methods: Changes::between(&self.class_methods, &other.class_methods),
+        }
+    }
+}
+
+/// The differences between two `RuntimeSnapshot`s.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotDiff {
+    /// The classes that were added, removed or changed.
+    pub classes: Changes,
+    /// The differences of each changed class, by name.
+    pub class_diffs: BTreeMap<String, ClassDiff>,
+    /// The protocols that were added, removed or changed.
+    pub protocols: Changes,
+    /// The differences of each changed protocol, by name.
+    pub protocol_diffs: BTreeMap<String, ProtocolDiff>,
+}
+
+impl SnapshotDiff {
+    /// Returns whether the snapshots were the same.
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty() && self.protocols.is_empty()
+    }
+}
+
+#[cfg(all(test, feature = "mock-runtime"))]
+mod tests {
+    use crate::declare::{ClassDecl, ProtocolDecl};
+    use crate::runtime::{Class, Imp, Object, Sel};
+    use alloc::collections::BTreeMap;
+
+    use super::{ClassSnapshot, ProtocolMethodSnapshot, ProtocolSnapshot, RuntimeSnapshot};
+
+    #[test]
+    fn test_class_snapshot() {
+        let mut decl = ClassDecl::new("SnapshotTestObject", class!(NSObject)).unwrap();
+        decl.add_ivar::<u32>("_count");
+        let cls = decl.register();
+
+        let snapshot = ClassSnapshot::capture(cls);
+        assert_eq!(snapshot.superclasses, ["NSObject"]);
+        assert_eq!(snapshot.ivars.len(), 1);
+        assert_eq!(snapshot.ivars[0].name, "_count");
+        assert_eq!(snapshot.ivars[0].type_encoding, "I");
+        assert!(snapshot.instance_methods.is_empty());
+        assert!(snapshot.protocols.is_empty());
+    }
+
+    #[test]
+    fn test_ivar_invalid_utf8() {
+        use core::ffi::CStr;
+        use crate::runtime;
+
+        let name = CStr::from_bytes_with_nul(b"SnapshotInvalidIvar\0").unwrap();
+        let types = CStr::from_bytes_with_nul(b"@\"\xff\"\0").unwrap();
+        let cls = unsafe {
+            let cls = runtime::objc_allocateClassPair(class!(NSObject), name.as_ptr(), 0);
+            runtime::class_addIvar(cls, b"_obj\0".as_ptr() as *const _,
+                core::mem::size_of::<usize>(),
+                core::mem::align_of::<usize>().trailing_zeros() as u8, types.as_ptr());
+            runtime::objc_registerClassPair(cls);
+            &*cls
+        };
+
+        let snapshot = ClassSnapshot::capture(cls);
+        assert_eq!(snapshot.ivars[0].type_encoding, "@\"\u{fffd}\"");
+    }
+
+    #[test]
+    fn test_diff() {
+        extern fn get_value(_this: &Object, _cmd: Sel) -> u32 { 7 }
+        extern fn make(_cls: &Class, _cmd: Sel) -> *mut Object { core::ptr::null_mut() }
+
+        let before = RuntimeSnapshot::capture();
+        assert!(before.diff(&before).is_empty());
+
+        let proto = ProtocolDecl::new("SnapshotTestProtocol").unwrap().register();
+        let mut decl = ClassDecl::new("SnapshotTestAdded", class!(NSObject)).unwrap();
+        decl.add_protocol(proto);
+        unsafe {
+            decl.add_method(sel!(value), get_value as extern fn(&Object, Sel) -> u32);
+            decl.add_class_method(sel!(make), make as extern fn(&Class, Sel) -> *mut Object);
+        }
+        decl.register();
+
+        let after = RuntimeSnapshot::capture();
+        let diff = before.diff(&after);
+        assert!(diff.classes.added.contains(&"SnapshotTestAdded".to_owned()));
+        assert!(diff.protocols.added.contains(&"SnapshotTestProtocol".to_owned()));
+        assert!(diff.classes.removed.is_empty());
+
+        let cls = &after.classes["SnapshotTestAdded"];
+        assert_eq!(cls.instance_methods["value"], "I@:");
+        assert_eq!(cls.class_methods["make"], "@@:");
+        assert_eq!(cls.protocols, ["SnapshotTestProtocol"]);
+
+        // Removing what was added is reported the other way around
+        let reverse = after.diff(&before);
+        assert!(reverse.classes.removed.contains(&"SnapshotTestAdded".to_owned()));
+    }
+
+    #[test]
+    fn test_class_diff() {
+        extern fn get_value(_this: &Object, _cmd: Sel) -> u32 { 7 }
+
+        let cls = ClassDecl::new("SnapshotTestChanged", class!(NSObject)).unwrap().register();
+        let before = RuntimeSnapshot::capture();
+        unsafe {
+            let f: extern fn(&Object, Sel) -> u32 = get_value;
+            let imp: Imp = core::mem::transmute(f);
+            crate::runtime::class_addMethod(cls as *const Class as *mut Class, sel!(value), imp,
+                b"I@:\0".as_ptr() as *const _);
+        }
+        let diff = before.diff(&RuntimeSnapshot::capture());
+
+        assert!(diff.classes.changed.contains(&"SnapshotTestChanged".to_owned()));
+        let class_diff = &diff.class_diffs["SnapshotTestChanged"];
+        assert_eq!(class_diff.instance_methods.added, ["value"]);
+        assert!(class_diff.class_methods.is_empty());
+        assert!(!class_diff.superclasses_changed);
+    }
+
+    #[test]
+    fn test_protocol_diff() {
+        let method = |types: &str| ProtocolMethodSnapshot {
+            types: Some(types.to_owned()),
+            required: true,
+        };
+        let mut methods = BTreeMap::new();
+        methods.insert("value".to_owned(), method("I@:"));
+        let before = ProtocolSnapshot {
+            protocols: vec!["NSObject".to_owned()],
+            instance_methods: methods,
+            class_methods: BTreeMap::new(),
+        };
+        let mut after = before.clone();
+        after.instance_methods.insert("value".to_owned(), method("Q@:"));
+        after.class_methods.insert("make".to_owned(), method("@@:"));
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.instance_methods.changed, ["value"]);
+        assert_eq!(diff.class_methods.added, ["make"]);
+        assert!(diff.protocols.is_empty());
+
+        let mut snapshot = RuntimeSnapshot::default();
+        snapshot.protocols.insert("SnapshotTestDiffed".to_owned(), before);
+        let mut later = snapshot.clone();
+        later.protocols.insert("SnapshotTestDiffed".to_owned(), after);
+        let diff = snapshot.diff(&later);
+        assert_eq!(diff.protocols.changed, ["SnapshotTestDiffed"]);
+        assert_eq!(diff.protocol_diffs["SnapshotTestDiffed"].class_methods.added, ["make"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let proto = ProtocolDecl::new("SnapshotTestSerialized").unwrap().register();
+        let mut decl = ClassDecl::new("SnapshotTestSerialized", class!(NSObject)).unwrap();
+        decl.add_protocol(proto);
+        decl.add_ivar::<u32>("_count");
+        decl.register();
+
+        let before = RuntimeSnapshot::default();
+        let snapshot = RuntimeSnapshot::capture();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let loaded: RuntimeSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert!(loaded.classes.contains_key("SnapshotTestSerialized"));
+
+        let diff = before.diff(&snapshot);
+        let json = serde_json::to_string(&diff).unwrap();
+        let loaded: super::SnapshotDiff = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, diff);
+    }
+}
@@ -1,9 +1,14 @@
 use std::ops::{Deref, DerefMut};
+#[cfg(not(feature = "gcc_libobjc"))]
 use std::os::raw::c_char;
 use std::sync::Once;
 
-use crate::declare::{ClassDecl, ProtocolDecl};
-use crate::runtime::{Class, Object, Protocol, Sel, self};
+use crate::declare::ClassDecl;
+#[cfg(not(feature = "gcc_libobjc"))]
+use crate::declare::ProtocolDecl;
+use crate::runtime::{Class, Object, Sel, self};
+#[cfg(not(feature = "gcc_libobjc"))]
+use crate::runtime::Protocol;
 use crate::{Encode, Encoding};
 
 pub struct CustomObject {
@@ -62,9 +67,8 @@ pub fn custom_class() -> &'static Class {
         extern fn custom_obj_class_initialize(_this: &Class, _cmd: Sel) { }
 
         let mut decl = ClassDecl::root("CustomObject", custom_obj_class_initialize).unwrap();
-        let proto = custom_protocol();
-
-        decl.add_protocol(proto);
+        #[cfg(not(feature = "gcc_libobjc"))]
+        decl.add_protocol(custom_protocol());
         decl.add_ivar::<u32>("_foo");
 
         extern fn custom_obj_set_foo(this: &mut Object, _cmd: Sel, foo: u32) {
@@ -93,7 +97,7 @@ pub fn custom_class() -> &'static Class {
 
         unsafe {
             let set_foo: extern fn(&mut Object, Sel, u32) = custom_obj_set_foo;
-            decl.add_method(sel!(setFoo:), set_foo);
+            add_method!(decl, sel!(setFoo:), set_foo);
             let get_foo: extern fn(&Object, Sel) -> u32 = custom_obj_get_foo;
             decl.add_method(sel!(foo), get_foo);
             let get_struct: extern fn(&Object, Sel) -> CustomStruct = custom_obj_get_struct;
@@ -104,7 +108,7 @@ pub fn custom_class() -> &'static Class {
             let protocol_instance_method: extern fn(&mut Object, Sel, u32) = custom_obj_set_bar;
             decl.add_method(sel!(setBar:), protocol_instance_method);
             let protocol_class_method: extern fn(&Class, Sel, i32, i32) -> i32 = custom_obj_add_number_to_number;
-            decl.add_class_method(sel!(addNumber:toNumber:), protocol_class_method);
+            add_class_method!(decl, sel!(addNumber:toNumber:), protocol_class_method);
         }
 
         decl.register();
@@ -113,6 +117,7 @@ pub fn custom_class() -> &'static Class {
     class!(CustomObject)
 }
 
+#[cfg(not(feature = "gcc_libobjc"))]
 pub fn custom_protocol() -> &'static Protocol {
     static REGISTER_CUSTOM_PROTOCOL: Once = Once::new();
 
@@ -121,7 +126,7 @@ pub fn custom_protocol() -> &'static Protocol {
 
         decl.add_method_description::<(i32,), ()>(sel!(setBar:), true);
         decl.add_method_description::<(), *const c_char>(sel!(getName), false);
-        decl.add_class_method_description::<(i32, i32), i32>(sel!(addNumber:toNumber:), true);
+        add_method_description!(decl, class sel!(addNumber:toNumber:), (i32, i32) -> i32, true);
 
         decl.register();
     });
@@ -129,6 +134,7 @@ pub fn custom_protocol() -> &'static Protocol {
     Protocol::get("CustomProtocol").unwrap()
 }
 
+#[cfg(not(feature = "gcc_libobjc"))]
 pub fn custom_subprotocol() -> &'static Protocol {
     static REGISTER_CUSTOM_SUBPROTOCOL: Once = Once::new();
 
@@ -0,0 +1,110 @@
+#![cfg(all(feature = "codegen", feature = "mock-runtime"))]
+
+extern crate objc;
+
+use std::sync::Once;
+
+use objc::codegen::Generator;
+use objc::declare::{ClassDecl, ProtocolDecl};
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel};
+
+// The wrappers generated for the classes and protocols declared below, which
+// must compile against the crate. Regenerate them by running this test with
+// OBJC_UPDATE_FIXTURES set.
+#[allow(dead_code)]
+mod generated {
+    include!("codegen/generated.rs");
+}
+
+use generated::{CodegenFixtureCounting, CodegenFixtureNaming};
+
+const FIXTURE: &str = "tests/codegen/generated.rs";
+
+fn declare() -> Generator {
+    static DECLARE: Once = Once::new();
+
+    extern fn name(_this: &Object, _cmd: Sel) -> *mut Object { std::ptr::null_mut() }
+    extern fn count(_this: &Object, _cmd: Sel) -> u32 { 3 }
+    extern fn set_count(_this: &mut Object, _cmd: Sel, _count: u32, _animated: bool) { }
+    extern fn perform(_this: &Object, _cmd: Sel, _sel: Sel, obj: *mut Object) -> *mut Object { obj }
+    extern fn make(cls: &Class, _cmd: Sel) -> *mut Object {
+        unsafe { msg_send![cls, new] }
+    }
+
+    DECLARE.call_once(|| {
+        let mut decl = ProtocolDecl::new("CodegenFixtureNaming").unwrap();
+        decl.add_method_description::<(), *mut Object>(sel!(name), true);
+        let naming = decl.register();
+
+        // Conforms to CodegenFixtureNaming, which becomes its supertrait
+        let mut decl = ProtocolDecl::new("CodegenFixtureCounting").unwrap();
+        decl.add_protocol(naming);
+        decl.add_method_description::<(), u32>(sel!(count), true);
+        decl.add_class_method_description::<(), *mut Object>(sel!(make), false);
+        let counting = decl.register();
+
+        let mut decl = ClassDecl::new("CodegenFixtureCounter", class!(NSObject)).unwrap();
+        decl.add_protocol(counting);
+        unsafe {
+            decl.add_method(sel!(name), name as extern fn(&Object, Sel) -> *mut Object);
+            decl.add_method(sel!(count), count as extern fn(&Object, Sel) -> u32);
+            decl.add_method(sel!(setCount:animated:),
+                set_count as extern fn(&mut Object, Sel, u32, bool));
+            decl.add_class_method(sel!(make), make as extern fn(&Class, Sel) -> *mut Object);
+        }
+        let counter = decl.register();
+
+        let mut decl = ClassDecl::new("CodegenFixtureView", counter).unwrap();
+        unsafe {
+            decl.add_method(sel!(performSelector::),
+                perform as extern fn(&Object, Sel, Sel, *mut Object) -> *mut Object);
+        }
+        decl.register();
+    });
+
+    let mut generator = Generator::new();
+    generator.add_class(class!(CodegenFixtureCounter));
+    generator.add_class(class!(CodegenFixtureView));
+    for &name in &["CodegenFixtureNaming", "CodegenFixtureCounting"] {
+        generator.add_protocol(objc::runtime::Protocol::get(name).unwrap());
+    }
+    generator
+}
+
+#[test]
+fn fixture_is_up_to_date() {
+    let source = declare().generate();
+    if std::env::var_os("OBJC_UPDATE_FIXTURES").is_some() {
+        std::fs::write(FIXTURE, &source).unwrap();
+    }
+    assert!(source == include_str!("codegen/generated.rs"),
+        "{} is out of date; run this test with OBJC_UPDATE_FIXTURES set", FIXTURE);
+}
+
+#[test]
+fn generated_wrappers_send_messages() {
+    use generated::{CodegenFixtureCounter, CodegenFixtureView};
+
+    declare();
+    unsafe {
+        let obj: *mut Object = msg_send![class!(CodegenFixtureView), new];
+        let view = &*(obj as *const CodegenFixtureView);
+        // Methods of the superclass are reached through Deref
+        assert_eq!(view.count(), 3);
+        view.set_count_animated(4, true);
+        // Protocol methods, including those of supertraits
+        assert_eq!(CodegenFixtureCounting::count(&**view), 3);
+        assert!(CodegenFixtureNaming::name(&**view).is_null());
+        // Selectors with empty labels
+        assert!(view.perform_selector(sel!(count), obj) == obj);
+        let _: () = msg_send![obj, release];
+
+        // Class methods, inherent and of protocols
+        for &obj in &[CodegenFixtureCounter::make(),
+                      <CodegenFixtureCounter as CodegenFixtureCounting>::make()] {
+            assert!((*obj).class() == class!(CodegenFixtureCounter));
+            let _: () = msg_send![obj, release];
+        }
+    }
+}
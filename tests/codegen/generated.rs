@@ -0,0 +1,119 @@
+// Generated from the Objective-C runtime by objc::codegen.
+
+/// The Objective-C protocol `CodegenFixtureCounting`.
+pub trait CodegenFixtureCounting: ::objc::Message + Sized + CodegenFixtureNaming {
+    /// `-[CodegenFixtureCounting count]`
+    unsafe fn count(&self) -> u32 {
+        ::objc::msg_send![self, count]
+    }
+    /// `+[CodegenFixtureCounting make]`
+    unsafe fn make() -> *mut ::objc::runtime::Object where Self: ::objc::runtime::ClassType {
+        ::objc::msg_send![<Self as ::objc::runtime::ClassType>::class(), make]
+    }
+}
+
+/// The Objective-C protocol `CodegenFixtureNaming`.
+pub trait CodegenFixtureNaming: ::objc::Message + Sized {
+    /// `-[CodegenFixtureNaming name]`
+    unsafe fn name(&self) -> *mut ::objc::runtime::Object {
+        ::objc::msg_send![self, name]
+    }
+}
+
+/// The Objective-C class `CodegenFixtureCounter`.
+#[repr(C)]
+pub struct CodegenFixtureCounter {
+    _priv: [u8; 0],
+}
+
+unsafe impl ::objc::Message for CodegenFixtureCounter { }
+
+unsafe impl ::objc::runtime::ClassType for CodegenFixtureCounter {
+    fn class() -> &'static ::objc::runtime::Class {
+        ::objc::class!(CodegenFixtureCounter)
+    }
+}
+
+unsafe impl ::objc::TypedObject for CodegenFixtureCounter {
+    const CLASS_NAME: &'static str = "CodegenFixtureCounter";
+}
+
+unsafe impl<'a> ::objc::Encode for &'a CodegenFixtureCounter {
+    const ENCODING: ::objc::Encoding<'static> = ::objc::Encoding::Object;
+}
+
+unsafe impl<'a> ::objc::Encode for &'a mut CodegenFixtureCounter {
+    const ENCODING: ::objc::Encoding<'static> = ::objc::Encoding::Object;
+}
+
+impl ::core::ops::Deref for CodegenFixtureCounter {
+    type Target = ::objc::runtime::Object;
+
+    fn deref(&self) -> &::objc::runtime::Object {
+        unsafe { &*(self as *const Self as *const ::objc::runtime::Object) }
+    }
+}
+
+impl CodegenFixtureCounter {
+    /// `-[CodegenFixtureCounter count]`
+    pub unsafe fn count(&self) -> u32 {
+        ::objc::msg_send![self, count]
+    }
+    /// `-[CodegenFixtureCounter name]`
+    pub unsafe fn name(&self) -> *mut ::objc::runtime::Object {
+        ::objc::msg_send![self, name]
+    }
+    /// `-[CodegenFixtureCounter setCount:animated:]`
+    pub unsafe fn set_count_animated(&self, set_count: u32, animated: bool) {
+        ::objc::msg_send![self, setCount: set_count animated: animated]
+    }
+    /// `+[CodegenFixtureCounter make]`
+    pub unsafe fn make() -> *mut ::objc::runtime::Object {
+        ::objc::msg_send![<Self as ::objc::runtime::ClassType>::class(), make]
+    }
+}
+
+impl CodegenFixtureCounting for CodegenFixtureCounter { }
+
+impl CodegenFixtureNaming for CodegenFixtureCounter { }
+
+/// The Objective-C class `CodegenFixtureView`.
+#[repr(C)]
+pub struct CodegenFixtureView {
+    _priv: [u8; 0],
+}
+
+unsafe impl ::objc::Message for CodegenFixtureView { }
+
+unsafe impl ::objc::runtime::ClassType for CodegenFixtureView {
+    fn class() -> &'static ::objc::runtime::Class {
+        ::objc::class!(CodegenFixtureView)
+    }
+}
+
+unsafe impl ::objc::TypedObject for CodegenFixtureView {
+    const CLASS_NAME: &'static str = "CodegenFixtureView";
+}
+
+unsafe impl<'a> ::objc::Encode for &'a CodegenFixtureView {
+    const ENCODING: ::objc::Encoding<'static> = ::objc::Encoding::Object;
+}
+
+unsafe impl<'a> ::objc::Encode for &'a mut CodegenFixtureView {
+    const ENCODING: ::objc::Encoding<'static> = ::objc::Encoding::Object;
+}
+
+impl ::core::ops::Deref for CodegenFixtureView {
+    type Target = CodegenFixtureCounter;
+
+    fn deref(&self) -> &CodegenFixtureCounter {
+        unsafe { &*(self as *const Self as *const CodegenFixtureCounter) }
+    }
+}
+
+impl CodegenFixtureView {
+    /// `-[CodegenFixtureView performSelector::]`
+    pub unsafe fn perform_selector(&self, perform_selector: ::objc::runtime::Sel, arg1: *mut ::objc::runtime::Object) -> *mut ::objc::runtime::Object {
+        ::objc::msg_send![self, performSelector: perform_selector : arg1]
+    }
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "derive")]
+
+extern crate objc;
+
+use std::marker::PhantomData;
+
+use objc::Encode;
+
+#[derive(Encode)]
+#[repr(C)]
+struct CGPoint {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Encode)]
+#[repr(C)]
+struct CGSize {
+    width: f64,
+    height: f64,
+}
+
+#[derive(Encode)]
+#[repr(C)]
+struct CGRect {
+    origin: CGPoint,
+    size: CGSize,
+}
+
+#[derive(Encode)]
+#[encode(name = "_NSRange")]
+#[repr(C)]
+struct NSRange {
+    location: usize,
+    length: usize,
+}
+
+#[derive(Encode)]
+#[repr(C)]
+struct Pair<T>(T, T);
+
+#[derive(Encode)]
+#[repr(C)]
+union Number {
+    int: i32,
+    float: f32,
+}
+
+#[derive(Encode)]
+#[repr(transparent)]
+struct Handle<T> {
+    raw: *mut u8,
+    marker: PhantomData<T>,
+}
+
+#[derive(Encode)]
+#[repr(isize)]
+#[allow(dead_code)]
+enum Alignment {
+    Left = 0,
+    Center = 1,
+    Right = 2,
+}
+
+#[derive(Encode)]
+#[repr(C)]
+#[allow(dead_code)]
+enum Style {
+    Plain,
+    Bold,
+}
+
+#[test]
+fn derive_struct() {
+    assert_eq!(CGPoint::ENCODING.to_string(), "{CGPoint=dd}");
+    assert_eq!(CGRect::ENCODING.to_string(), "{CGRect={CGPoint=dd}{CGSize=dd}}");
+    assert_eq!(NSRange::ENCODING.to_string(), "{_NSRange=QQ}");
+    assert_eq!(Pair::<u16>::ENCODING.to_string(), "{Pair=SS}");
+}
+
+#[test]
+fn derive_union() {
+    assert_eq!(Number::ENCODING.to_string(), "(Number=if)");
+}
+
+#[test]
+fn derive_transparent() {
+    assert_eq!(Handle::<CGPoint>::ENCODING.to_string(), "*");
+}
+
+#[test]
+fn derive_enum() {
+    assert_eq!(Alignment::ENCODING, isize::ENCODING);
+    assert_eq!(Style::ENCODING.to_string(), "i");
+}
@@ -0,0 +1,44 @@
+#![cfg(any(target_os = "macos", target_os = "ios"))]
+
+extern crate objc;
+
+use objc::foundation::{NSInteger, NSRange, NSUInteger, unichar};
+use objc::runtime::{Class, Sel};
+use objc::{class, sel, Encode};
+
+#[link(name = "Foundation", kind = "framework")]
+extern { }
+
+fn return_type(cls: &Class, sel: Sel) -> String {
+    cls.instance_method(sel).unwrap().return_type().to_string()
+}
+
+fn argument_type(cls: &Class, sel: Sel, index: usize) -> String {
+    cls.instance_method(sel).unwrap().argument_type(index).unwrap().to_string()
+}
+
+#[test]
+fn scalar_encodings() {
+    let string = class!(NSString);
+    assert_eq!(return_type(string, sel!(length)), NSUInteger::ENCODING.to_string());
+    assert_eq!(return_type(string, sel!(characterAtIndex:)), unichar::ENCODING.to_string());
+    assert_eq!(return_type(class!(NSNumber), sel!(integerValue)), NSInteger::ENCODING.to_string());
+}
+
+#[test]
+fn range_encoding() {
+    assert_eq!(return_type(class!(NSValue), sel!(rangeValue)), NSRange::ENCODING.to_string());
+    assert_eq!(argument_type(class!(NSString), sel!(substringWithRange:), 2),
+        NSRange::ENCODING.to_string());
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn geometry_encodings() {
+    use objc::foundation::{CGPoint, CGRect, CGSize};
+
+    let value = class!(NSValue);
+    assert_eq!(return_type(value, sel!(pointValue)), CGPoint::ENCODING.to_string());
+    assert_eq!(return_type(value, sel!(sizeValue)), CGSize::ENCODING.to_string());
+    assert_eq!(return_type(value, sel!(rectValue)), CGRect::ENCODING.to_string());
+}
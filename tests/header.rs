@@ -0,0 +1,143 @@
+//! Compiles a header exported from a `ClassDecl` with Objective-C code using
+//! the class, and links it against libobjc into a shared library.
+//! Ignored by default, since it needs an Objective-C compiler; run it with
+//! `cargo test --test header -- --ignored`, as travis_test.sh does on Linux. The compiler is `$OBJC`, or else
+//! `clang` or `gcc` if they can compile Objective-C. Extra flags, like the
+//! include paths for libobjc2, can be given in `$OBJCFLAGS`, and flags for
+//! linking, like its library path, in `$OBJCLDFLAGS`.
+#![cfg(all(feature = "std", not(feature = "objfw")))]
+
+extern crate objc;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use objc::declare::ClassDecl;
+use objc::header::Header;
+use objc::runtime::{Class, Object, Sel};
+use objc::{add_class_method, add_method};
+
+const SOURCE: &str = r#"#import "Counter.h"
+
+int counter_total(void) {
+    Counter *counter = [CounterClass() counterWithStart: 3];
+    counter.count = counter.count + 1;
+    [counter addAmount: 2 repeated: true];
+    return (int)counter.count;
+}
+"#;
+
+const PROBE: &str = "@class Probe;\n";
+
+fn compiler_flags(compiler: &str) -> Vec<String> {
+    if let Ok(flags) = env::var("OBJCFLAGS") {
+        return flags.split_whitespace().map(str::to_owned).collect();
+    }
+    if cfg!(any(target_os = "macos", target_os = "ios")) {
+        Vec::new()
+    } else if compiler.contains("clang") {
+        vec!["-fobjc-runtime=gnustep-2.0".to_owned()]
+    } else {
+        vec!["-fgnu-runtime".to_owned()]
+    }
+}
+
+fn compile(compiler: &str, source: &Path, dir: &Path) -> process::Output {
+    Command::new(compiler)
+        .args(compiler_flags(compiler))
+        .arg("-I").arg(dir)
+        .arg("-fPIC").arg("-c").arg(source)
+        .arg("-o").arg(source.with_extension("o"))
+        .output()
+        .unwrap()
+}
+
+fn link(compiler: &str, object: &Path) -> process::Output {
+    let (kind, library) = if cfg!(any(target_os = "macos", target_os = "ios")) {
+        ("-dynamiclib", "libcounter.dylib")
+    } else {
+        ("-shared", "libcounter.so")
+    };
+    let mut command = Command::new(compiler);
+    command.arg(kind).arg(object)
+        .arg("-o").arg(object.with_file_name(library));
+    if let Ok(flags) = env::var("OBJCLDFLAGS") {
+        command.args(flags.split_whitespace());
+    }
+    if !cfg!(any(target_os = "macos", target_os = "ios")) {
+        // Fail on symbols libobjc doesn't define instead of deferring them
+        command.arg("-Wl,--no-undefined");
+    }
+    command.arg("-lobjc").output().unwrap()
+}
+
+fn find_compiler(dir: &Path) -> Option<String> {
+    if let Ok(compiler) = env::var("OBJC") {
+        return Some(compiler);
+    }
+    let probe = dir.join("probe.m");
+    fs::write(&probe, PROBE).unwrap();
+    ["clang", "gcc"].iter()
+        .find(|compiler| {
+            let output = Command::new(compiler)
+                .args(compiler_flags(compiler))
+                .arg("-fsyntax-only").arg(&probe)
+                .output();
+            matches!(output, Ok(ref output) if output.status.success())
+        })
+        .map(|&compiler| compiler.to_owned())
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!("objc-header-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+extern fn initialize(_this: &Class, _cmd: Sel) { }
+extern fn counter_with_start(_this: &Class, _cmd: Sel, _start: usize) -> *mut Object {
+    std::ptr::null_mut()
+}
+extern fn count(_this: &Object, _cmd: Sel) -> usize { 0 }
+extern fn set_count(_this: &mut Object, _cmd: Sel, _count: usize) { }
+extern fn add_amount(_this: &mut Object, _cmd: Sel, _amount: usize, _repeated: bool) { }
+
+fn counter_header() -> Header {
+    let mut decl = ClassDecl::root("Counter", initialize).unwrap();
+    unsafe {
+        add_class_method!(decl, sel!(counterWithStart:),
+            counter_with_start as extern fn(&Class, Sel, usize) -> *mut Object);
+        add_method!(decl, sel!(count), count as extern fn(&Object, Sel) -> usize);
+        add_method!(decl, sel!(setCount:),
+            set_count as extern fn(&mut Object, Sel, usize));
+        add_method!(decl, sel!(addAmount:repeated:),
+            add_amount as extern fn(&mut Object, Sel, usize, bool));
+    }
+
+    let mut header = Header::new("COUNTER_H");
+    header.add_interface(decl.interface());
+    header
+}
+
+#[test]
+#[ignore]
+fn compile_exported_header() {
+    let dir = temp_dir();
+    let compiler = find_compiler(&dir)
+        .expect("no Objective-C compiler found; set OBJC to one");
+
+    counter_header().write_to_file(dir.join("Counter.h")).unwrap();
+    let source = dir.join("counter.m");
+    fs::write(&source, SOURCE).unwrap();
+
+    let output = compile(&compiler, &source, &dir);
+    assert!(output.status.success(), "{} failed to compile the header:\n{}",
+        compiler, String::from_utf8_lossy(&output.stderr));
+
+    let output = link(&compiler, &source.with_extension("o"));
+    assert!(output.status.success(), "{} failed to link against libobjc:\n{}",
+        compiler, String::from_utf8_lossy(&output.stderr));
+    fs::remove_dir_all(&dir).unwrap();
+}
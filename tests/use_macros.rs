@@ -19,5 +19,7 @@ fn use_class_and_msg_send() {
 fn use_sel() {
     let _sel = sel!(description);
     let _sel = sel!(setObject:forKey:);
+    let _sel = sel!(performSelector::);
+    let _sel = sel!("_setObject:forKey:");
 }
 
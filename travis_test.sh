@@ -5,6 +5,13 @@ set -eu
 if [ -z "$IOS_ARCHS" ]; then
     cargo build --verbose --features "$FEATURES"
     cargo test --verbose --features "$FEATURES"
+    if [ "$TRAVIS_OS_NAME" = "linux" ]; then
+        # Compile and link an exported header against the libobjc2 we built
+        OBJC=clang \
+        OBJCFLAGS="-fobjc-runtime=gnustep-1.9 -I$HOME/libobjc2_staging/include" \
+        OBJCLDFLAGS="-L$HOME/libobjc2_staging/lib" \
+            cargo test --verbose --features "$FEATURES" --test header -- --ignored
+    fi
 else
     ./rust-test-ios
 fi